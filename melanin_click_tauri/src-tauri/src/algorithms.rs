@@ -0,0 +1,379 @@
+use crate::native_miner::{self, Midstate};
+use crate::yespower::{self, Yespower};
+use crate::AppError;
+use blake2::{Blake2s256, Digest};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+// Difficulty 1 targets as big-endian 256-bit numbers
+pub const SHA256D_DIFF1: [u8; 32] = diff1_target(4);
+// Scrypt and Yespower pools, like cpuminer, scale difficulty 1 by 65536
+pub const SCRYPT_DIFF1: [u8; 32] = diff1_target(2);
+
+// 0xffff followed by zeros, starting at the given byte
+const fn diff1_target(leading_zero_bytes: usize) -> [u8; 32] {
+    let mut target = [0u8; 32];
+    target[leading_zero_bytes] = 0xff;
+    target[leading_zero_bytes + 1] = 0xff;
+    target
+}
+
+// A proof-of-work hash over 80 byte block headers. Instances may keep scratch
+// memory, so each mining thread creates its own from the registry.
+pub trait HashAlgorithm: Send {
+    // Name the algorithm is registered under
+    fn name(&self) -> &'static str;
+
+    fn hash_header(&mut self, header: &[u8; 80]) -> [u8; 32];
+
+    // Target a hash must not exceed at pool difficulty 1
+    fn diff1_target(&self) -> [u8; 32];
+
+    fn meets_target(&self, hash: &[u8; 32], target: &[u8; 32]) -> bool {
+        native_miner::meets_target(hash, target)
+    }
+
+    fn share_target(&self, difficulty: f64) -> [u8; 32] {
+        native_miner::scale_target(&self.diff1_target(), difficulty)
+    }
+
+    // Nonces to hash between checks for a newer job
+    fn nonce_batch(&self) -> u32 {
+        16
+    }
+
+    // Hashes `count` nonces from `start` and reports every hash at or below the target
+    fn scan(
+        &mut self,
+        header: &[u8; 80],
+        start: u32,
+        count: u32,
+        target: &[u8; 32],
+        found: &mut dyn FnMut(u32, [u8; 32]),
+    ) {
+        let mut header = *header;
+        for offset in 0..count {
+            let nonce = start.wrapping_add(offset);
+            header[76..].copy_from_slice(&nonce.to_le_bytes());
+            let hash = self.hash_header(&header);
+            if self.meets_target(&hash, target) {
+                found(nonce, hash);
+            }
+        }
+    }
+}
+
+pub struct Sha256d;
+
+impl HashAlgorithm for Sha256d {
+    fn name(&self) -> &'static str {
+        "sha256d"
+    }
+
+    fn hash_header(&mut self, header: &[u8; 80]) -> [u8; 32] {
+        native_miner::sha256d(header)
+    }
+
+    fn diff1_target(&self) -> [u8; 32] {
+        SHA256D_DIFF1
+    }
+
+    fn nonce_batch(&self) -> u32 {
+        0x10000
+    }
+
+    // Reuses the SHA-256 state of the first 64 header bytes across nonces
+    fn scan(
+        &mut self,
+        header: &[u8; 80],
+        start: u32,
+        count: u32,
+        target: &[u8; 32],
+        found: &mut dyn FnMut(u32, [u8; 32]),
+    ) {
+        Midstate::new(header).scan(start, count, target, found);
+    }
+}
+
+// Yespower 1.0 with Whive's parameters
+pub struct YespowerWhive(Yespower);
+
+impl HashAlgorithm for YespowerWhive {
+    fn name(&self) -> &'static str {
+        "yespower"
+    }
+
+    fn hash_header(&mut self, header: &[u8; 80]) -> [u8; 32] {
+        self.0.hash(header)
+    }
+
+    fn diff1_target(&self) -> [u8; 32] {
+        SCRYPT_DIFF1
+    }
+}
+
+// Litecoin-style scrypt: N=1024, r=1, p=1 with the header as password and salt
+pub struct Scrypt(scrypt::Params);
+
+impl HashAlgorithm for Scrypt {
+    fn name(&self) -> &'static str {
+        "scrypt"
+    }
+
+    fn hash_header(&mut self, header: &[u8; 80]) -> [u8; 32] {
+        let mut hash = [0u8; 32];
+        scrypt::scrypt(header, header, &self.0, &mut hash)
+            .expect("32 bytes is a valid scrypt output length");
+        hash
+    }
+
+    fn diff1_target(&self) -> [u8; 32] {
+        SCRYPT_DIFF1
+    }
+}
+
+// Single BLAKE2s-256 of the header
+pub struct Blake2s;
+
+impl HashAlgorithm for Blake2s {
+    fn name(&self) -> &'static str {
+        "blake2s"
+    }
+
+    fn hash_header(&mut self, header: &[u8; 80]) -> [u8; 32] {
+        Blake2s256::digest(header).into()
+    }
+
+    fn diff1_target(&self) -> [u8; 32] {
+        SHA256D_DIFF1
+    }
+
+    fn nonce_batch(&self) -> u32 {
+        0x10000
+    }
+}
+
+pub type AlgorithmFactory = fn() -> Box<dyn HashAlgorithm>;
+
+struct Registration {
+    name: &'static str,
+    // Value for the -a flag of external cpuminer builds
+    miner_flag: &'static str,
+    factory: AlgorithmFactory,
+}
+
+lazy_static::lazy_static! {
+    // Algorithms by normalized name or alias
+    static ref REGISTRY: RwLock<HashMap<String, Registration>> = RwLock::new(builtin_algorithms());
+}
+
+fn builtin_algorithms() -> HashMap<String, Registration> {
+    let builtins: [(&'static str, &[&str], AlgorithmFactory); 4] = [
+        ("sha256d", &["sha256"], || Box::new(Sha256d)),
+        ("yespower", &[], || {
+            Box::new(YespowerWhive(
+                Yespower::new(yespower::WHIVE).expect("Whive parameters are valid"),
+            ))
+        }),
+        ("scrypt", &[], || {
+            Box::new(Scrypt(
+                scrypt::Params::new(10, 1, 1, 32).expect("Litecoin parameters are valid"),
+            ))
+        }),
+        ("blake2s", &[], || Box::new(Blake2s)),
+    ];
+
+    let mut registry = HashMap::new();
+    for (name, aliases, factory) in builtins {
+        insert(&mut registry, name, aliases, name, factory);
+    }
+    registry
+}
+
+// "SHA-256 (ASIC)" and "Yespower (native)" style labels reduce to "sha256" and "yespower"
+fn normalize_name(name: &str) -> String {
+    let base = name.split('(').next().unwrap_or(name);
+    base.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+// Keys every registration by the normalized form of its name and aliases, the
+// same form lookups normalize to
+fn insert(
+    registry: &mut HashMap<String, Registration>,
+    name: &'static str,
+    aliases: &[&str],
+    miner_flag: &'static str,
+    factory: AlgorithmFactory,
+) {
+    for key in aliases.iter().chain(std::iter::once(&name)) {
+        let registration = Registration {
+            name,
+            miner_flag,
+            factory,
+        };
+        registry.insert(normalize_name(key), registration);
+    }
+}
+
+// Adds or replaces an algorithm, reachable by its name and any aliases
+pub fn register(
+    name: &'static str,
+    aliases: &[&str],
+    miner_flag: &'static str,
+    factory: AlgorithmFactory,
+) {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    insert(&mut registry, name, aliases, miner_flag, factory);
+}
+
+// Registered name for a name, alias or display label
+pub fn lookup(name: &str) -> Result<&'static str, AppError> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&normalize_name(name))
+        .map(|registration| registration.name)
+        .ok_or_else(|| AppError::Mining(format!("Unsupported mining algorithm: {}", name)))
+}
+
+// cpuminer -a value for an algorithm, without building its hasher
+pub fn miner_flag(name: &str) -> Result<&'static str, AppError> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&normalize_name(name))
+        .map(|registration| registration.miner_flag)
+        .ok_or_else(|| AppError::Mining(format!("Unsupported mining algorithm: {}", name)))
+}
+
+pub fn create(name: &str) -> Result<Box<dyn HashAlgorithm>, AppError> {
+    let factory = REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&normalize_name(name))
+        .map(|registration| registration.factory)
+        .ok_or_else(|| AppError::Mining(format!("Unsupported mining algorithm: {}", name)))?;
+    Ok(factory())
+}
+
+pub fn registered_algorithms() -> Vec<&'static str> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    let mut names: Vec<_> = registry.values().map(|r| r.name).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference_header() -> [u8; 80] {
+        std::array::from_fn(|i| (i * 3) as u8)
+    }
+
+    #[test]
+    fn test_lookup_normalizes_labels() {
+        assert_eq!(lookup("SHA-256d").unwrap(), "sha256d");
+        assert_eq!(lookup("SHA-256 (ASIC)").unwrap(), "sha256d");
+        assert_eq!(lookup("Yespower (native)").unwrap(), "yespower");
+        assert_eq!(lookup("scrypt").unwrap(), "scrypt");
+        assert!(lookup("ethash").is_err());
+        assert!(create("ethash").is_err());
+        assert!(miner_flag("ethash").is_err());
+        assert_eq!(miner_flag("Yespower (native)").unwrap(), "yespower");
+
+        for name in ["blake2s", "scrypt", "sha256d", "yespower"] {
+            assert!(registered_algorithms().contains(&name));
+        }
+
+        // Built-in keys are stored normalized, exactly like registered ones
+        for key in builtin_algorithms().keys() {
+            assert_eq!(&normalize_name(key), key);
+        }
+    }
+
+    #[test]
+    fn test_header_known_answers() {
+        let header = reference_header();
+        let cases = [
+            (
+                "sha256d",
+                "b26ab2a5bb5b411b16cb0c3d20f2e2b7df05a3e5e14a7cc415c3da3a5ee6adce",
+            ),
+            (
+                "scrypt",
+                "345a235bb0c3bddfe163a9719576aa5303966a8810bbdbbdd80263a253150784",
+            ),
+            (
+                "blake2s",
+                "4b8c7ce32cbc1f6632d643f689ecdbd30a0f0bd2300d1a499fd72d817af952e7",
+            ),
+            (
+                "yespower",
+                "3e132ee33d9eb900c1782e4b67beb9722b36a734ab7c80f16eb005b54eeb034c",
+            ),
+        ];
+        for (name, expected) in cases {
+            let mut algorithm = create(name).unwrap();
+            assert_eq!(algorithm.name(), name);
+            assert_eq!(hex::encode(algorithm.hash_header(&header)), expected);
+        }
+    }
+
+    #[test]
+    fn test_scan_matches_hash_header() {
+        let header = reference_header();
+        for name in ["sha256d", "blake2s", "scrypt"] {
+            let mut algorithm = create(name).unwrap();
+            let mut found = Vec::new();
+            algorithm.scan(&header, u32::MAX - 1, 3, &[0xff; 32], &mut |nonce, hash| {
+                found.push((nonce, hash))
+            });
+
+            let nonces: Vec<u32> = found.iter().map(|(nonce, _)| *nonce).collect();
+            assert_eq!(nonces, vec![u32::MAX - 1, u32::MAX, 0], "{}", name);
+            for (nonce, hash) in found {
+                let mut expected = header;
+                expected[76..].copy_from_slice(&nonce.to_le_bytes());
+                assert_eq!(hash, algorithm.hash_header(&expected), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_share_target_scales_diff1() {
+        let sha256d = create("sha256d").unwrap();
+        let yespower = create("yespower").unwrap();
+        assert_eq!(sha256d.share_target(1.0), SHA256D_DIFF1);
+        assert_eq!(yespower.share_target(1.0), SCRYPT_DIFF1);
+        assert_eq!(yespower.share_target(65536.0), SHA256D_DIFF1);
+    }
+
+    #[test]
+    fn test_register_custom_algorithm() {
+        struct Identity;
+        impl HashAlgorithm for Identity {
+            fn name(&self) -> &'static str {
+                "identity"
+            }
+            fn hash_header(&mut self, header: &[u8; 80]) -> [u8; 32] {
+                header[..32].try_into().unwrap()
+            }
+            fn diff1_target(&self) -> [u8; 32] {
+                [0xff; 32]
+            }
+        }
+
+        register("identity", &["Identity Hash"], "ident", || {
+            Box::new(Identity)
+        });
+        assert_eq!(lookup("identity-hash").unwrap(), "identity");
+        assert_eq!(miner_flag("Identity Hash").unwrap(), "ident");
+        let mut algorithm = create("identity").unwrap();
+        assert_eq!(algorithm.hash_header(&[7; 80]), [7; 32]);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::algorithms;
+use crate::benchmark::{self, BenchmarkConfig};
+use crate::monitoring::get_cpu_temperature;
+use crate::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use sysinfo::System;
+use tracing::info;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutotuneConfig {
+    // Thread counts to try; empty means powers of two up to all logical CPUs
+    pub thread_counts: Vec<usize>,
+    // Duty cycles to try, in percent
+    pub intensities: Vec<u8>,
+    pub warmup_secs: f64,
+    pub duration_secs: f64,
+    // Settings that run the CPU hotter than this are not chosen
+    pub max_temperature: f64,
+}
+
+impl Default for AutotuneConfig {
+    fn default() -> Self {
+        Self {
+            thread_counts: Vec::new(),
+            intensities: vec![50, 75, 100],
+            warmup_secs: 1.0,
+            duration_secs: 3.0,
+            max_temperature: 80.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrialResult {
+    pub threads: usize,
+    pub intensity: u8,
+    pub hashrate: f64,
+    pub temperature: Option<f64>,
+}
+
+// Best measured setting for one algorithm on one machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunedSetting {
+    pub machine_id: String,
+    pub algorithm: String,
+    pub threads: usize,
+    pub intensity: u8,
+    pub hashrate: f64,
+    pub temperature: Option<f64>,
+    pub tuned_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn default_thread_counts() -> Vec<usize> {
+    let cpus = num_cpus::get();
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |count| Some(count * 2))
+        .take_while(|&count| count < cpus)
+        .collect();
+    counts.push(cpus);
+    counts
+}
+
+// Fastest trial within the temperature limit, or the coolest one if all run too hot.
+// Trials without a temperature reading count as within the limit.
+pub fn choose_best(trials: &[TrialResult], max_temperature: f64) -> Option<&TrialResult> {
+    let within_limit = trials
+        .iter()
+        .filter(|trial| trial.temperature.is_none_or(|t| t <= max_temperature))
+        .max_by(|a, b| a.hashrate.total_cmp(&b.hashrate));
+
+    within_limit.or_else(|| {
+        trials.iter().min_by(|a, b| {
+            let a = a.temperature.unwrap_or(f64::MAX);
+            let b = b.temperature.unwrap_or(f64::MAX);
+            a.total_cmp(&b)
+        })
+    })
+}
+
+// Sweeps thread counts and duty cycles for an algorithm and returns the best setting
+pub async fn autotune(algorithm: &str, config: &AutotuneConfig) -> Result<TunedSetting, AppError> {
+    let algorithm = algorithms::lookup(algorithm)?;
+    let thread_counts = if config.thread_counts.is_empty() {
+        default_thread_counts()
+    } else {
+        config.thread_counts.clone()
+    };
+
+    let mut trials = Vec::new();
+    for &threads in &thread_counts {
+        for &intensity in &config.intensities {
+            let benchmark_config = BenchmarkConfig {
+                algorithms: vec![algorithm.to_string()],
+                thread_counts: vec![threads],
+                warmup_secs: config.warmup_secs,
+                duration_secs: config.duration_secs,
+                samples: 1,
+                intensity,
+            };
+            let result = tokio::task::spawn_blocking(move || {
+                benchmark::benchmark_algorithm(algorithm, threads, &benchmark_config)
+            })
+            .await
+            .map_err(|e| AppError::Mining(format!("Autotune trial failed: {e}")))??;
+
+            // Read right after the run, while the CPU is still at its loaded temperature
+            let trial = TrialResult {
+                threads,
+                intensity,
+                hashrate: result.total_hashrate,
+                temperature: get_cpu_temperature().await,
+            };
+            info!(
+                "Autotune {}: {} threads at {}% -> {:.1} H/s, {:?} C",
+                algorithm, threads, intensity, trial.hashrate, trial.temperature
+            );
+            trials.push(trial);
+        }
+    }
+
+    let best = choose_best(&trials, config.max_temperature)
+        .ok_or_else(|| AppError::Validation("Autotune needs at least one trial".to_string()))?;
+
+    Ok(TunedSetting {
+        machine_id: machine_id(),
+        algorithm: algorithm.to_string(),
+        threads: best.threads,
+        intensity: best.intensity,
+        hashrate: best.hashrate,
+        temperature: best.temperature,
+        tuned_at: chrono::Utc::now(),
+    })
+}
+
+// Identifies the hardware a setting was tuned on
+pub fn machine_id() -> String {
+    let mut sys = System::new();
+    sys.refresh_cpu();
+    let cpu_brand = sys
+        .cpus()
+        .first()
+        .map(|cpu| cpu.brand().trim().to_string())
+        .unwrap_or_default();
+
+    format!(
+        "{}/{}/{}",
+        System::host_name().unwrap_or_else(|| "unknown".to_string()),
+        cpu_brand,
+        num_cpus::get()
+    )
+}
+
+fn settings_path() -> Option<PathBuf> {
+    Some(
+        dirs::home_dir()?
+            .join(".melanin_click")
+            .join("autotune.json"),
+    )
+}
+
+pub fn load_settings(path: &Path) -> Vec<TunedSetting> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+// Stores a setting, replacing any earlier one for the same machine and algorithm
+pub fn store_setting(path: &Path, setting: &TunedSetting) -> Result<(), AppError> {
+    let mut settings = load_settings(path);
+    settings.retain(|saved| {
+        saved.machine_id != setting.machine_id || saved.algorithm != setting.algorithm
+    });
+    settings.push(setting.clone());
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&settings)?)?;
+    Ok(())
+}
+
+pub fn find_setting(path: &Path, machine_id: &str, algorithm: &str) -> Option<TunedSetting> {
+    let algorithm = algorithms::lookup(algorithm).ok()?;
+    load_settings(path)
+        .into_iter()
+        .find(|saved| saved.machine_id == machine_id && saved.algorithm == algorithm)
+}
+
+// Saved setting for this machine, used when a start does not pass threads or intensity
+pub fn tuned_setting(algorithm: &str) -> Option<TunedSetting> {
+    find_setting(&settings_path()?, &machine_id(), algorithm)
+}
+
+// Runs the autotuner and saves its choice for later starts
+#[tauri::command]
+pub async fn autotune_mining(
+    algorithm: String,
+    config: Option<AutotuneConfig>,
+) -> Result<TunedSetting, AppError> {
+    let setting = autotune(&algorithm, &config.unwrap_or_default()).await?;
+    let path = settings_path()
+        .ok_or_else(|| AppError::Config("Could not find home directory".to_string()))?;
+    store_setting(&path, &setting)?;
+
+    info!(
+        "Saved autotuned {} setting: {} threads at {}%",
+        setting.algorithm, setting.threads, setting.intensity
+    );
+    Ok(setting)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trial(
+        threads: usize,
+        intensity: u8,
+        hashrate: f64,
+        temperature: Option<f64>,
+    ) -> TrialResult {
+        TrialResult {
+            threads,
+            intensity,
+            hashrate,
+            temperature,
+        }
+    }
+
+    #[test]
+    fn test_choose_best_respects_temperature_limit() {
+        let trials = vec![
+            trial(1, 100, 100.0, Some(60.0)),
+            trial(4, 75, 350.0, Some(78.0)),
+            trial(4, 100, 400.0, Some(85.0)),
+        ];
+        assert_eq!(choose_best(&trials, 80.0), Some(&trials[1]));
+        assert_eq!(choose_best(&trials, 90.0), Some(&trials[2]));
+
+        // Everything too hot: take the coolest
+        assert_eq!(choose_best(&trials, 50.0), Some(&trials[0]));
+
+        // No sensor: fastest wins
+        let unknown = vec![trial(1, 100, 100.0, None), trial(2, 100, 190.0, None)];
+        assert_eq!(choose_best(&unknown, 80.0), Some(&unknown[1]));
+        assert_eq!(choose_best(&[], 80.0), None);
+    }
+
+    #[test]
+    fn test_settings_saved_per_machine_and_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".melanin_click").join("autotune.json");
+        let setting = |machine: &str, algorithm: &str, threads| TunedSetting {
+            machine_id: machine.to_string(),
+            algorithm: algorithm.to_string(),
+            threads,
+            intensity: 75,
+            hashrate: 1000.0,
+            temperature: Some(70.0),
+            tuned_at: chrono::Utc::now(),
+        };
+
+        store_setting(&path, &setting("laptop", "yespower", 2)).unwrap();
+        store_setting(&path, &setting("laptop", "sha256d", 8)).unwrap();
+        store_setting(&path, &setting("desktop", "yespower", 16)).unwrap();
+        store_setting(&path, &setting("laptop", "yespower", 4)).unwrap();
+
+        assert_eq!(load_settings(&path).len(), 3);
+        let found = find_setting(&path, "laptop", "Yespower").unwrap();
+        assert_eq!(found.threads, 4);
+        assert_eq!(
+            find_setting(&path, "desktop", "yespower").unwrap().threads,
+            16
+        );
+        assert!(find_setting(&path, "desktop", "sha256d").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_autotune_sweeps_every_combination() {
+        let config = AutotuneConfig {
+            thread_counts: vec![1, 2],
+            intensities: vec![50, 100],
+            warmup_secs: 0.0,
+            duration_secs: 0.1,
+            max_temperature: 1000.0,
+        };
+        let setting = autotune("sha256d", &config).await.unwrap();
+        assert_eq!(setting.algorithm, "sha256d");
+        assert!([1, 2].contains(&setting.threads));
+        assert!([50, 100].contains(&setting.intensity));
+        assert!(setting.hashrate > 0.0);
+        assert_eq!(setting.machine_id, machine_id());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::algorithms::{self, HashAlgorithm};
+use crate::throttle::DutyCycle;
+use crate::AppError;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+
+// Upper bound on nonces hashed between clock checks, so slow hashes still stop on time
+const MAX_BATCH: u32 = 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BenchmarkConfig {
+    // Registered algorithm names; empty means every registered algorithm
+    pub algorithms: Vec<String>,
+    // Thread counts to measure; empty means 1, half and all logical CPUs
+    pub thread_counts: Vec<usize>,
+    pub warmup_secs: f64,
+    pub duration_secs: f64,
+    // Measurement windows the duration is split into for the variance
+    pub samples: usize,
+    // Percentage of each duty period spent hashing
+    pub intensity: u8,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            algorithms: Vec::new(),
+            thread_counts: Vec::new(),
+            warmup_secs: 1.0,
+            duration_secs: 5.0,
+            samples: 5,
+            intensity: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub algorithm: String,
+    pub threads: usize,
+    pub intensity: u8,
+    pub total_hashrate: f64,
+    pub per_thread_hashrate: Vec<f64>,
+    // Variance of the total H/s across measurement windows
+    pub variance: f64,
+    pub samples: usize,
+}
+
+impl BenchmarkConfig {
+    fn resolved_algorithms(&self) -> Result<Vec<&'static str>, AppError> {
+        if self.algorithms.is_empty() {
+            return Ok(algorithms::registered_algorithms());
+        }
+        self.algorithms
+            .iter()
+            .map(|name| algorithms::lookup(name))
+            .collect()
+    }
+
+    fn resolved_thread_counts(&self) -> Vec<usize> {
+        let mut counts = if self.thread_counts.is_empty() {
+            let cpus = num_cpus::get();
+            vec![1, cpus / 2, cpus]
+        } else {
+            self.thread_counts.clone()
+        };
+        counts.retain(|&count| count > 0);
+        counts.sort_unstable();
+        counts.dedup();
+        counts
+    }
+}
+
+// Benchmarks every configured algorithm at every thread count; blocks for the whole run
+pub fn run_benchmarks(config: &BenchmarkConfig) -> Result<Vec<BenchmarkResult>, AppError> {
+    if config.duration_secs <= 0.0 || config.warmup_secs < 0.0 {
+        return Err(AppError::Validation(
+            "Benchmark duration must be positive and warm-up not negative".to_string(),
+        ));
+    }
+    if !(1..=100).contains(&config.intensity) {
+        return Err(AppError::Validation(
+            "Benchmark intensity must be between 1 and 100".to_string(),
+        ));
+    }
+
+    let mut results = Vec::new();
+    for algorithm in config.resolved_algorithms()? {
+        for threads in config.resolved_thread_counts() {
+            results.push(benchmark_algorithm(algorithm, threads, config)?);
+        }
+    }
+    Ok(results)
+}
+
+pub fn benchmark_algorithm(
+    algorithm: &'static str,
+    threads: usize,
+    config: &BenchmarkConfig,
+) -> Result<BenchmarkResult, AppError> {
+    let samples = config.samples.max(1);
+    let warmup = Duration::from_secs_f64(config.warmup_secs);
+    let window = Duration::from_secs_f64(config.duration_secs / samples as f64);
+    let threads = threads.max(1);
+    let intensity = config.intensity;
+
+    tracing::info!(
+        "Benchmarking {} on {} threads at {}% for {:.1}s",
+        algorithm,
+        threads,
+        config.intensity,
+        config.duration_secs
+    );
+
+    // Hashers are created up front so no thread is left waiting on the barrier
+    let hashers = (0..threads)
+        .map(|_| algorithms::create(algorithm))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Every thread starts measuring together so their windows line up
+    let barrier = Arc::new(Barrier::new(threads));
+    let handles: Vec<_> = hashers
+        .into_iter()
+        .enumerate()
+        .map(|(index, hasher)| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                benchmark_thread(
+                    hasher,
+                    index as u32,
+                    intensity,
+                    warmup,
+                    window,
+                    samples,
+                    &barrier,
+                )
+            })
+        })
+        .collect();
+
+    let mut thread_windows = Vec::with_capacity(threads);
+    for handle in handles {
+        let windows = handle
+            .join()
+            .map_err(|_| AppError::Mining(format!("{} benchmark thread panicked", algorithm)))?;
+        thread_windows.push(windows);
+    }
+
+    let per_thread_hashrate: Vec<f64> = thread_windows
+        .iter()
+        .map(|windows| windows.iter().sum::<f64>() / windows.len() as f64)
+        .collect();
+    let window_totals: Vec<f64> = (0..samples)
+        .map(|sample| thread_windows.iter().map(|windows| windows[sample]).sum())
+        .collect();
+
+    Ok(BenchmarkResult {
+        algorithm: algorithm.to_string(),
+        threads,
+        intensity: config.intensity,
+        total_hashrate: per_thread_hashrate.iter().sum(),
+        per_thread_hashrate,
+        variance: variance(&window_totals),
+        samples,
+    })
+}
+
+// Hashes a header on one thread and returns its H/s in each measurement window,
+// counting the time slept between busy periods
+fn benchmark_thread(
+    mut hasher: Box<dyn HashAlgorithm>,
+    thread_index: u32,
+    intensity: u8,
+    warmup: Duration,
+    window: Duration,
+    samples: usize,
+    barrier: &Barrier,
+) -> Vec<f64> {
+    let batch = hasher.nonce_batch().clamp(1, MAX_BATCH);
+    let mut header = [0u8; 80];
+    header[..4].copy_from_slice(&thread_index.to_le_bytes());
+    // An all-zero target is never met, so every hash goes through the full comparison
+    let target = [0u8; 32];
+    let mut nonce = 0u32;
+
+    let mut hash_for = |duration: Duration| {
+        let started = Instant::now();
+        let mut duty = DutyCycle::new();
+        let mut hashes = 0u64;
+        while started.elapsed() < duration {
+            hasher.scan(&header, nonce, batch, &target, &mut |_, _| {});
+            nonce = nonce.wrapping_add(batch);
+            hashes += batch as u64;
+            duty.pace(intensity);
+        }
+        hashes as f64 / started.elapsed().as_secs_f64()
+    };
+
+    barrier.wait();
+    hash_for(warmup);
+    barrier.wait();
+    (0..samples).map(|_| hash_for(window)).collect()
+}
+
+fn variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / (values.len() - 1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quick_config(algorithms: &[&str], thread_counts: &[usize]) -> BenchmarkConfig {
+        BenchmarkConfig {
+            algorithms: algorithms.iter().map(|name| name.to_string()).collect(),
+            thread_counts: thread_counts.to_vec(),
+            warmup_secs: 0.02,
+            duration_secs: 0.15,
+            samples: 3,
+            intensity: 100,
+        }
+    }
+
+    #[test]
+    fn test_measures_each_algorithm_and_thread_count() {
+        let config = quick_config(&["SHA-256d", "blake2s"], &[2, 1, 2, 0]);
+        let results = run_benchmarks(&config).unwrap();
+
+        let runs: Vec<_> = results
+            .iter()
+            .map(|result| (result.algorithm.as_str(), result.threads))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                ("sha256d", 1),
+                ("sha256d", 2),
+                ("blake2s", 1),
+                ("blake2s", 2)
+            ]
+        );
+
+        for result in results {
+            assert_eq!(result.per_thread_hashrate.len(), result.threads);
+            assert!(result.per_thread_hashrate.iter().all(|&rate| rate > 0.0));
+            let sum: f64 = result.per_thread_hashrate.iter().sum();
+            assert!((result.total_hashrate - sum).abs() < 1e-6);
+            assert!(result.variance >= 0.0);
+            assert_eq!(result.samples, 3);
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_config() {
+        assert!(run_benchmarks(&quick_config(&["ethash"], &[1])).is_err());
+
+        let mut config = quick_config(&["sha256d"], &[1]);
+        config.duration_secs = 0.0;
+        assert!(run_benchmarks(&config).is_err());
+
+        let mut config = quick_config(&["sha256d"], &[1]);
+        config.intensity = 0;
+        assert!(run_benchmarks(&config).is_err());
+    }
+
+    #[test]
+    fn test_intensity_limits_hashing_time() {
+        let mut config = quick_config(&["sha256d"], &[1]);
+        config.duration_secs = 0.3;
+        let full = run_benchmarks(&config).unwrap()[0].total_hashrate;
+
+        config.intensity = 25;
+        let throttled = run_benchmarks(&config).unwrap()[0].total_hashrate;
+        assert!(throttled < full * 0.6, "{} vs {}", throttled, full);
+    }
+
+    #[test]
+    fn test_variance() {
+        assert_eq!(variance(&[5.0]), 0.0);
+        assert_eq!(variance(&[2.0, 4.0, 6.0]), 4.0);
+    }
+}
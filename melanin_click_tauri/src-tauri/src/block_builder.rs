@@ -0,0 +1,610 @@
+use crate::native_miner::{meets_target, sha256d};
+use crate::solo_mining::BlockTemplate;
+use crate::AppError;
+
+// Value the witness commitment is computed with; carried as the coinbase witness (BIP141)
+const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
+const WITNESS_COMMITMENT_HEADER: [u8; 4] = [0xaa, 0x21, 0xa9, 0xed];
+// Consensus limit on the size of the coinbase script
+const MAX_COINBASE_SCRIPT_SIZE: usize = 100;
+
+const OP_0: u8 = 0x00;
+const OP_1: u8 = 0x51;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_RETURN: u8 = 0x6a;
+
+// Base58 version bytes of Bitcoin mainnet, Bitcoin testnet and regtest, and Whive
+const P2PKH_VERSIONS: [u8; 3] = [0x00, 0x6f, 0x49];
+const P2SH_VERSIONS: [u8; 3] = [0x05, 0xc4, 0x07];
+
+// Output script paying a base58 (P2PKH, P2SH) or bech32/bech32m (segwit) address
+pub fn address_script(address: &str) -> Result<Vec<u8>, AppError> {
+    if let Ok((_, version, program)) = bech32::segwit::decode(address) {
+        let version = version.to_u8();
+        let mut script = vec![if version == 0 {
+            OP_0
+        } else {
+            OP_1 + version - 1
+        }];
+        push_data(&mut script, &program);
+        return Ok(script);
+    }
+
+    let invalid = || AppError::Validation(format!("Cannot pay to address {address}"));
+    let decoded = bs58::decode(address).into_vec().map_err(|_| invalid())?;
+    if decoded.len() != 25 || sha256d(&decoded[..21])[..4] != decoded[21..] {
+        return Err(invalid());
+    }
+    let hash = &decoded[1..21];
+    let mut script = Vec::with_capacity(25);
+    if P2PKH_VERSIONS.contains(&decoded[0]) {
+        script.extend([OP_DUP, OP_HASH160]);
+        push_data(&mut script, hash);
+        script.extend([OP_EQUALVERIFY, OP_CHECKSIG]);
+    } else if P2SH_VERSIONS.contains(&decoded[0]) {
+        script.push(OP_HASH160);
+        push_data(&mut script, hash);
+        script.push(OP_EQUAL);
+    } else {
+        return Err(invalid());
+    }
+    Ok(script)
+}
+
+// Merkle root of transaction hashes in internal byte order; an odd level repeats its last hash
+pub fn merkle_root(hashes: &[[u8; 32]]) -> [u8; 32] {
+    let mut level = hashes.to_vec();
+    if level.is_empty() {
+        return [0; 32];
+    }
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| sha256d(&[&pair[0][..], &pair[1][..]].concat()))
+            .collect();
+    }
+    level[0]
+}
+
+// Coinbase output script committing to the witness txids of the non-coinbase transactions
+pub fn witness_commitment_script(wtxids: &[[u8; 32]]) -> Vec<u8> {
+    // The coinbase itself counts with a zero witness txid
+    let mut hashes = vec![[0; 32]];
+    hashes.extend_from_slice(wtxids);
+    let commitment = sha256d(&[&merkle_root(&hashes)[..], &WITNESS_RESERVED_VALUE[..]].concat());
+
+    let mut script = vec![OP_RETURN];
+    push_data(
+        &mut script,
+        &[&WITNESS_COMMITMENT_HEADER[..], &commitment[..]].concat(),
+    );
+    script
+}
+
+// A block assembled from a template, ready to be hashed and submitted
+#[derive(Debug, Clone)]
+pub struct BlockCandidate {
+    pub height: u64,
+    header: [u8; 80],
+    // Block target as a big-endian 256-bit number
+    target: [u8; 32],
+    // Serialized with witness data when the block carries a witness commitment
+    coinbase: Vec<u8>,
+    transactions: Vec<Vec<u8>>,
+}
+
+impl BlockCandidate {
+    // `extranonce` goes into the coinbase script, so each value gives a new merkle root
+    pub fn new(
+        template: &BlockTemplate,
+        payout_script: &[u8],
+        extranonce: u64,
+    ) -> Result<Self, AppError> {
+        let transactions = template
+            .transactions
+            .iter()
+            .map(|tx| tx.bytes())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut txids = template
+            .transactions
+            .iter()
+            .map(|tx| decode_hash(&tx.txid))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let commitment = match &template.default_witness_commitment {
+            Some(script) => Some(decode_hex(script)?),
+            None if template.has_witness_data() => {
+                let wtxids = template
+                    .transactions
+                    .iter()
+                    .map(|tx| decode_hash(&tx.hash))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some(witness_commitment_script(&wtxids))
+            }
+            None => None,
+        };
+        let (coinbase, coinbase_txid) =
+            build_coinbase(template, payout_script, extranonce, commitment.as_deref())?;
+        txids.insert(0, coinbase_txid);
+
+        let mut header = [0u8; 80];
+        header[0..4].copy_from_slice(&template.version.to_le_bytes());
+        header[4..36].copy_from_slice(&decode_hash(&template.previous_block_hash)?);
+        header[36..68].copy_from_slice(&merkle_root(&txids));
+        header[68..72].copy_from_slice(&(template.cur_time as u32).to_le_bytes());
+        header[72..76].copy_from_slice(&parse_bits(&template.bits)?.to_le_bytes());
+
+        let target = decode_hex(&template.target)?.try_into().map_err(|_| {
+            AppError::Mining(format!("Invalid template target: {}", template.target))
+        })?;
+
+        Ok(Self {
+            height: template.height,
+            header,
+            target,
+            coinbase,
+            transactions,
+        })
+    }
+
+    pub fn header(&self) -> &[u8; 80] {
+        &self.header
+    }
+
+    pub fn target(&self) -> &[u8; 32] {
+        &self.target
+    }
+
+    pub fn set_time(&mut self, time: u32) {
+        self.header[68..72].copy_from_slice(&time.to_le_bytes());
+    }
+
+    pub fn set_nonce(&mut self, nonce: u32) {
+        self.header[76..80].copy_from_slice(&nonce.to_le_bytes());
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        sha256d(&self.header)
+    }
+
+    pub fn meets_target(&self) -> bool {
+        meets_target(&self.hash(), &self.target)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let size = self.coinbase.len() + self.transactions.iter().map(Vec::len).sum::<usize>();
+        let mut block = Vec::with_capacity(80 + 9 + size);
+        block.extend_from_slice(&self.header);
+        write_varint(&mut block, 1 + self.transactions.len() as u64);
+        block.extend_from_slice(&self.coinbase);
+        for tx in &self.transactions {
+            block.extend_from_slice(tx);
+        }
+        block
+    }
+
+    // Hex for submitblock
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.serialize())
+    }
+}
+
+// Returns the coinbase as it goes in the block, and its txid
+fn build_coinbase(
+    template: &BlockTemplate,
+    payout_script: &[u8],
+    extranonce: u64,
+    witness_commitment: Option<&[u8]>,
+) -> Result<(Vec<u8>, [u8; 32]), AppError> {
+    // BIP34: the script starts with the block height
+    let mut script = Vec::new();
+    push_height(&mut script, template.height);
+    let flags = template.coinbase_flags()?;
+    if !flags.is_empty() {
+        push_data(&mut script, &flags);
+    }
+    push_data(&mut script, &extranonce.to_le_bytes());
+    if script.len() > MAX_COINBASE_SCRIPT_SIZE {
+        return Err(AppError::Mining(format!(
+            "Coinbase script of {} bytes exceeds the limit of {MAX_COINBASE_SCRIPT_SIZE}",
+            script.len()
+        )));
+    }
+
+    let mut inputs = Vec::new();
+    write_varint(&mut inputs, 1);
+    inputs.extend_from_slice(&[0; 32]);
+    inputs.extend_from_slice(&u32::MAX.to_le_bytes());
+    write_varint(&mut inputs, script.len() as u64);
+    inputs.extend_from_slice(&script);
+    inputs.extend_from_slice(&u32::MAX.to_le_bytes());
+
+    let mut outputs = Vec::new();
+    write_varint(&mut outputs, 1 + witness_commitment.is_some() as u64);
+    write_output(&mut outputs, template.coinbase_value, payout_script);
+    if let Some(commitment) = witness_commitment {
+        write_output(&mut outputs, 0, commitment);
+    }
+
+    let version = 2u32.to_le_bytes();
+    let lock_time = 0u32.to_le_bytes();
+    let txid = sha256d(&[&version[..], &inputs, &outputs, &lock_time].concat());
+
+    let mut coinbase = version.to_vec();
+    match witness_commitment {
+        Some(_) => {
+            // Marker and flag, then one witness item holding the reserved value
+            coinbase.extend([0x00, 0x01]);
+            coinbase.extend(&inputs);
+            coinbase.extend(&outputs);
+            write_varint(&mut coinbase, 1);
+            write_varint(&mut coinbase, WITNESS_RESERVED_VALUE.len() as u64);
+            coinbase.extend_from_slice(&WITNESS_RESERVED_VALUE);
+        }
+        None => {
+            coinbase.extend(&inputs);
+            coinbase.extend(&outputs);
+        }
+    }
+    coinbase.extend_from_slice(&lock_time);
+    Ok((coinbase, txid))
+}
+
+// Pushes the height the way `CScript() << height` does
+fn push_height(script: &mut Vec<u8>, height: u64) {
+    match height {
+        0 => script.push(OP_0),
+        1..=16 => script.push(OP_1 + height as u8 - 1),
+        _ => {
+            let mut number = height.to_le_bytes().to_vec();
+            while number.last() == Some(&0) {
+                number.pop();
+            }
+            // The top bit is the sign, so a set one needs an extra byte
+            if number.last().is_some_and(|byte| byte & 0x80 != 0) {
+                number.push(0);
+            }
+            push_data(script, &number);
+        }
+    }
+}
+
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        len if len < 0x4c => script.push(len as u8),
+        len if len <= 0xff => script.extend([0x4c, len as u8]),
+        len => {
+            script.push(0x4d);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
+
+fn write_output(buf: &mut Vec<u8>, value: u64, script: &[u8]) {
+    buf.extend_from_slice(&value.to_le_bytes());
+    write_varint(buf, script.len() as u64);
+    buf.extend_from_slice(script);
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => buf.push(value as u8),
+        0xfd..=0xffff => {
+            buf.push(0xfd);
+            buf.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            buf.push(0xfe);
+            buf.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            buf.push(0xff);
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, AppError> {
+    hex::decode(value).map_err(|_| AppError::Mining(format!("Invalid hex in template: {value}")))
+}
+
+// Hashes are displayed byte-reversed, so flip them back to internal order
+fn decode_hash(value: &str) -> Result<[u8; 32], AppError> {
+    let mut hash: [u8; 32] = decode_hex(value)?
+        .try_into()
+        .map_err(|_| AppError::Mining(format!("Expected a 32 byte hash in template: {value}")))?;
+    hash.reverse();
+    Ok(hash)
+}
+
+fn parse_bits(value: &str) -> Result<u32, AppError> {
+    u32::from_str_radix(value, 16)
+        .map_err(|_| AppError::Mining(format!("Invalid bits in template: {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solo_mining::fixtures::regtest_template;
+
+    const PAYOUT_ADDRESS: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    fn display_hash(hash: &[u8; 32]) -> String {
+        hex::encode(hash.iter().rev().copied().collect::<Vec<u8>>())
+    }
+
+    // Reads blocks back the way a node would, to check what the builder wrote
+    struct Reader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    struct ParsedTx {
+        // Serialization without witness data, which the txid is computed from
+        stripped: Vec<u8>,
+        full: Vec<u8>,
+        script_sig: Vec<u8>,
+        outputs: Vec<(u64, Vec<u8>)>,
+        witness: Vec<Vec<u8>>,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> &'a [u8] {
+            let bytes = &self.data[self.pos..self.pos + len];
+            self.pos += len;
+            bytes
+        }
+
+        fn varint(&mut self) -> u64 {
+            match self.take(1)[0] {
+                0xfd => u16::from_le_bytes(self.take(2).try_into().unwrap()) as u64,
+                0xfe => u32::from_le_bytes(self.take(4).try_into().unwrap()) as u64,
+                0xff => u64::from_le_bytes(self.take(8).try_into().unwrap()),
+                byte => byte as u64,
+            }
+        }
+
+        fn bytes(&mut self) -> Vec<u8> {
+            let len = self.varint() as usize;
+            self.take(len).to_vec()
+        }
+
+        fn tx(&mut self) -> ParsedTx {
+            let start = self.pos;
+            let version = self.take(4).to_vec();
+            let segwit = self.data[self.pos] == 0;
+            if segwit {
+                assert_eq!(self.take(2), [0x00, 0x01]);
+            }
+            let body_start = self.pos;
+            let mut script_sig = Vec::new();
+            for _ in 0..self.varint() {
+                self.take(36);
+                script_sig = self.bytes();
+                self.take(4);
+            }
+            let outputs = (0..self.varint())
+                .map(|_| {
+                    let value = u64::from_le_bytes(self.take(8).try_into().unwrap());
+                    (value, self.bytes())
+                })
+                .collect();
+            let body = self.data[body_start..self.pos].to_vec();
+            let mut witness = Vec::new();
+            if segwit {
+                for _ in 0..self.varint() {
+                    witness.push(self.bytes());
+                }
+            }
+            let lock_time = self.take(4).to_vec();
+            ParsedTx {
+                stripped: [version, body, lock_time].concat(),
+                full: self.data[start..self.pos].to_vec(),
+                script_sig,
+                outputs,
+                witness,
+            }
+        }
+    }
+
+    #[test]
+    fn test_address_scripts() {
+        let cases = [
+            (
+                "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+                "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+            ),
+            (
+                "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+                "a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87",
+            ),
+            (
+                "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ),
+        ];
+        for (address, script) in cases {
+            assert_eq!(hex::encode(address_script(address).unwrap()), script);
+        }
+
+        for bad in [
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+            "",
+        ] {
+            assert!(address_script(bad).is_err(), "{bad} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_merkle_root_of_block_100000() {
+        let txids = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .map(|txid| decode_hash(txid).unwrap());
+
+        assert_eq!(
+            display_hash(&merkle_root(&txids)),
+            "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766"
+        );
+        // A lone transaction is its own root; three repeat the last one
+        assert_eq!(merkle_root(&txids[..1]), txids[0]);
+        assert_eq!(
+            merkle_root(&txids[..3]),
+            merkle_root(&[txids[0], txids[1], txids[2], txids[2]])
+        );
+    }
+
+    #[test]
+    fn test_push_height() {
+        for (height, script) in [
+            (1, "51"),
+            (16, "60"),
+            (17, "0111"),
+            (101, "0165"),
+            (128, "028000"),
+            (840000, "0340d10c"),
+        ] {
+            let mut pushed = Vec::new();
+            push_height(&mut pushed, height);
+            assert_eq!(hex::encode(pushed), script, "height {height}");
+        }
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let template: BlockTemplate = serde_json::from_value(regtest_template()).unwrap();
+        let payout = address_script(PAYOUT_ADDRESS).unwrap();
+        let mut candidate = BlockCandidate::new(&template, &payout, 7).unwrap();
+        candidate.set_nonce(42);
+
+        let block = candidate.serialize();
+        assert_eq!(hex::decode(candidate.to_hex()).unwrap(), block);
+        let mut reader = Reader {
+            data: &block,
+            pos: 0,
+        };
+
+        // Header
+        let header = reader.take(80).to_vec();
+        assert_eq!(header, candidate.header());
+        assert_eq!(
+            u32::from_le_bytes(header[0..4].try_into().unwrap()),
+            536870912
+        );
+        assert_eq!(
+            display_hash(&header[4..36].try_into().unwrap()),
+            template.previous_block_hash
+        );
+        assert_eq!(
+            u32::from_le_bytes(header[68..72].try_into().unwrap()),
+            1700000600
+        );
+        assert_eq!(hex::encode(&header[72..76]), "ffff7f20");
+        assert_eq!(u32::from_le_bytes(header[76..80].try_into().unwrap()), 42);
+        assert_eq!(
+            display_hash(&candidate.hash()),
+            display_hash(&sha256d(&header))
+        );
+
+        // Transactions, with the template's ones unchanged and in order
+        assert_eq!(reader.varint(), 3);
+        let txs: Vec<ParsedTx> = (0..3).map(|_| reader.tx()).collect();
+        assert_eq!(reader.pos, block.len());
+        for (parsed, tx) in txs[1..].iter().zip(&template.transactions) {
+            assert_eq!(parsed.full, tx.bytes().unwrap());
+            assert_eq!(display_hash(&sha256d(&parsed.stripped)), tx.txid);
+            assert_eq!(display_hash(&sha256d(&parsed.full)), tx.hash);
+        }
+
+        let txids: Vec<[u8; 32]> = txs.iter().map(|tx| sha256d(&tx.stripped)).collect();
+        assert_eq!(header[36..68], merkle_root(&txids));
+
+        // Coinbase: height first, then the extranonce; pays the whole value to the address
+        let coinbase = &txs[0];
+        assert_eq!(coinbase.script_sig[..2], [0x01, 0x65]);
+        assert_eq!(
+            coinbase.script_sig[2..],
+            [&[8u8][..], &7u64.to_le_bytes()].concat()
+        );
+        assert_eq!(coinbase.outputs[0], (5000020000, payout.clone()));
+        assert_eq!(coinbase.witness, vec![WITNESS_RESERVED_VALUE.to_vec()]);
+
+        // The node's commitment matches the one computed from the witness txids
+        let wtxids: Vec<[u8; 32]> = txs[1..].iter().map(|tx| sha256d(&tx.full)).collect();
+        let commitment = witness_commitment_script(&wtxids);
+        assert_eq!(
+            hex::encode(&commitment),
+            template.default_witness_commitment.clone().unwrap()
+        );
+        assert_eq!(coinbase.outputs[1], (0, commitment.clone()));
+
+        // Without the node's commitment the builder computes the same one
+        let mut uncommitted = template.clone();
+        uncommitted.default_witness_commitment = None;
+        let rebuilt = BlockCandidate::new(&uncommitted, &payout, 7).unwrap();
+        assert_eq!(rebuilt.header()[36..68], header[36..68]);
+
+        // Another extranonce changes the coinbase and with it the merkle root
+        let other = BlockCandidate::new(&template, &payout, 8).unwrap();
+        assert_ne!(other.header()[36..68], header[36..68]);
+    }
+
+    #[test]
+    fn test_block_without_witness_data() {
+        let mut template: BlockTemplate = serde_json::from_value(regtest_template()).unwrap();
+        template.transactions.truncate(1);
+        template.default_witness_commitment = None;
+        let payout = address_script(PAYOUT_ADDRESS).unwrap();
+        let candidate = BlockCandidate::new(&template, &payout, 0).unwrap();
+
+        let block = candidate.serialize();
+        let mut reader = Reader {
+            data: &block[80..],
+            pos: 0,
+        };
+        assert_eq!(reader.varint(), 2);
+        let coinbase = reader.tx();
+        assert_eq!(coinbase.full, coinbase.stripped);
+        assert_eq!(coinbase.outputs.len(), 1);
+        assert_eq!(
+            candidate.header()[36..68],
+            merkle_root(&[
+                sha256d(&coinbase.stripped),
+                decode_hash(&template.transactions[0].txid).unwrap()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_finds_block_at_regtest_difficulty() {
+        let template: BlockTemplate = serde_json::from_value(regtest_template()).unwrap();
+        let payout = address_script(PAYOUT_ADDRESS).unwrap();
+        let mut candidate = BlockCandidate::new(&template, &payout, 0).unwrap();
+
+        // Half of all hashes meet the regtest target
+        let nonce = (0..64)
+            .find(|nonce| {
+                candidate.set_nonce(*nonce);
+                candidate.meets_target()
+            })
+            .unwrap();
+        candidate.set_nonce(nonce);
+        assert!(candidate.hash()[31] < 0x80);
+    }
+}
@@ -22,6 +22,7 @@ pub struct AppConfig {
     pub max_log_size_mb: u64,
     pub network_timeout_seconds: u64,
     pub max_retry_attempts: u32,
+    pub node_stop_timeout_seconds: u64,
     pub debug_mode: bool,
     pub enable_telemetry: bool,
     pub auto_update_check: bool,
@@ -105,6 +106,12 @@ impl AppConfig {
                 .parse()
                 .map_err(|_| AppError::Config("Invalid MAX_RETRY_ATTEMPTS".to_string()))?,
 
+            // Process Configuration
+            node_stop_timeout_seconds: env::var("NODE_STOP_TIMEOUT_SECONDS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .map_err(|_| AppError::Config("Invalid NODE_STOP_TIMEOUT_SECONDS".to_string()))?,
+
             // Development Settings
             debug_mode: env::var("DEBUG_MODE")
                 .unwrap_or_else(|_| "false".to_string())
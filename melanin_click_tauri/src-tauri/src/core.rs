@@ -1,8 +1,17 @@
+use crate::process_limits::ProcessLimits;
+use crate::process_logs::{LogStream, PROCESS_LOGS};
+use crate::process_registry::{self, RegistryEntry};
+use crate::rpc::RpcClient;
+use crate::throttle::ProcessThrottle;
 use crate::AppError;
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitStatus, Stdio};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::Child;
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
@@ -10,15 +19,31 @@ use tracing::{debug, error, info, warn};
 pub type ProcessId = u32;
 pub type ProcessName = String;
 
+// Lines of stderr kept for the failure report of a process
+const STDERR_TAIL_LINES: usize = 20;
+// How often the supervisor looks for exited processes
+const SUPERVISOR_INTERVAL: Duration = Duration::from_secs(2);
+// Time a daemon gets to write its pidfile after its launcher exits
+const DAEMON_STARTUP_GRACE: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Clone)]
 pub struct ProcessInfo {
     pub pid: ProcessId,
     pub name: ProcessName,
     pub command: String,
     pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
     pub started_at: chrono::DateTime<chrono::Utc>,
     pub status: ProcessStatus,
     pub resource_usage: ResourceUsage,
+    // Restarts made by the supervisor since the process was first started
+    pub restart_count: u32,
+    // How the last stop_process call ended the process
+    pub stop_outcome: Option<StopOutcome>,
+    // Written by a daemonized process, which outlives the child we spawned
+    pub pidfile: Option<PathBuf>,
+    // Tracked by pid alone: a daemon that has forked, or a process reattached after an app restart
+    pub detached: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -32,12 +57,182 @@ pub struct ResourceUsage {
 pub enum ProcessStatus {
     Running,
     Stopped,
-    Failed(String),
+    Failed {
+        exit_code: Option<i32>,
+        reason: String,
+        stderr_tail: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StopOutcome {
+    // Exited on its own after the stop request
+    Graceful,
+    // Killed after the stop timeout
+    Forced,
+    // Had already exited when the stop was requested
+    AlreadyExited,
+}
+
+// How a process is asked to shut down before it is killed
+#[derive(Debug, Clone)]
+pub struct StopPolicy {
+    // Time given to exit after the stop request
+    pub timeout: Duration,
+    // Node type whose RPC `stop` is tried before SIGTERM, so nodes shut down
+    // the way their own tools do it
+    pub rpc_node: Option<String>,
+}
+
+impl Default for StopPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            rpc_node: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartMode {
+    Never,
+    OnFailure,
+    Always,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    // Restarts allowed within the window before the process is left down
+    pub max_retries: u32,
+    pub window_secs: u64,
+    // Delay before the first restart in the window, doubled for each one after it
+    pub backoff_secs: u64,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            mode: RestartMode::Never,
+            max_retries: 5,
+            window_secs: 600,
+            backoff_secs: 5,
+            max_backoff_secs: 300,
+        }
+    }
+}
+
+impl RestartPolicy {
+    pub fn on_failure() -> Self {
+        Self {
+            mode: RestartMode::OnFailure,
+            ..Self::default()
+        }
+    }
+
+    fn wants_restart(&self, succeeded: bool) -> bool {
+        match self.mode {
+            RestartMode::Never => false,
+            RestartMode::OnFailure => !succeeded,
+            RestartMode::Always => true,
+        }
+    }
+
+    fn backoff(&self, restarts_in_window: u32) -> Duration {
+        let secs = self
+            .backoff_secs
+            .saturating_mul(1u64 << restarts_in_window.min(32))
+            .min(self.max_backoff_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+// Applies a restart policy to the exits of one process
+#[derive(Debug, Clone, Default)]
+pub struct RestartTracker {
+    restarts: VecDeque<DateTime<Utc>>,
+    due_at: Option<DateTime<Utc>>,
+}
+
+impl RestartTracker {
+    // Schedules a restart after an exit; false when the policy or retry limit forbids one
+    pub fn on_exit(&mut self, policy: &RestartPolicy, succeeded: bool, now: DateTime<Utc>) -> bool {
+        self.due_at = None;
+        if !policy.wants_restart(succeeded) {
+            return false;
+        }
+
+        let window = chrono::Duration::seconds(policy.window_secs as i64);
+        self.restarts.retain(|restart| now - *restart < window);
+        let recent = self.restarts.len() as u32;
+        if recent >= policy.max_retries {
+            return false;
+        }
+
+        let backoff = chrono::Duration::from_std(policy.backoff(recent))
+            .unwrap_or_else(|_| chrono::Duration::zero());
+        self.due_at = Some(now + backoff);
+        true
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.due_at.is_some_and(|due_at| due_at <= now)
+    }
+
+    pub fn is_scheduled(&self) -> bool {
+        self.due_at.is_some()
+    }
+
+    pub fn record_restart(&mut self, now: DateTime<Utc>) {
+        self.due_at = None;
+        self.restarts.push_back(now);
+    }
+
+    pub fn cancel(&mut self) {
+        self.due_at = None;
+    }
+}
+
+#[derive(Default)]
+struct RestartState {
+    policy: RestartPolicy,
+    tracker: RestartTracker,
+}
+
+// Tasks draining a child's output into the process logs
+struct OutputReaders {
+    // Sequence number of the first log line of this launch
+    since_seq: u64,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+// Status for a process that has exited on its own
+fn status_after_exit(exit_status: ExitStatus, stderr_tail: Vec<String>) -> ProcessStatus {
+    if exit_status.success() {
+        return ProcessStatus::Stopped;
+    }
+    ProcessStatus::Failed {
+        exit_code: exit_status.code(),
+        reason: format!("Process exited with {exit_status}"),
+        stderr_tail,
+    }
 }
 
 pub struct ProcessManager {
     processes: Arc<Mutex<HashMap<ProcessName, ProcessInfo>>>,
     active_children: Arc<Mutex<HashMap<ProcessName, Child>>>,
+    throttles: Arc<Mutex<HashMap<ProcessName, ProcessThrottle>>>,
+    restarts: Arc<Mutex<HashMap<ProcessName, RestartState>>>,
+    output_readers: Arc<Mutex<HashMap<ProcessName, OutputReaders>>>,
+    stop_policies: Arc<Mutex<HashMap<ProcessName, StopPolicy>>>,
+    pidfiles: Arc<Mutex<HashMap<ProcessName, PathBuf>>>,
+    limits: Arc<Mutex<HashMap<ProcessName, ProcessLimits>>>,
+    // Where running processes are saved so a restarted app can reattach to them
+    registry: Option<PathBuf>,
 }
 
 impl Default for ProcessManager {
@@ -51,6 +246,20 @@ impl ProcessManager {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
             active_children: Arc::new(Mutex::new(HashMap::new())),
+            throttles: Arc::new(Mutex::new(HashMap::new())),
+            restarts: Arc::new(Mutex::new(HashMap::new())),
+            output_readers: Arc::new(Mutex::new(HashMap::new())),
+            stop_policies: Arc::new(Mutex::new(HashMap::new())),
+            pidfiles: Arc::new(Mutex::new(HashMap::new())),
+            limits: Arc::new(Mutex::new(HashMap::new())),
+            registry: None,
+        }
+    }
+
+    pub fn with_registry(path: PathBuf) -> Self {
+        Self {
+            registry: Some(path),
+            ..Self::new()
         }
     }
 
@@ -74,29 +283,20 @@ impl ProcessManager {
             )));
         }
 
-        let mut cmd = tokio::process::Command::new(executable);
-        cmd.args(args);
-
-        if let Some(dir) = working_dir {
-            cmd.current_dir(dir);
+        // A fresh start replaces any restart still waiting on its backoff
+        if let Some(state) = self.restarts.lock().await.get_mut(name) {
+            state.tracker.cancel();
         }
 
-        cmd.stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .stdin(Stdio::null())
-            .kill_on_drop(true); // Ensure child is killed when dropped
-
-        let child = cmd
-            .spawn()
-            .map_err(|e| AppError::Process(format!("Failed to start {name}: {e}")))?;
-
-        let pid = child.id().unwrap_or(0);
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let pid = self.spawn(name, executable, &args, working_dir).await?;
 
         let process_info = ProcessInfo {
             pid,
             name: name.to_string(),
             command: executable.display().to_string(),
-            args: args.iter().map(|s| s.to_string()).collect(),
+            args,
+            working_dir: working_dir.map(Path::to_path_buf),
             started_at: chrono::Utc::now(),
             status: ProcessStatus::Running,
             resource_usage: ResourceUsage {
@@ -104,78 +304,135 @@ impl ProcessManager {
                 memory_mb: 0,
                 uptime_seconds: 0,
             },
+            restart_count: 0,
+            stop_outcome: None,
+            pidfile: self.pidfiles.lock().await.get(name).cloned(),
+            detached: false,
         };
 
-        // Store both process info and child handle
         {
             let mut processes = self.processes.lock().await;
             processes.insert(name.to_string(), process_info);
         }
+        self.persist().await;
+
+        info!("Successfully started process: {} with PID: {}", name, pid);
+        Ok(pid)
+    }
+
+    // Spawns the child, stores its handle and keeps the tail of its stderr
+    async fn spawn(
+        &self,
+        name: &str,
+        executable: &Path,
+        args: &[String],
+        working_dir: Option<&Path>,
+    ) -> Result<ProcessId, AppError> {
+        let mut cmd = tokio::process::Command::new(executable);
+        cmd.args(args);
+
+        if let Some(dir) = working_dir {
+            cmd.current_dir(dir);
+        }
+        if let Some(limits) = self.limits.lock().await.get(name) {
+            limits.apply(&mut cmd);
+        }
+
+        cmd.stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdin(Stdio::null())
+            .kill_on_drop(true); // Ensure child is killed when dropped
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| AppError::Process(format!("Failed to start {name}: {e}")))?;
+
+        let pid = child.id().unwrap_or(0);
+
+        // Drained continuously so a chatty child never blocks on a full pipe
+        let since_seq = PROCESS_LOGS.next_seq();
+        let mut tasks = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            tasks.push(PROCESS_LOGS.drain(name, LogStream::Stdout, stdout, |_| {}));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tasks.push(PROCESS_LOGS.drain(name, LogStream::Stderr, stderr, |_| {}));
+        }
+        self.output_readers
+            .lock()
+            .await
+            .insert(name.to_string(), OutputReaders { since_seq, tasks });
 
         {
             let mut children = self.active_children.lock().await;
             children.insert(name.to_string(), child);
         }
 
-        info!("Successfully started process: {} with PID: {}", name, pid);
         Ok(pid)
     }
 
     pub async fn stop_process(&self, name: &str) -> Result<(), AppError> {
         info!("Stopping process: {}", name);
 
-        // First try to gracefully stop via child handle
-        let mut child_removed = false;
-        {
-            let mut children = self.active_children.lock().await;
-            if let Some(mut child) = children.remove(name) {
-                // Try graceful shutdown first
-                match child.start_kill() {
-                    Ok(_) => {
-                        info!("Sent SIGTERM to process: {}", name);
-
-                        // Wait for up to 5 seconds for graceful shutdown
-                        match tokio::time::timeout(
-                            tokio::time::Duration::from_secs(5),
-                            child.wait(),
-                        )
-                        .await
-                        {
-                            Ok(Ok(exit_status)) => {
-                                info!("Process {} exited gracefully: {:?}", name, exit_status);
-                            }
-                            Ok(Err(e)) => {
-                                warn!("Error waiting for process {}: {}", name, e);
-                            }
-                            Err(_) => {
-                                warn!("Process {} did not exit gracefully, force killing", name);
-                                let _ = child.kill().await;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to send kill signal to process {}: {}", name, e);
-                        let _ = child.kill().await;
-                    }
-                }
-                child_removed = true;
-            }
+        // Let a throttled process run again so it can handle the signal
+        self.throttles.lock().await.remove(name);
+        if let Some(state) = self.restarts.lock().await.get_mut(name) {
+            state.tracker.cancel();
         }
 
+        let policy = self
+            .stop_policies
+            .lock()
+            .await
+            .get(name)
+            .cloned()
+            .unwrap_or_default();
+        let child = self.active_children.lock().await.remove(name);
+        let process_info = self.processes.lock().await.get(name).cloned();
+        if child.is_none() && process_info.is_none() {
+            return Err(AppError::Process(format!("Process '{name}' not found")));
+        }
+        let detached = process_info
+            .filter(|process_info| child.is_none() && process_info.detached)
+            .and_then(|process_info| Some((detached_pid(&process_info)?, process_info)));
+        let outcome = match detached {
+            Some((pid, process_info)) => shutdown_detached(name, pid, &process_info, &policy).await,
+            None => shutdown(name, child, &policy).await,
+        };
+
         // Update process status
-        {
-            let mut processes = self.processes.lock().await;
-            if let Some(process_info) = processes.get_mut(name) {
-                process_info.status = ProcessStatus::Stopped;
-                info!("Process {} stopped successfully", name);
-            } else if !child_removed {
-                return Err(AppError::Process(format!("Process '{name}' not found")));
-            }
+        if let Some(process_info) = self.processes.lock().await.get_mut(name) {
+            process_info.status = ProcessStatus::Stopped;
+            process_info.stop_outcome = Some(outcome);
         }
+        info!("Process {} stopped: {:?}", name, outcome);
+        self.persist().await;
 
         Ok(())
     }
 
+    // Stop request and timeout used by stop_process for this process
+    pub async fn set_stop_policy(&self, name: &str, policy: StopPolicy) {
+        self.stop_policies
+            .lock()
+            .await
+            .insert(name.to_string(), policy);
+    }
+
+    // Nice level, CPU affinity, memory limit and I/O priority for later starts and restarts
+    pub async fn set_process_limits(&self, name: &str, limits: ProcessLimits) {
+        self.limits.lock().await.insert(name.to_string(), limits);
+    }
+
+    // Pidfile a daemonized process writes, used to follow it once its launcher exits
+    pub async fn set_pidfile(&self, name: &str, pidfile: Option<PathBuf>) {
+        let mut pidfiles = self.pidfiles.lock().await;
+        match pidfile {
+            Some(pidfile) => pidfiles.insert(name.to_string(), pidfile),
+            None => pidfiles.remove(name),
+        };
+    }
+
     pub async fn is_process_running(&self, name: &str) -> bool {
         // First check if we have an active child handle
         {
@@ -186,13 +443,9 @@ impl ProcessManager {
                         debug!("Process {} has exited: {:?}", name, exit_status);
                         // Remove the child since it's no longer running
                         children.remove(name);
-
-                        // Update process status
-                        let mut processes = self.processes.lock().await;
-                        if let Some(process_info) = processes.get_mut(name) {
-                            process_info.status = ProcessStatus::Stopped;
-                        }
-                        return false;
+                        drop(children);
+                        self.record_exit(name, exit_status).await;
+                        return self.is_detached_running(name).await;
                     }
                     Ok(None) => {
                         // Process is still running
@@ -207,11 +460,182 @@ impl ProcessManager {
         }
 
         // Fallback to process info if no child handle
-        let processes = self.processes.lock().await;
-        if let Some(process_info) = processes.get(name) {
-            matches!(process_info.status, ProcessStatus::Running)
+        let running = {
+            let processes = self.processes.lock().await;
+            processes.get(name).map(|process_info| {
+                (
+                    matches!(process_info.status, ProcessStatus::Running),
+                    process_info.detached,
+                )
+            })
+        };
+        match running {
+            Some((true, true)) => self.is_detached_running(name).await,
+            Some((running, _)) => running,
+            None => false,
+        }
+    }
+
+    // Checks a detached process by pid and marks it failed once it has gone
+    async fn is_detached_running(&self, name: &str) -> bool {
+        {
+            let mut processes = self.processes.lock().await;
+            let Some(process_info) = processes.get_mut(name) else {
+                return false;
+            };
+            if !process_info.detached || !matches!(process_info.status, ProcessStatus::Running) {
+                return false;
+            }
+            if let Some(pid) = detached_pid(process_info) {
+                process_info.pid = pid;
+                return true;
+            }
+            let starting = process_info.pidfile.is_some()
+                && (Utc::now() - process_info.started_at)
+                    .to_std()
+                    .is_ok_and(|elapsed| elapsed < DAEMON_STARTUP_GRACE);
+            if starting {
+                return true;
+            }
+
+            // Its exit status is unknown, so the restart policy is not applied
+            warn!("Detached process {} is no longer running", name);
+            process_info.status = ProcessStatus::Failed {
+                exit_code: None,
+                reason: format!(
+                    "Process {} exited while detached; exit status unknown",
+                    process_info.pid
+                ),
+                stderr_tail: Vec::new(),
+            };
+        }
+        self.persist().await;
+        false
+    }
+
+    // Takes back the processes saved by an earlier run of the app that are still alive
+    pub async fn reattach(&self) -> Vec<ProcessName> {
+        let Some(path) = &self.registry else {
+            return Vec::new();
+        };
+
+        let mut reattached = Vec::new();
+        for entry in process_registry::load(path) {
+            let pid = match &entry.pidfile {
+                Some(pidfile) => process_registry::read_pidfile(pidfile),
+                None => Some(entry.pid),
+            };
+            let Some(pid) = pid
+                .filter(|pid| process_registry::process_matches(*pid, &entry.command, &entry.args))
+            else {
+                info!(
+                    "Process {} from the last run is no longer running",
+                    entry.name
+                );
+                continue;
+            };
+
+            let mut processes = self.processes.lock().await;
+            if processes.contains_key(&entry.name) {
+                continue;
+            }
+            info!("Reattached to process {} with PID: {}", entry.name, pid);
+            if let Some(pidfile) = &entry.pidfile {
+                self.pidfiles
+                    .lock()
+                    .await
+                    .insert(entry.name.clone(), pidfile.clone());
+            }
+            processes.insert(
+                entry.name.clone(),
+                ProcessInfo {
+                    pid,
+                    name: entry.name.clone(),
+                    command: entry.command,
+                    args: entry.args,
+                    working_dir: entry.working_dir,
+                    started_at: entry.started_at,
+                    status: ProcessStatus::Running,
+                    resource_usage: ResourceUsage {
+                        cpu_percent: 0.0,
+                        memory_mb: 0,
+                        uptime_seconds: 0,
+                    },
+                    restart_count: 0,
+                    stop_outcome: None,
+                    pidfile: entry.pidfile,
+                    detached: true,
+                },
+            );
+            reattached.push(entry.name);
+        }
+
+        self.persist().await;
+        reattached
+    }
+
+    // Saves the running processes to the registry
+    async fn persist(&self) {
+        let Some(path) = &self.registry else {
+            return;
+        };
+        let entries: Vec<RegistryEntry> = self
+            .processes
+            .lock()
+            .await
+            .values()
+            .filter(|process_info| matches!(process_info.status, ProcessStatus::Running))
+            .map(|process_info| RegistryEntry {
+                name: process_info.name.clone(),
+                pid: process_info.pid,
+                command: process_info.command.clone(),
+                args: process_info.args.clone(),
+                working_dir: process_info.working_dir.clone(),
+                started_at: process_info.started_at,
+                pidfile: process_info.pidfile.clone(),
+            })
+            .collect();
+        if let Err(e) = process_registry::save(path, &entries) {
+            warn!("Failed to save process registry: {}", e);
+        }
+    }
+
+    // Limits a running process to about `intensity` percent of its CPU time
+    pub async fn set_process_intensity(&self, name: &str, intensity: u8) -> Result<(), AppError> {
+        if !self.is_process_running(name).await {
+            return Err(AppError::Process(format!(
+                "Process '{name}' is not running"
+            )));
+        }
+        let pid = self
+            .processes
+            .lock()
+            .await
+            .get(name)
+            .map(|process_info| process_info.pid)
+            .ok_or_else(|| AppError::Process(format!("Process '{name}' not found")))?;
+
+        let mut throttles = self.throttles.lock().await;
+        if intensity >= 100 {
+            throttles.remove(name);
+        } else if let Some(throttle) = throttles.get(name).filter(|t| t.pid() == pid) {
+            throttle.set_intensity(intensity);
         } else {
-            false
+            throttles.insert(name.to_string(), ProcessThrottle::start(pid, intensity));
+        }
+
+        info!("Set intensity of process {} to {}%", name, intensity);
+        Ok(())
+    }
+
+    // A restarted process has a new pid, so its throttle is started again on that pid
+    async fn rethrottle(&self, name: &str, pid: ProcessId) {
+        let mut throttles = self.throttles.lock().await;
+        if let Some(previous) = throttles.remove(name) {
+            throttles.insert(
+                name.to_string(),
+                ProcessThrottle::start(pid, previous.intensity()),
+            );
         }
     }
 
@@ -259,42 +683,219 @@ impl ProcessManager {
         }
     }
 
+    // Sets the status of a process that exited on its own and schedules its restart
+    async fn record_exit(&self, name: &str, exit: ExitStatus) {
+        if exit.success() && self.follow_daemon(name).await {
+            return;
+        }
+
+        let stderr_tail = match self.output_readers.lock().await.remove(name) {
+            Some(readers) => {
+                // The pipes close with the process, unless a daemonized child still holds them
+                let _ = tokio::time::timeout(
+                    Duration::from_millis(500),
+                    futures_util::future::join_all(readers.tasks),
+                )
+                .await;
+                let stderr: Vec<String> = PROCESS_LOGS
+                    .since(name, readers.since_seq)
+                    .into_iter()
+                    .filter(|line| line.stream == LogStream::Stderr)
+                    .map(|line| line.line)
+                    .collect();
+                let skip = stderr.len().saturating_sub(STDERR_TAIL_LINES);
+                stderr[skip..].to_vec()
+            }
+            None => Vec::new(),
+        };
+        let status = status_after_exit(exit, stderr_tail);
+        if let ProcessStatus::Failed {
+            reason,
+            stderr_tail,
+            ..
+        } = &status
+        {
+            warn!("Process {} failed: {}", name, reason);
+            for line in stderr_tail {
+                warn!("{} stderr: {}", name, line);
+            }
+        }
+
+        if let Some(process_info) = self.processes.lock().await.get_mut(name) {
+            process_info.status = status;
+        }
+        self.persist().await;
+
+        let mut restarts = self.restarts.lock().await;
+        if let Some(state) = restarts.get_mut(name) {
+            if state
+                .tracker
+                .on_exit(&state.policy, exit.success(), Utc::now())
+            {
+                info!("Scheduled restart of process {}", name);
+            } else if state.policy.mode != RestartMode::Never {
+                warn!("Not restarting process {}", name);
+            }
+        }
+    }
+
+    // A launcher that exits cleanly after forking leaves its daemon to be followed by pidfile
+    async fn follow_daemon(&self, name: &str) -> bool {
+        {
+            let mut processes = self.processes.lock().await;
+            let Some(process_info) = processes
+                .get_mut(name)
+                .filter(|process_info| process_info.pidfile.is_some())
+            else {
+                return false;
+            };
+            process_info.detached = true;
+            info!("Process {} daemonized, following its pidfile", name);
+        }
+        // The daemon may still hold the pipes, so its output keeps being logged
+        self.output_readers.lock().await.remove(name);
+        self.persist().await;
+        true
+    }
+
     pub async fn cleanup_dead_processes(&self) -> Result<(), AppError> {
-        let mut children = self.active_children.lock().await;
-        let mut processes = self.processes.lock().await;
-        let mut to_remove = Vec::new();
+        let mut exited = Vec::new();
+        {
+            let mut children = self.active_children.lock().await;
+            let mut processes = self.processes.lock().await;
+            let mut to_remove = Vec::new();
 
-        for (name, child) in children.iter_mut() {
-            match child.try_wait() {
-                Ok(Some(exit_status)) => {
-                    info!("Process {} has exited: {:?}", name, exit_status);
-                    to_remove.push(name.clone());
+            for (name, child) in children.iter_mut() {
+                match child.try_wait() {
+                    Ok(Some(exit_status)) => {
+                        info!("Process {} has exited: {:?}", name, exit_status);
+                        to_remove.push(name.clone());
+                        exited.push((name.clone(), exit_status));
+                    }
+                    Ok(None) => {
+                        // Process still running
+                    }
+                    Err(e) => {
+                        warn!("Error checking process {}: {}", name, e);
+                        to_remove.push(name.clone());
 
-                    if let Some(process_info) = processes.get_mut(name) {
-                        process_info.status = ProcessStatus::Stopped;
+                        if let Some(process_info) = processes.get_mut(name) {
+                            process_info.status = ProcessStatus::Failed {
+                                exit_code: None,
+                                reason: e.to_string(),
+                                stderr_tail: Vec::new(),
+                            };
+                        }
                     }
                 }
-                Ok(None) => {
-                    // Process still running
+            }
+
+            for name in to_remove {
+                children.remove(&name);
+            }
+        }
+
+        for (name, exit_status) in exited {
+            self.record_exit(&name, exit_status).await;
+        }
+
+        let detached: Vec<ProcessName> = self
+            .processes
+            .lock()
+            .await
+            .values()
+            .filter(|process_info| {
+                process_info.detached && matches!(process_info.status, ProcessStatus::Running)
+            })
+            .map(|process_info| process_info.name.clone())
+            .collect();
+        for name in detached {
+            self.is_detached_running(&name).await;
+        }
+
+        Ok(())
+    }
+
+    // Restart policy applied when the process exits on its own; kept across restarts
+    pub async fn set_restart_policy(&self, name: &str, policy: RestartPolicy) {
+        let mut restarts = self.restarts.lock().await;
+        restarts.entry(name.to_string()).or_default().policy = policy;
+    }
+
+    // Reaps exited processes and starts again those whose restart is due
+    pub async fn supervise_once(&self) -> Result<(), AppError> {
+        self.cleanup_dead_processes().await?;
+
+        let now = Utc::now();
+        let due: Vec<ProcessName> = self
+            .restarts
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, state)| state.tracker.is_due(now))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in due {
+            let Some(process_info) = self.get_process_info(&name).await else {
+                continue;
+            };
+            if let Some(state) = self.restarts.lock().await.get_mut(&name) {
+                state.tracker.record_restart(now);
+            }
+
+            let executable = PathBuf::from(&process_info.command);
+            match self
+                .spawn(
+                    &name,
+                    &executable,
+                    &process_info.args,
+                    process_info.working_dir.as_deref(),
+                )
+                .await
+            {
+                Ok(pid) => {
+                    info!("Restarted process {} with PID: {}", name, pid);
+                    if let Some(process_info) = self.processes.lock().await.get_mut(&name) {
+                        process_info.pid = pid;
+                        process_info.started_at = now;
+                        process_info.status = ProcessStatus::Running;
+                        process_info.restart_count += 1;
+                        process_info.detached = false;
+                    }
+                    self.rethrottle(&name, pid).await;
+                    self.persist().await;
                 }
                 Err(e) => {
-                    warn!("Error checking process {}: {}", name, e);
-                    to_remove.push(name.clone());
-
-                    if let Some(process_info) = processes.get_mut(name) {
-                        process_info.status = ProcessStatus::Failed(e.to_string());
+                    error!("Failed to restart process {}: {}", name, e);
+                    if let Some(process_info) = self.processes.lock().await.get_mut(&name) {
+                        process_info.status = ProcessStatus::Failed {
+                            exit_code: None,
+                            reason: e.to_string(),
+                            stderr_tail: Vec::new(),
+                        };
+                    }
+                    // A failed start counts as a failed run for the policy
+                    if let Some(state) = self.restarts.lock().await.get_mut(&name) {
+                        state.tracker.on_exit(&state.policy, false, now);
                     }
                 }
             }
         }
 
-        for name in to_remove {
-            children.remove(&name);
-        }
-
         Ok(())
     }
 
+    // Background supervisor applying restart policies; runs for the life of the app
+    pub async fn supervise(&self) {
+        loop {
+            tokio::time::sleep(SUPERVISOR_INTERVAL).await;
+            if let Err(e) = self.supervise_once().await {
+                warn!("Process supervisor error: {}", e);
+            }
+        }
+    }
+
     #[allow(dead_code)]
     fn check_process_exists(&self, pid: ProcessId) -> bool {
         #[cfg(unix)]
@@ -321,11 +922,140 @@ impl ProcessManager {
     }
 }
 
+// Asks the process to exit, waits for the policy timeout, and kills it only after that
+async fn shutdown(name: &str, child: Option<Child>, policy: &StopPolicy) -> StopOutcome {
+    let Some(mut child) = child.filter(|child| child.id().is_some()) else {
+        return stop_untracked(name, policy).await;
+    };
+    if matches!(child.try_wait(), Ok(Some(_))) {
+        return stop_untracked(name, policy).await;
+    }
+
+    if request_stop(name, policy, child.id()).await {
+        match tokio::time::timeout(policy.timeout, child.wait()).await {
+            Ok(Ok(exit_status)) => {
+                info!("Process {} exited gracefully: {:?}", name, exit_status);
+                return StopOutcome::Graceful;
+            }
+            Ok(Err(e)) => warn!("Error waiting for process {}: {}", name, e),
+            Err(_) => warn!(
+                "Process {} did not exit within {:?}, force killing",
+                name, policy.timeout
+            ),
+        }
+    }
+
+    if let Err(e) = child.kill().await {
+        error!("Failed to kill process {}: {}", name, e);
+    }
+    StopOutcome::Forced
+}
+
+// Same as shutdown for a process we hold no child handle for, polling its pid instead
+async fn shutdown_detached(
+    name: &str,
+    pid: ProcessId,
+    process_info: &ProcessInfo,
+    policy: &StopPolicy,
+) -> StopOutcome {
+    let alive =
+        || process_registry::process_matches(pid, &process_info.command, &process_info.args);
+    if request_stop(name, policy, Some(pid)).await {
+        let deadline = tokio::time::Instant::now() + policy.timeout;
+        while tokio::time::Instant::now() < deadline {
+            if !alive() {
+                info!("Process {} exited gracefully", name);
+                return StopOutcome::Graceful;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        warn!(
+            "Process {} did not exit within {:?}, force killing",
+            name, policy.timeout
+        );
+    }
+
+    if alive() && !force_kill(pid) {
+        error!("Failed to kill process {}", name);
+    }
+    StopOutcome::Forced
+}
+
+// The child is gone, but a node it started may still answer RPC
+async fn stop_untracked(name: &str, policy: &StopPolicy) -> StopOutcome {
+    if policy.rpc_node.is_some() && request_stop(name, policy, None).await {
+        StopOutcome::Graceful
+    } else {
+        StopOutcome::AlreadyExited
+    }
+}
+
+// Sends the RPC stop of the policy, falling back to SIGTERM; false if neither got through
+async fn request_stop(name: &str, policy: &StopPolicy, pid: Option<ProcessId>) -> bool {
+    if let Some(node_type) = &policy.rpc_node {
+        // Built per stop so the cookie the node wrote on its latest start is used
+        let client = RpcClient::for_node(node_type)
+            .with_timeout(Duration::from_secs(10))
+            .with_retries(0);
+        match client.call::<Value>("stop", json!([])).await {
+            Ok(_) => {
+                info!("Requested RPC stop of process: {}", name);
+                return true;
+            }
+            Err(e) => warn!("RPC stop of process {} failed: {}", name, e),
+        }
+    }
+
+    let sent = pid.is_some_and(terminate);
+    if sent {
+        info!("Sent SIGTERM to process: {}", name);
+    }
+    sent
+}
+
+// Pid of a detached process if it is still running the same program
+fn detached_pid(process_info: &ProcessInfo) -> Option<ProcessId> {
+    let pid = match &process_info.pidfile {
+        Some(pidfile) => process_registry::read_pidfile(pidfile)?,
+        None => process_info.pid,
+    };
+    process_registry::process_matches(pid, &process_info.command, &process_info.args).then_some(pid)
+}
+
+// Sends SIGTERM; false where there is no such signal or the process is gone
+#[cfg(unix)]
+fn terminate(pid: u32) -> bool {
+    // SAFETY: kill has no memory-safety preconditions
+    unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) == 0 }
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn force_kill(pid: u32) -> bool {
+    // SAFETY: kill has no memory-safety preconditions
+    unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) == 0 }
+}
+
+#[cfg(not(unix))]
+fn force_kill(pid: u32) -> bool {
+    Command::new("taskkill")
+        .args(["/F", "/PID", &pid.to_string()])
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
 // Global process manager instance
 static PROCESS_MANAGER: std::sync::OnceLock<ProcessManager> = std::sync::OnceLock::new();
 
 pub fn get_process_manager() -> &'static ProcessManager {
-    PROCESS_MANAGER.get_or_init(ProcessManager::new)
+    PROCESS_MANAGER.get_or_init(|| {
+        process_registry::registry_path()
+            .map_or_else(ProcessManager::new, ProcessManager::with_registry)
+    })
 }
 
 // Utility functions
@@ -381,3 +1111,248 @@ pub async fn set_executable_permissions(path: &Path) -> Result<(), AppError> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_policy_modes_and_backoff() {
+        let now = Utc::now();
+        let never = RestartPolicy::default();
+        assert!(!RestartTracker::default().on_exit(&never, false, now));
+
+        let on_failure = RestartPolicy::on_failure();
+        assert!(!RestartTracker::default().on_exit(&on_failure, true, now));
+        assert!(RestartTracker::default().on_exit(&on_failure, false, now));
+
+        let always = RestartPolicy {
+            mode: RestartMode::Always,
+            ..RestartPolicy::default()
+        };
+        assert!(RestartTracker::default().on_exit(&always, true, now));
+
+        assert_eq!(on_failure.backoff(0), Duration::from_secs(5));
+        assert_eq!(on_failure.backoff(2), Duration::from_secs(20));
+        assert_eq!(on_failure.backoff(40), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_restart_tracker_limits_retries_within_window() {
+        let policy = RestartPolicy {
+            max_retries: 2,
+            window_secs: 60,
+            backoff_secs: 10,
+            ..RestartPolicy::on_failure()
+        };
+        let mut tracker = RestartTracker::default();
+        let start = Utc::now();
+
+        assert!(tracker.on_exit(&policy, false, start));
+        assert!(!tracker.is_due(start));
+        assert!(tracker.is_due(start + chrono::Duration::seconds(10)));
+        tracker.record_restart(start + chrono::Duration::seconds(10));
+
+        // The second restart backs off twice as long
+        assert!(tracker.on_exit(&policy, false, start + chrono::Duration::seconds(15)));
+        assert!(!tracker.is_due(start + chrono::Duration::seconds(30)));
+        assert!(tracker.is_due(start + chrono::Duration::seconds(35)));
+        tracker.record_restart(start + chrono::Duration::seconds(35));
+
+        // Out of retries until the first restart leaves the window
+        assert!(!tracker.on_exit(&policy, false, start + chrono::Duration::seconds(40)));
+        assert!(!tracker.is_scheduled());
+        assert!(tracker.on_exit(&policy, false, start + chrono::Duration::seconds(71)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_supervisor_records_failure_and_restarts() {
+        let manager = ProcessManager::new();
+        manager
+            .set_restart_policy(
+                "crasher",
+                RestartPolicy {
+                    max_retries: 1,
+                    backoff_secs: 0,
+                    ..RestartPolicy::on_failure()
+                },
+            )
+            .await;
+        manager
+            .start_process(
+                "crasher",
+                Path::new("sh"),
+                &["-c", "echo first >&2; echo boom >&2; exit 3"],
+                None,
+            )
+            .await
+            .unwrap();
+
+        let mut restarts = 0;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            manager.supervise_once().await.unwrap();
+            let info = manager.get_process_info("crasher").await.unwrap();
+            restarts = info.restart_count;
+            if restarts == 1 && !manager.is_process_running("crasher").await {
+                break;
+            }
+        }
+        assert_eq!(restarts, 1);
+
+        // The retry limit leaves the process down with the reason it died
+        manager.supervise_once().await.unwrap();
+        let info = manager.get_process_info("crasher").await.unwrap();
+        assert_eq!(info.restart_count, 1);
+        match info.status {
+            ProcessStatus::Failed {
+                exit_code,
+                stderr_tail,
+                ..
+            } => {
+                assert_eq!(exit_code, Some(3));
+                assert_eq!(stderr_tail, vec!["first", "boom"]);
+            }
+            status => panic!("unexpected status {:?}", status),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_restart_keeps_throttle() {
+        let manager = ProcessManager::new();
+        manager
+            .set_restart_policy(
+                "throttled",
+                RestartPolicy {
+                    max_retries: 1,
+                    backoff_secs: 0,
+                    ..RestartPolicy::on_failure()
+                },
+            )
+            .await;
+        let first_pid = manager
+            .start_process(
+                "throttled",
+                Path::new("sh"),
+                &["-c", "sleep 0.3; exit 1"],
+                None,
+            )
+            .await
+            .unwrap();
+        manager
+            .set_process_intensity("throttled", 40)
+            .await
+            .unwrap();
+
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            manager.supervise_once().await.unwrap();
+            let info = manager.get_process_info("throttled").await.unwrap();
+            if info.restart_count == 1 {
+                break;
+            }
+        }
+
+        let info = manager.get_process_info("throttled").await.unwrap();
+        assert_eq!(info.restart_count, 1);
+        assert_ne!(info.pid, first_pid);
+        let throttles = manager.throttles.lock().await;
+        let throttle = throttles.get("throttled").unwrap();
+        assert_eq!(throttle.pid(), info.pid);
+        assert_eq!(throttle.intensity(), 40);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stop_waits_for_sigterm_then_kills() {
+        let manager = ProcessManager::new();
+        let policy = StopPolicy {
+            timeout: Duration::from_millis(500),
+            rpc_node: None,
+        };
+        for (name, trap) in [("polite", "exit 0"), ("stubborn", "")] {
+            manager.set_stop_policy(name, policy.clone()).await;
+            let script = format!("trap '{trap}' TERM; while :; do sleep 0.1; done");
+            manager
+                .start_process(name, Path::new("sh"), &["-c", &script], None)
+                .await
+                .unwrap();
+        }
+        // Give the shells time to install their traps
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        manager.stop_process("polite").await.unwrap();
+        manager.stop_process("stubborn").await.unwrap();
+        let polite = manager.get_process_info("polite").await.unwrap();
+        assert!(matches!(polite.status, ProcessStatus::Stopped));
+        assert_eq!(polite.stop_outcome, Some(StopOutcome::Graceful));
+        let stubborn = manager.get_process_info("stubborn").await.unwrap();
+        assert_eq!(stubborn.stop_outcome, Some(StopOutcome::Forced));
+
+        assert!(manager.stop_process("missing").await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_follows_daemon_and_reattaches_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("daemon.sh");
+        std::fs::write(
+            &script,
+            "if [ \"$1\" = run ]; then trap 'exit 0' TERM; while :; do sleep 0.1; done; fi\n\
+             sh \"$0\" run \"$1\" &\n\
+             echo $! > \"$1\"\n",
+        )
+        .unwrap();
+        let pidfile = dir.path().join("daemon.pid");
+        let registry = dir.path().join("processes.json");
+        let args = [script.display().to_string(), pidfile.display().to_string()];
+
+        let manager = ProcessManager::with_registry(registry.clone());
+        manager.set_pidfile("daemon", Some(pidfile.clone())).await;
+        manager
+            .start_process("daemon", Path::new("sh"), &[&args[0], &args[1]], None)
+            .await
+            .unwrap();
+
+        // The launcher exits straight away and the daemon it forked is followed instead
+        let mut followed = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if manager.is_process_running("daemon").await {
+                let info = manager.get_process_info("daemon").await.unwrap();
+                if info.detached && process_registry::read_pidfile(&pidfile) == Some(info.pid) {
+                    followed = true;
+                    break;
+                }
+            }
+        }
+        assert!(followed);
+        let daemon_pid = process_registry::read_pidfile(&pidfile).unwrap();
+
+        let restarted = ProcessManager::with_registry(registry.clone());
+        assert_eq!(restarted.reattach().await, vec!["daemon"]);
+        assert!(restarted.is_process_running("daemon").await);
+        restarted
+            .set_stop_policy(
+                "daemon",
+                StopPolicy {
+                    timeout: Duration::from_secs(5),
+                    rpc_node: None,
+                },
+            )
+            .await;
+        restarted.stop_process("daemon").await.unwrap();
+        let info = restarted.get_process_info("daemon").await.unwrap();
+        assert_eq!(info.stop_outcome, Some(StopOutcome::Graceful));
+        assert!(!process_registry::process_matches(daemon_pid, "sh", &args));
+
+        assert!(process_registry::load(&registry).is_empty());
+        assert!(ProcessManager::with_registry(registry)
+            .reattach()
+            .await
+            .is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::rpc::RpcError;
 use crate::{log_security, AppError};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -189,6 +190,43 @@ impl ErrorHandler {
                 recoverable: true,
                 suggested_action: Some("Check data format and try again.".to_string()),
             },
+
+            AppError::Rpc(rpc_error) => {
+                let (code, message, severity, action) = match rpc_error {
+                    e if e.is_warming_up() => (
+                        "NODE_WARMING_UP",
+                        "Node is still starting up",
+                        ErrorSeverity::Low,
+                        "Wait for the node to finish loading, then try again.",
+                    ),
+                    RpcError::Auth(_) => (
+                        "NODE_AUTH_FAILED",
+                        "Node rejected the RPC credentials",
+                        ErrorSeverity::High,
+                        "Check the RPC user and password, or the node's cookie file.",
+                    ),
+                    RpcError::Transport(_) => (
+                        "NODE_UNREACHABLE",
+                        "Could not reach the node",
+                        ErrorSeverity::Medium,
+                        "Make sure the node is running and its RPC port is reachable.",
+                    ),
+                    RpcError::Rpc { .. } | RpcError::InvalidResponse(_) => (
+                        "NODE_RPC_ERROR",
+                        "Node request failed",
+                        ErrorSeverity::Medium,
+                        "Check the node's logs for details.",
+                    ),
+                };
+                UserError {
+                    code: code.to_string(),
+                    message: message.to_string(),
+                    details: Some(rpc_error.to_string()),
+                    severity,
+                    recoverable: true,
+                    suggested_action: Some(action.to_string()),
+                }
+            }
         }
     }
 
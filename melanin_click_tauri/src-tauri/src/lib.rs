@@ -21,6 +21,8 @@ pub enum AppError {
     Stratum(String),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Node RPC error: {0}")]
+    Rpc(#[from] rpc::RpcError),
 }
 
 impl Serialize for AppError {
@@ -37,7 +39,6 @@ impl Serialize for AppError {
 pub struct AppState {
     pub downloads: Mutex<HashMap<String, DownloadProgress>>,
     pub processes: Mutex<HashMap<String, u32>>,
-    pub mining_stats: Mutex<HashMap<String, MiningStats>>,
     pub system_info: Mutex<Option<SystemInfo>>,
 }
 
@@ -65,6 +66,8 @@ pub struct MiningStats {
     pub algorithm: String,
     pub threads: u32,
     pub last_update: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub pool_switches: Vec<pool_failover::PoolSwitchEvent>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,6 +115,42 @@ pub struct MiningConfig {
     pub algorithm: String,
     pub auto_start: bool,
     pub hardware_selection: Vec<String>,
+    // Ordered failover list; the first entry is the primary pool
+    #[serde(default)]
+    pub pools: Vec<PoolEndpoint>,
+    #[serde(default)]
+    pub failover: FailoverPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolEndpoint {
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailoverPolicy {
+    // Fraction of rejected shares above which the pool is abandoned
+    pub max_reject_ratio: f64,
+    // Shares needed on a pool before its reject ratio is trusted
+    pub min_shares_for_ratio: u64,
+    pub failback_after_secs: u64,
+    // How long a pool may stay silent after a stratum failure before it counts as lost;
+    // cpuminer reconnects on its own, which usually takes a few seconds
+    pub disconnect_grace_secs: u64,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            max_reject_ratio: 0.1,
+            min_shares_for_ratio: 20,
+            failback_after_secs: 600,
+            disconnect_grace_secs: 60,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -123,23 +162,43 @@ pub struct NodeStatus {
     pub network: String,
     pub data_dir: String,
     pub config_path: String,
+    pub headers: u64,
+    pub mempool_size: u64,
+    pub uptime_seconds: u64,
+    // Why the status fields are empty while the node is running
+    pub rpc_error: Option<String>,
 }
 
 // Declare modules
+pub mod algorithms;
 pub mod android_lifecycle;
+pub mod autotune;
+pub mod benchmark;
+pub mod block_builder;
 pub mod config;
 pub mod core;
 pub mod error_handler;
 pub mod logging;
 pub mod mining;
+pub mod mining_session;
 pub mod mining_stats;
 pub mod mobile;
 pub mod monitoring;
+pub mod native_miner;
 pub mod node;
+pub mod pool_failover;
+pub mod process_limits;
+pub mod process_logs;
+pub mod process_registry;
+pub mod rpc;
 pub mod solo_mining;
 pub mod stratum;
+pub mod stratum_proxy;
+pub mod stratum_v2;
+pub mod throttle;
 pub mod utils;
 pub mod validation;
+pub mod yespower;
 
 pub fn run() {
     main()
@@ -163,7 +222,21 @@ fn main() {
         .manage(mobile::MobileManager::new())
         .manage(solo_mining::SoloMiner::new())
         .manage(android_lifecycle::AndroidLifecycleManager::new())
-        .setup(|_app| {
+        .setup(|app| {
+            // Miners whose output was parsed cannot be monitored again, so stop any
+            // the last session left running before a new start doubles them up
+            mining_stats::MINING_STATS.reap_orphans();
+
+            // Take back nodes and miners left running by the last session, then restart
+            // crashed ones according to their restart policies
+            tauri::async_runtime::spawn(async {
+                let process_manager = core::get_process_manager();
+                process_manager.reattach().await;
+                process_manager.supervise().await;
+            });
+            tauri::async_runtime::spawn(mining_session::MINING_SESSIONS.supervise());
+            tauri::async_runtime::spawn(process_logs::forward_to_ui(app.handle().clone()));
+
             // Perform additional setup here
             tracing::info!("Tauri application setup complete");
             Ok(())
@@ -177,7 +250,12 @@ fn main() {
             mining::start_enhanced_whive_mining,
             mining::start_enhanced_bitcoin_mining,
             mining::stop_mining,
+            mining::restart_mining,
+            mining::set_mining_intensity,
             mining::get_mining_status,
+            mining::get_mining_sessions,
+            process_logs::get_process_logs,
+            process_logs::list_logged_processes,
             mining::update_mining_config,
             mining::get_mining_pools,
             // Node commands with enhanced functionality
@@ -193,6 +271,7 @@ fn main() {
             monitoring::get_system_info,
             monitoring::get_hardware_info,
             monitoring::benchmark_hardware,
+            autotune::autotune_mining,
             // Validation commands
             validation::validate_bitcoin_address,
             validation::validate_whive_address,
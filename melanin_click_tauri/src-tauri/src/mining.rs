@@ -1,11 +1,23 @@
-use crate::core::{find_executable_in_path, get_process_manager};
+use crate::algorithms;
+use crate::core::{find_executable_in_path, get_process_manager, RestartPolicy};
+use crate::mining_session::{MinerHandle, MinerLauncher, MiningSession, MINING_SESSIONS};
 use crate::mining_stats::MINING_STATS;
+use crate::native_miner::NativeMiner;
+use crate::pool_failover::{probe_pool, PoolFailover};
+use crate::process_limits::ProcessLimits;
+use crate::stratum::PoolClient;
 use crate::validation::{validate_bitcoin_address, validate_whive_address};
-use crate::{AppError, AppState, MiningConfig, MiningStats};
+use crate::{AppError, AppState, MiningConfig, MiningStats, PoolEndpoint};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tauri::State;
 use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+// How often the active pool's health is checked for failover
+const POOL_HEALTH_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MiningPool {
@@ -28,16 +40,6 @@ pub enum PoolStatus {
     Unknown,
 }
 
-#[derive(Debug, Clone)]
-pub struct MiningSession {
-    pub algorithm: String,
-    pub pool_url: String,
-    pub worker_address: String,
-    pub threads: u32,
-    pub started_at: chrono::DateTime<chrono::Utc>,
-    pub stats: MiningStats,
-}
-
 // Download and install mining executables
 #[tauri::command]
 pub async fn download_and_install_miners(state: State<'_, AppState>) -> Result<String, AppError> {
@@ -257,6 +259,8 @@ pub async fn start_enhanced_whive_mining(
     threads: Option<u32>,
     intensity: Option<u8>,
     pool_url: Option<String>,
+    algorithm: Option<String>,
+    autotune: Option<bool>,
     _state: State<'_, AppState>,
 ) -> Result<String, AppError> {
     // Validate address
@@ -267,62 +271,167 @@ pub async fn start_enhanced_whive_mining(
     }
 
     // Check if already mining
-    let process_manager = get_process_manager();
-    if process_manager.is_process_running("whive_miner").await {
+    if MINING_SESSIONS.is_active("whive").await {
         return Err(AppError::Mining(
             "Whive mining is already active".to_string(),
         ));
     }
 
+    // Autotune mode measures this machine first; otherwise a saved tuning fills in
+    // whatever the caller left unset. The sweep holds up this command for every trial
+    // (about four seconds each, a minute or more on a many-core CPU) without reporting
+    // progress, so a UI that wants to show one runs `autotune_mining` before starting.
+    let tuned = if autotune.unwrap_or(false) {
+        tracing::info!("Autotuning yespower before starting Whive mining");
+        Some(crate::autotune::autotune_mining("yespower".to_string(), None).await?)
+    } else {
+        crate::autotune::tuned_setting("yespower")
+    };
+
+    // Setup mining parameters following the exact Whive pool example
+    let num_threads = threads
+        .or(tuned.as_ref().map(|setting| setting.threads as u32))
+        .unwrap_or(2); // Default to 2 threads as in example
+    let mining_intensity = match intensity.or(tuned.as_ref().map(|setting| setting.intensity)) {
+        Some(intensity) => intensity.min(100),
+        None => default_intensity().await,
+    };
+    let pool = pool_url.unwrap_or_else(|| "stratum+tcp://206.189.2.17:3333".to_string());
+    let user_string = format!("{whive_address}.w1"); // Use .w1 worker name as in example
+
+    // algorithm = "yespower" selects the in-process engine instead of minerd
+    let native = match algorithm.as_deref() {
+        Some(name) if algorithms::lookup(name)? != "yespower" => {
+            return Err(AppError::Validation(format!(
+                "Whive is mined with yespower, not {name}"
+            )));
+        }
+        Some(_) => true,
+        None => false,
+    };
+
     // Ensure miners are installed
     let home_dir = dirs::home_dir()
         .ok_or_else(|| AppError::Mining("Could not find home directory".to_string()))?;
     let miners_dir = home_dir.join("melanin_miners");
 
-    let miner_path = find_miner_executable(&miners_dir).await?;
+    // Platforms without a minerd build, such as Android, mine with the native engine
+    let miner_path = if native {
+        None
+    } else {
+        match find_miner_executable(&miners_dir).await {
+            Ok(path) => Some(path),
+            Err(e) => {
+                tracing::warn!("{}; falling back to native Yespower mining", e);
+                None
+            }
+        }
+    };
 
-    // Setup mining parameters following the exact Whive pool example
-    let num_threads = threads.unwrap_or(2); // Default to 2 threads as in example
-    let _mining_intensity = intensity.unwrap_or(85);
-    let pool = pool_url.unwrap_or_else(|| "stratum+tcp://206.189.2.17:3333".to_string());
-    let user_string = format!("{whive_address}.w1"); // Use .w1 worker name as in example
+    let Some(miner_path) = miner_path else {
+        let session = MiningSession::new(
+            "whive",
+            "Yespower (native)",
+            &pool,
+            &user_string,
+            num_threads,
+            mining_intensity,
+        )
+        .with_power_consumption(15.0);
+        let launcher = native_launcher(
+            pool.clone(),
+            user_string,
+            num_threads,
+            algorithms::lookup("yespower")?,
+        );
+        MINING_SESSIONS.start(session, launcher).await?;
+
+        return Ok(format!(
+            "Native Whive mining started successfully. Using {num_threads} threads at {mining_intensity}% intensity on Yespower algorithm targeting pool: {pool}"
+        ));
+    };
 
     // Prepare mining command exactly as shown in example:
     // ./minerd -a yespower -o stratum+tcp://206.189.2.17:3333 -u WALLET_ADDRESS.worker -t 2
-    let num_threads_str = num_threads.to_string();
     let args = vec![
-        "-a",
-        "yespower", // Algorithm
-        "-o",
-        &pool, // Pool URL
-        "-u",
-        &user_string, // User.worker (WALLET_ADDRESS.w1)
-        "-t",
-        &num_threads_str, // Number of threads
+        "-a".to_string(),
+        algorithms::miner_flag("yespower")?.to_string(), // Algorithm
+        "-o".to_string(),
+        pool.clone(), // Pool URL
+        "-u".to_string(),
+        user_string.clone(), // User.worker (WALLET_ADDRESS.w1)
+        "-t".to_string(),
+        num_threads.to_string(), // Number of threads
     ];
 
-    // Start mining process with stdout capture for real-time stats
-    let mut cmd = Command::new(&miner_path);
-    cmd.args(&args)
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
-
-    let child = cmd
-        .spawn()
-        .map_err(|e| AppError::Mining(format!("Failed to start mining process: {e}")))?;
-
-    // Start monitoring the process for real-time statistics
-    MINING_STATS
-        .start_monitoring_process("whive", child)
-        .await?;
-
-    // Note: Process is now managed by MINING_STATS, no need for separate registration
+    let session = MiningSession::new(
+        "whive",
+        "Yespower",
+        &pool,
+        &user_string,
+        num_threads,
+        mining_intensity,
+    )
+    .with_power_consumption(15.0);
+    let limits = miner_limits().await?;
+    let launcher: MinerLauncher = Arc::new(move || {
+        let miner_path = miner_path.clone();
+        let args = args.clone();
+        let limits = limits.clone();
+        Box::pin(async move {
+            // Start mining process with stdout capture for real-time stats
+            let mut cmd = Command::new(&miner_path);
+            cmd.args(&args)
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped());
+            limits.apply(&mut cmd);
+            let child = cmd
+                .spawn()
+                .map_err(|e| AppError::Mining(format!("Failed to start mining process: {e}")))?;
+
+            // Start monitoring the process for real-time statistics
+            MINING_STATS
+                .start_monitoring_process("whive", child)
+                .await?;
+            Ok(MinerHandle::Monitored { supervisor: None })
+        })
+    });
+    MINING_SESSIONS.start(session, launcher).await?;
 
     Ok(format!(
-        "Whive mining started successfully. Using {num_threads} threads on Yespower algorithm targeting pool: {pool}"
+        "Whive mining started successfully. Using {num_threads} threads at {mining_intensity}% intensity on Yespower algorithm targeting pool: {pool}"
     ))
 }
 
+// Connects to the pool and hashes its jobs in-process, for miners without a binary
+fn native_launcher(
+    pool_url: String,
+    user_string: String,
+    threads: u32,
+    algorithm: &'static str,
+) -> MinerLauncher {
+    Arc::new(move || {
+        let pool_url = pool_url.clone();
+        let user_string = user_string.clone();
+        Box::pin(async move {
+            let client = Arc::new(PoolClient::from_url(&pool_url, &user_string, "x")?);
+            client.connect().await?;
+
+            let miner = Arc::new(NativeMiner::new(threads as usize).with_algorithm(algorithm));
+            let task = tokio::spawn({
+                let miner = miner.clone();
+                async move {
+                    if let Err(e) = miner.run(client.clone()).await {
+                        tracing::error!("Native miner failed: {}", e);
+                    }
+                    let _ = client.disconnect().await;
+                }
+            });
+            Ok(MinerHandle::Native { miner, task })
+        })
+    })
+}
+
 // Enhanced Bitcoin Mining with proper CPU miner setup
 #[tauri::command]
 pub async fn start_enhanced_bitcoin_mining(
@@ -331,7 +440,7 @@ pub async fn start_enhanced_bitcoin_mining(
     pool_name: String,
     threads: Option<u32>,
     mining_mode: Option<String>,
-    state: State<'_, AppState>,
+    _state: State<'_, AppState>,
 ) -> Result<String, AppError> {
     // Validate address
     if !validate_bitcoin_address(bitcoin_address.clone()).await? {
@@ -341,8 +450,7 @@ pub async fn start_enhanced_bitcoin_mining(
     }
 
     // Check if already mining
-    let process_manager = get_process_manager();
-    if process_manager.is_process_running("bitcoin_miner").await {
+    if MINING_SESSIONS.is_active("bitcoin").await {
         return Err(AppError::Mining(
             "Bitcoin mining is already active".to_string(),
         ));
@@ -351,12 +459,13 @@ pub async fn start_enhanced_bitcoin_mining(
     let mode = mining_mode.unwrap_or_else(|| "cpu".to_string());
 
     match mode.as_str() {
-        "cpu" => {
-            start_bitcoin_cpu_mining(bitcoin_address, worker_name, pool_name, threads, state).await
+        "cpu" => start_bitcoin_cpu_mining(bitcoin_address, worker_name, pool_name, threads).await,
+        "native" => {
+            start_bitcoin_native_mining(bitcoin_address, worker_name, pool_name, threads).await
         }
-        "stick" => start_bitcoin_stick_mining(bitcoin_address, worker_name, pool_name, state).await,
+        "stick" => start_bitcoin_stick_mining(bitcoin_address, worker_name, pool_name).await,
         _ => Err(AppError::Mining(
-            "Invalid mining mode. Use 'cpu' or 'stick'".to_string(),
+            "Invalid mining mode. Use 'cpu', 'native' or 'stick'".to_string(),
         )),
     }
 }
@@ -366,7 +475,6 @@ async fn start_bitcoin_cpu_mining(
     worker_name: String,
     pool_name: String,
     threads: Option<u32>,
-    state: State<'_, AppState>,
 ) -> Result<String, AppError> {
     // Find miner executable
     let home_dir = dirs::home_dir()
@@ -375,8 +483,84 @@ async fn start_bitcoin_cpu_mining(
     let miners_dir = home_dir.join("melanin_miners");
     let miner_path = find_miner_executable(&miners_dir).await?;
 
-    // Enhanced pool selection with the exact example format
-    let (pool_url, pool_description) = match pool_name.as_str() {
+    let (pool_url, pool_description) = bitcoin_pool_by_name(&pool_name);
+
+    // A saved tuning fills in what the caller left unset; one thread is the conservative default
+    let tuned = crate::autotune::tuned_setting("sha256d");
+    let num_threads = threads
+        .or(tuned.as_ref().map(|setting| setting.threads as u32))
+        .unwrap_or(1);
+    let intensity = match tuned {
+        Some(setting) => setting.intensity,
+        None => default_intensity().await,
+    };
+    let user_string = format!("{bitcoin_address}.{worker_name}");
+
+    // A saved pool list takes precedence over the named pool and enables failover
+    let saved_config = load_mining_config().await;
+    let failover_policy = saved_config
+        .as_ref()
+        .map(|c| c.failover.clone())
+        .unwrap_or_default();
+    let mut pools = saved_config.map(|c| c.pools).unwrap_or_default();
+    if pools.is_empty() {
+        pools.push(PoolEndpoint {
+            url: pool_url.to_string(),
+            username: user_string.clone(),
+            password: "x".to_string(),
+        });
+    }
+    for pool in pools.iter_mut().filter(|p| p.username.is_empty()) {
+        pool.username = user_string.clone();
+    }
+
+    let pool_url = pools[0].url.clone();
+    let session = MiningSession::new(
+        "bitcoin",
+        "SHA-256",
+        &pool_url,
+        &user_string,
+        num_threads,
+        intensity,
+    )
+    .with_power_consumption(30.0); // Lower power for CPU Bitcoin mining
+    let limits = miner_limits().await?;
+    // Shared by every launch, so a crash restart stays on the pool failover moved to
+    let failover = Arc::new(Mutex::new(PoolFailover::new(
+        pools,
+        failover_policy,
+        chrono::Utc::now(),
+    )));
+    let launcher: MinerLauncher = Arc::new(move || {
+        let miner_path = miner_path.clone();
+        let limits = limits.clone();
+        let failover = Arc::clone(&failover);
+        Box::pin(async move {
+            let pool = failover.lock().await.current().clone();
+            spawn_bitcoin_cpu_miner(&miner_path, &pool, num_threads, &limits).await?;
+            let supervisor = tokio::spawn(supervise_bitcoin_pools(
+                miner_path,
+                failover,
+                num_threads,
+                limits,
+            ));
+            Ok(MinerHandle::Monitored {
+                supervisor: Some(supervisor),
+            })
+        })
+    });
+    MINING_SESSIONS.start(session, launcher).await?;
+    // Switch history covers this session only, across its crash restarts
+    MINING_STATS.clear_pool_switches("bitcoin").await;
+
+    Ok(format!(
+        "Bitcoin mining started successfully. Using {num_threads} threads on {pool_name} - {pool_description}"
+    ))
+}
+
+// Enhanced pool selection with the exact example format
+fn bitcoin_pool_by_name(pool_name: &str) -> (&'static str, &'static str) {
+    match pool_name {
         "Public Pool" => (
             "stratum+tcp://public-pool.io:21496",
             "Public Pool - Example from documentation",
@@ -409,59 +593,160 @@ async fn start_bitcoin_cpu_mining(
             "stratum+tcp://public-pool.io:21496",
             "Public Pool (Default)",
         ), // Use example pool as default
-    };
-
-    let num_threads = threads.unwrap_or(1); // Conservative for Bitcoin CPU mining
-    let user_string = format!("{bitcoin_address}.{worker_name}");
+    }
+}
 
+async fn spawn_bitcoin_cpu_miner(
+    miner_path: &Path,
+    pool: &PoolEndpoint,
+    threads: u32,
+    limits: &ProcessLimits,
+) -> Result<(), AppError> {
     // Prepare mining command exactly as shown in example:
     // ./minerd -a sha256d -o stratum+tcp://public-pool.io:21496 -u bc1q9rqda0ppf8phfe9e57k4r6qecmwyqcdltn0ktt.waka -p x
+    let threads = threads.to_string();
     let args = vec![
         "-a",
-        "sha256d", // Algorithm
+        algorithms::miner_flag("sha256d")?, // Algorithm
         "-o",
-        pool_url, // Pool URL
+        &pool.url, // Pool URL
         "-u",
-        &user_string, // User.worker
+        &pool.username, // User.worker
         "-p",
-        "x", // Password
+        &pool.password, // Password
+        "-t",
+        &threads, // Thread count
     ];
 
     // Start mining process with stdout capture for real-time stats
-    let mut cmd = Command::new(&miner_path);
+    let mut cmd = Command::new(miner_path);
     cmd.args(&args)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped());
+    limits.apply(&mut cmd);
 
     let child = cmd
         .spawn()
         .map_err(|e| AppError::Mining(format!("Failed to start Bitcoin mining process: {e}")))?;
 
-    // Start monitoring the process for real-time statistics
+    // Start monitoring the process for real-time statistics; this replaces a previous miner
     MINING_STATS
         .start_monitoring_process("bitcoin", child)
-        .await?;
+        .await
+}
+
+// Restarts the miner on another pool whenever the failover policy calls for a switch
+async fn supervise_bitcoin_pools(
+    miner_path: PathBuf,
+    failover: Arc<Mutex<PoolFailover>>,
+    threads: u32,
+    limits: ProcessLimits,
+) {
+    loop {
+        tokio::time::sleep(POOL_HEALTH_INTERVAL).await;
+
+        // Stats disappear once mining is stopped
+        let Some(stats) = MINING_STATS.get_stats("bitcoin").await else {
+            break;
+        };
+
+        // An exited miner is the session supervisor's to restart; this loop only moves pools
+        if !MINING_STATS.is_process_running("bitcoin").await {
+            continue;
+        }
+
+        let mut failover = failover.lock().await;
+        let now = chrono::Utc::now();
+        let health = stats.pool_health(failover.disconnect_grace(), now);
+        let switch = match failover.evaluate(&health, now) {
+            Some(event) => Some(event),
+            // Only go back to the primary once it accepts connections again
+            None if failover.failback_due(now) => {
+                let reachable = probe_pool(&failover.primary().url).await;
+                failover.failback(reachable, now)
+            }
+            None => None,
+        };
+
+        if let Some(event) = switch {
+            MINING_STATS.record_pool_switch("bitcoin", event).await;
+            if let Err(e) =
+                spawn_bitcoin_cpu_miner(&miner_path, failover.current(), threads, &limits).await
+            {
+                tracing::error!(
+                    "Failed to restart miner on {}: {}",
+                    failover.current().url,
+                    e
+                );
+            }
+        }
+    }
 
-    // Note: Process is now managed by MINING_STATS, no need for separate registration
-    let mining_stats = MiningStats {
-        hashrate: 0.0,
-        accepted_shares: 0,
-        rejected_shares: 0,
-        uptime: 0,
-        temperature: 35.0,
-        power_consumption: 30.0, // Lower power for CPU Bitcoin mining
-        estimated_earnings: 0.0,
-        pool_url: pool_url.to_string(),
-        algorithm: "SHA-256".to_string(),
-        threads: num_threads,
-        last_update: chrono::Utc::now(),
+    tracing::debug!("Bitcoin pool supervisor stopped");
+}
+
+async fn load_mining_config() -> Option<MiningConfig> {
+    let config_file = dirs::home_dir()?
+        .join(".melanin_click")
+        .join("mining_config.json");
+    let contents = tokio::fs::read_to_string(config_file).await.ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+// Limits from the saved hardware selection, applied to every miner process we start
+async fn miner_limits() -> Result<ProcessLimits, AppError> {
+    match load_mining_config().await {
+        Some(config) => ProcessLimits::from_hardware_selection(&config.hardware_selection),
+        None => Ok(ProcessLimits::default()),
+    }
+}
+
+// Intensity for starts that do not pass one: the saved mining config, else the app config
+async fn default_intensity() -> u8 {
+    let intensity = match load_mining_config().await {
+        Some(config) => config.mining_intensity,
+        None => crate::config::get_config().mining_intensity,
     };
+    intensity.min(100)
+}
 
-    let mut stats = state.mining_stats.lock().await;
-    stats.insert("bitcoin".to_string(), mining_stats);
+// Hashes in-process instead of running a downloaded miner binary
+async fn start_bitcoin_native_mining(
+    bitcoin_address: String,
+    worker_name: String,
+    pool_name: String,
+    threads: Option<u32>,
+) -> Result<String, AppError> {
+    let (pool_url, pool_description) = bitcoin_pool_by_name(&pool_name);
+    let tuned = crate::autotune::tuned_setting("sha256d");
+    let num_threads = threads
+        .or(tuned.as_ref().map(|setting| setting.threads as u32))
+        .unwrap_or(1);
+    let intensity = match tuned {
+        Some(setting) => setting.intensity,
+        None => default_intensity().await,
+    };
+    let user_string = format!("{bitcoin_address}.{worker_name}");
+
+    let session = MiningSession::new(
+        "bitcoin",
+        "SHA-256d (native)",
+        pool_url,
+        &user_string,
+        num_threads,
+        intensity,
+    )
+    .with_power_consumption(30.0);
+    let launcher = native_launcher(
+        pool_url.to_string(),
+        user_string,
+        num_threads,
+        algorithms::lookup("sha256d")?,
+    );
+    MINING_SESSIONS.start(session, launcher).await?;
 
     Ok(format!(
-        "Bitcoin mining started successfully. Using {num_threads} threads on {pool_name} - {pool_description}"
+        "Native Bitcoin mining started successfully. Using {num_threads} threads on {pool_name} - {pool_description}"
     ))
 }
 
@@ -469,7 +754,6 @@ async fn start_bitcoin_stick_mining(
     bitcoin_address: String,
     worker_name: String,
     pool_name: String,
-    state: State<'_, AppState>,
 ) -> Result<String, AppError> {
     // Check for cgminer installation
     let home_dir = dirs::home_dir()
@@ -495,7 +779,7 @@ async fn start_bitcoin_stick_mining(
     let user_string = format!("{bitcoin_address}.{worker_name}");
 
     // Prepare cgminer command for USB stick miners
-    let args = vec![
+    let args = [
         "--bmsc-options",
         "115200:20", // BMSC options
         "--bmsc-freq",
@@ -510,34 +794,40 @@ async fn start_bitcoin_stick_mining(
         "--api-port",
         "4028",    // API port
         "--quiet", // Reduce output
-    ];
-
-    // Start cgminer process
-    let process_manager = get_process_manager();
-    let pid = process_manager
-        .start_process("bitcoin_stick_miner", &cgminer_path, &args, None)
-        .await?;
-
-    // Initialize mining stats for stick miner
-    let mining_stats = MiningStats {
-        hashrate: 0.0,
-        accepted_shares: 0,
-        rejected_shares: 0,
-        uptime: 0,
-        temperature: 45.0,       // Higher temp for ASIC stick
-        power_consumption: 75.0, // Higher power for stick miner
-        estimated_earnings: 0.0,
-        pool_url: pool_url.to_string(),
-        algorithm: "SHA-256 (ASIC)".to_string(),
-        threads: 1, // Stick miners are single threaded
-        last_update: chrono::Utc::now(),
-    };
-
-    let mut stats = state.mining_stats.lock().await;
-    stats.insert("bitcoin_stick".to_string(), mining_stats);
+    ]
+    .map(String::from);
+
+    // Stick miners are single threaded and draw more power than a CPU
+    let session = MiningSession::new(
+        "bitcoin_stick",
+        "SHA-256 (ASIC)",
+        pool_url,
+        &user_string,
+        1,
+        100,
+    )
+    .with_power_consumption(75.0);
+    let launcher: MinerLauncher = Arc::new(move || {
+        let cgminer_path = cgminer_path.clone();
+        let args = args.clone();
+        Box::pin(async move {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            get_process_manager()
+                .start_process("bitcoin_stick_miner", &cgminer_path, &args, None)
+                .await?;
+            Ok(MinerHandle::Managed("bitcoin_stick_miner".to_string()))
+        })
+    });
+    get_process_manager()
+        .set_restart_policy("bitcoin_stick_miner", RestartPolicy::on_failure())
+        .await;
+    get_process_manager()
+        .set_process_limits("bitcoin_stick_miner", miner_limits().await?)
+        .await;
+    MINING_SESSIONS.start(session, launcher).await?;
 
     Ok(format!(
-        "Bitcoin stick mining started successfully with PID: {pid}. Using cgminer on {pool_name} - {pool_description}"
+        "Bitcoin stick mining started successfully. Using cgminer on {pool_name} - {pool_description}"
     ))
 }
 
@@ -545,56 +835,55 @@ async fn start_bitcoin_stick_mining(
 #[tauri::command]
 pub async fn stop_mining(
     mining_type: String,
-    state: State<'_, AppState>,
+    _state: State<'_, AppState>,
 ) -> Result<String, AppError> {
-    // Stop monitoring and kill the process via the stats collector
-    MINING_STATS.stop_monitoring(&mining_type).await?;
+    MINING_SESSIONS.stop(&mining_type).await;
 
-    // Also stop via process manager for compatibility
-    let process_name = format!("{}_miner", mining_type);
-    let process_manager = get_process_manager();
-    if process_manager.is_process_running(&process_name).await {
-        let _ = process_manager.stop_process(&process_name).await; // Ignore errors since process may already be dead
+    Ok(format!("{mining_type} mining stopped successfully"))
+}
+
+// Stops a miner and starts it again with the same settings
+#[tauri::command]
+pub async fn restart_mining(mining_type: String) -> Result<String, AppError> {
+    MINING_SESSIONS.restart(&mining_type).await?;
+
+    Ok(format!("{mining_type} mining restarted successfully"))
+}
+
+// Changes how much CPU time a running miner may use, in percent
+#[tauri::command]
+pub async fn set_mining_intensity(mining_type: String, intensity: u8) -> Result<String, AppError> {
+    if intensity > 100 {
+        return Err(AppError::Validation(
+            "Mining intensity must be between 0 and 100".to_string(),
+        ));
     }
 
-    // Clear mining stats from old system
-    let mut stats = state.mining_stats.lock().await;
-    stats.remove(&mining_type);
+    MINING_SESSIONS
+        .set_intensity(&mining_type, intensity)
+        .await?;
 
-    Ok(format!("{mining_type} mining stopped successfully"))
+    Ok(format!(
+        "{mining_type} mining intensity set to {intensity}%"
+    ))
 }
 
 // Get Mining Status with enhanced monitoring
 #[tauri::command]
 pub async fn get_mining_status(
     mining_type: String,
-    state: State<'_, AppState>,
+    _state: State<'_, AppState>,
 ) -> Result<Option<MiningStats>, AppError> {
-    let stats = state.mining_stats.lock().await;
-    if let Some(mut mining_stats) = stats.get(&mining_type).cloned() {
-        // Update real-time stats if mining is active
-        let process_manager = get_process_manager();
-        let process_name = format!("{}_miner", mining_type);
-
-        if process_manager.is_process_running(&process_name).await {
-            // Update uptime
-            let uptime = chrono::Utc::now()
-                .signed_duration_since(mining_stats.last_update)
-                .num_seconds() as u64;
-            mining_stats.uptime += uptime;
-            mining_stats.last_update = chrono::Utc::now();
-
-            // TODO: Parse miner output for real hashrate, shares, etc.
-            // For now, simulate some activity
-            if mining_stats.hashrate == 0.0 {
-                mining_stats.hashrate = if mining_type == "whive" { 450.0 } else { 25.0 };
-            }
-        }
+    Ok(MINING_SESSIONS
+        .status(&mining_type)
+        .await
+        .map(|session| session.stats))
+}
 
-        Ok(Some(mining_stats))
-    } else {
-        Ok(None)
-    }
+// Every mining session, including miners that have exited since they were started
+#[tauri::command]
+pub async fn get_mining_sessions() -> Result<Vec<MiningSession>, AppError> {
+    Ok(MINING_SESSIONS.sessions().await)
 }
 
 // Update Mining Configuration
@@ -792,11 +1081,12 @@ pub async fn test_simple_mining(
 
     let user_string = format!("{}.worker", address);
     let threads_str = threads.to_string();
+    let miner_flag = algorithms::miner_flag(&algorithm)?;
 
     // Simple command: minerd -a ALGO -o POOL -u ADDRESS.worker -t THREADS
     let args = vec![
         "-a",
-        &algorithm,
+        miner_flag,
         "-o",
         &pool,
         "-u",
@@ -811,8 +1101,9 @@ pub async fn test_simple_mining(
         .await?;
 
     Ok(format!(
-        "Started miner with PID: {pid} - Command: {} -a {algorithm} -o {pool} -u {user_string} -t {threads}",
-        miner_path.display()
+        "Started miner with PID: {pid} - Command: {} -a {} -o {pool} -u {user_string} -t {threads}",
+        miner_path.display(),
+        miner_flag
     ))
 }
 
@@ -845,8 +1136,9 @@ pub async fn start_simple_whive_mining(
 
     // Exact command from Python script
     let cmd = format!(
-        "{} -a yespower -o stratum+tcp://206.189.2.17:3333 -u {} -t {}",
+        "{} -a {} -o stratum+tcp://206.189.2.17:3333 -u {} -t {}",
         minerd_path.display(),
+        algorithms::miner_flag("yespower")?,
         user_string,
         num_threads
     );
@@ -893,8 +1185,9 @@ pub async fn start_simple_bitcoin_mining(
 
     // Exact command from Python script
     let cmd = format!(
-        "{} -a sha256d -o stratum+tcp://public-pool.io:21496 -u {} -p x",
+        "{} -a {} -o stratum+tcp://public-pool.io:21496 -u {} -p x",
         minerd_path.display(),
+        algorithms::miner_flag("sha256d")?,
         user_string
     );
 
@@ -0,0 +1,727 @@
+use crate::core::{get_process_manager, RestartPolicy, RestartTracker};
+use crate::mining_stats::MINING_STATS;
+use crate::monitoring::get_cpu_temperature;
+use crate::native_miner::NativeMiner;
+use crate::{AppError, MiningStats};
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+// How often the supervisor looks for miners that have exited
+const SUPERVISOR_INTERVAL: Duration = Duration::from_secs(5);
+
+// What a session's miner runs as, which decides how it is stopped and measured
+pub enum MinerHandle {
+    // External miner whose output MINING_STATS parses under the session's mining type,
+    // with an optional task that supervises it
+    Monitored {
+        supervisor: Option<JoinHandle<()>>,
+    },
+    // In-process hashing threads, driven by a task that ends when the miner stops
+    Native {
+        miner: Arc<NativeMiner>,
+        task: JoinHandle<()>,
+    },
+    // Process started through the ProcessManager under this name
+    Managed(String),
+    // Stands in while the launcher runs, so the session lock is not held across a pool
+    // dial and a second start is turned away
+    Starting,
+}
+
+// Starts a session's miner; called again on every restart
+pub type MinerLauncher =
+    Arc<dyn Fn() -> BoxFuture<'static, Result<MinerHandle, AppError>> + Send + Sync>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MiningSession {
+    pub mining_type: String,
+    pub algorithm: String,
+    pub pool_url: String,
+    pub worker_address: String,
+    pub threads: u32,
+    pub intensity: u8,
+    pub started_at: DateTime<Utc>,
+    pub restarts: u32,
+    // False once the miner has exited without being stopped
+    pub running: bool,
+    // Applied by the supervisor when the miner exits on its own
+    pub restart_policy: RestartPolicy,
+    // Why the miner last exited on its own
+    pub last_failure: Option<String>,
+    pub stats: MiningStats,
+}
+
+impl MiningSession {
+    pub fn new(
+        mining_type: &str,
+        algorithm: &str,
+        pool_url: &str,
+        worker_address: &str,
+        threads: u32,
+        intensity: u8,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            mining_type: mining_type.to_string(),
+            algorithm: algorithm.to_string(),
+            pool_url: pool_url.to_string(),
+            worker_address: worker_address.to_string(),
+            threads,
+            intensity: intensity.min(100),
+            started_at: now,
+            restarts: 0,
+            running: true,
+            restart_policy: RestartPolicy::on_failure(),
+            last_failure: None,
+            stats: MiningStats {
+                hashrate: 0.0,
+                accepted_shares: 0,
+                rejected_shares: 0,
+                uptime: 0,
+                temperature: 0.0,
+                power_consumption: 0.0,
+                estimated_earnings: 0.0,
+                pool_url: pool_url.to_string(),
+                algorithm: algorithm.to_string(),
+                threads,
+                last_update: now,
+                pool_switches: Vec::new(),
+            },
+        }
+    }
+
+    pub fn with_power_consumption(mut self, watts: f64) -> Self {
+        self.stats.power_consumption = watts;
+        self
+    }
+
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    // Counters start over with every launch of the miner
+    fn reset(&mut self) {
+        self.started_at = Utc::now();
+        self.running = true;
+        self.stats.hashrate = 0.0;
+        self.stats.accepted_shares = 0;
+        self.stats.rejected_shares = 0;
+        self.stats.uptime = 0;
+        self.stats.estimated_earnings = 0.0;
+        self.stats.pool_url = self.pool_url.clone();
+        self.stats.pool_switches.clear();
+    }
+}
+
+struct ActiveSession {
+    session: MiningSession,
+    handle: MinerHandle,
+    launcher: MinerLauncher,
+    tracker: RestartTracker,
+    // Set once the supervisor has handled the exit of the current launch
+    exit_handled: bool,
+}
+
+// Owns every running miner, whatever it runs as, keyed by mining type
+pub struct MiningSessionManager {
+    sessions: Mutex<HashMap<String, ActiveSession>>,
+}
+
+impl Default for MiningSessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MiningSessionManager {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Launches the miner and records the session. A session whose miner has exited
+    // is replaced; a running or starting one is an error.
+    pub async fn start(
+        &self,
+        session: MiningSession,
+        launcher: MinerLauncher,
+    ) -> Result<(), AppError> {
+        let mining_type = session.mining_type.clone();
+        let (pool_url, threads) = (session.pool_url.clone(), session.threads);
+        let stale = {
+            let mut sessions = self.sessions.lock().await;
+            if let Some(existing) = sessions.get(&mining_type) {
+                if matches!(existing.handle, MinerHandle::Starting) {
+                    return Err(AppError::Mining(format!(
+                        "{mining_type} mining is already starting"
+                    )));
+                }
+                if is_running(&mining_type, &existing.handle).await {
+                    return Err(AppError::Mining(format!(
+                        "{mining_type} mining is already active"
+                    )));
+                }
+            }
+            let stale = sessions.remove(&mining_type);
+            sessions.insert(
+                mining_type.clone(),
+                ActiveSession {
+                    session,
+                    handle: MinerHandle::Starting,
+                    launcher: Arc::clone(&launcher),
+                    tracker: RestartTracker::default(),
+                    exit_handled: false,
+                },
+            );
+            stale
+        };
+        if let Some(stale) = stale {
+            stop_handle(&mining_type, stale.handle).await;
+        }
+
+        let launched = launcher().await;
+        let mut sessions = self.sessions.lock().await;
+        let installed = match launched {
+            Ok(handle) => install(&mut sessions, &mining_type, handle)
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
+        match installed {
+            Ok(()) => {
+                info!(
+                    "Started {} mining session on {} with {} threads",
+                    mining_type, pool_url, threads
+                );
+                Ok(())
+            }
+            Err(e) => {
+                if is_starting(&sessions, &mining_type) {
+                    sessions.remove(&mining_type);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Stops the miner and returns its final record, or None if nothing was mining
+    pub async fn stop(&self, mining_type: &str) -> Option<MiningSession> {
+        let active = self.sessions.lock().await.remove(mining_type)?;
+        let mut session = active.session;
+        refresh(&mut session, &active.handle).await;
+        stop_handle(mining_type, active.handle).await;
+        info!("Stopped {} mining session", mining_type);
+        Some(session)
+    }
+
+    // Stops the miner and launches it again with the session's settings
+    pub async fn restart(&self, mining_type: &str) -> Result<(), AppError> {
+        self.sessions
+            .lock()
+            .await
+            .get_mut(mining_type)
+            .ok_or_else(|| AppError::Mining(format!("{mining_type} mining is not active")))?
+            .tracker
+            .cancel();
+        self.relaunch(mining_type).await
+    }
+
+    // Like start, the launcher runs without the session lock held
+    async fn relaunch(&self, mining_type: &str) -> Result<(), AppError> {
+        let (previous, launcher) = {
+            let mut sessions = self.sessions.lock().await;
+            let active = sessions
+                .get_mut(mining_type)
+                .ok_or_else(|| AppError::Mining(format!("{mining_type} mining is not active")))?;
+            if matches!(active.handle, MinerHandle::Starting) {
+                return Err(AppError::Mining(format!(
+                    "{mining_type} mining is already starting"
+                )));
+            }
+            let previous = std::mem::replace(&mut active.handle, MinerHandle::Starting);
+            (previous, Arc::clone(&active.launcher))
+        };
+        stop_handle(mining_type, previous).await;
+
+        let launched = launcher().await;
+        let mut sessions = self.sessions.lock().await;
+        let installed = match launched {
+            Ok(handle) => install(&mut sessions, mining_type, handle).await,
+            Err(e) => Err(e),
+        };
+        match installed {
+            Ok(active) => {
+                active.session.reset();
+                active.session.restarts += 1;
+                info!(
+                    "Restarted {} mining session ({} restarts)",
+                    mining_type, active.session.restarts
+                );
+                Ok(())
+            }
+            Err(e) => {
+                // Leave the session down so the restart policy can have another go
+                if let Some(active) = sessions.get_mut(mining_type) {
+                    if matches!(active.handle, MinerHandle::Starting) {
+                        active.handle = MinerHandle::Monitored { supervisor: None };
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Records why exited miners stopped and relaunches those their policy allows
+    pub async fn supervise_once(&self) {
+        let now = Utc::now();
+        let mut due = Vec::new();
+        {
+            let mut sessions = self.sessions.lock().await;
+            for (mining_type, active) in sessions.iter_mut() {
+                // Managed processes are restarted by the ProcessManager's own policy,
+                // and a starting session has no miner to check yet
+                if matches!(
+                    active.handle,
+                    MinerHandle::Managed(_) | MinerHandle::Starting
+                ) {
+                    continue;
+                }
+
+                if !active.exit_handled && !is_running(mining_type, &active.handle).await {
+                    active.exit_handled = true;
+                    let (succeeded, reason) = exit_reason(mining_type, &active.handle).await;
+                    warn!("{} miner exited: {}", mining_type, reason);
+                    active.session.running = false;
+                    active.session.last_failure = (!succeeded).then_some(reason);
+                    if active
+                        .tracker
+                        .on_exit(&active.session.restart_policy, succeeded, now)
+                    {
+                        info!("Scheduled restart of {} miner", mining_type);
+                    }
+                }
+
+                if active.tracker.is_due(now) {
+                    active.tracker.record_restart(now);
+                    due.push(mining_type.clone());
+                }
+            }
+        }
+
+        // Other sessions stay reachable while these relaunch
+        for mining_type in due {
+            if let Err(e) = self.relaunch(&mining_type).await {
+                error!("Failed to restart {} miner: {}", mining_type, e);
+                if let Some(active) = self.sessions.lock().await.get_mut(&mining_type) {
+                    active.exit_handled = true;
+                    active.session.last_failure = Some(e.to_string());
+                    active
+                        .tracker
+                        .on_exit(&active.session.restart_policy, false, now);
+                }
+            }
+        }
+    }
+
+    // Background supervisor applying restart policies; runs for the life of the app
+    pub async fn supervise(&self) {
+        loop {
+            tokio::time::sleep(SUPERVISOR_INTERVAL).await;
+            self.supervise_once().await;
+        }
+    }
+
+    // Current record with live stats from the miner
+    pub async fn status(&self, mining_type: &str) -> Option<MiningSession> {
+        let mut sessions = self.sessions.lock().await;
+        let active = sessions.get_mut(mining_type)?;
+        refresh(&mut active.session, &active.handle).await;
+        Some(active.session.clone())
+    }
+
+    pub async fn sessions(&self) -> Vec<MiningSession> {
+        let mut sessions = self.sessions.lock().await;
+        let mut records = Vec::with_capacity(sessions.len());
+        for active in sessions.values_mut() {
+            refresh(&mut active.session, &active.handle).await;
+            records.push(active.session.clone());
+        }
+        records.sort_by(|a, b| a.mining_type.cmp(&b.mining_type));
+        records
+    }
+
+    pub async fn is_active(&self, mining_type: &str) -> bool {
+        match self.sessions.lock().await.get(mining_type) {
+            Some(active) => is_running(mining_type, &active.handle).await,
+            None => false,
+        }
+    }
+
+    // Changes how much CPU time the miner may use, kept across restarts
+    pub async fn set_intensity(&self, mining_type: &str, intensity: u8) -> Result<(), AppError> {
+        let mut sessions = self.sessions.lock().await;
+        let active = sessions
+            .get_mut(mining_type)
+            .ok_or_else(|| AppError::Mining(format!("{mining_type} mining is not active")))?;
+        apply_intensity(mining_type, &active.handle, intensity).await?;
+        active.session.intensity = intensity.min(100);
+        Ok(())
+    }
+}
+
+fn is_starting(sessions: &HashMap<String, ActiveSession>, mining_type: &str) -> bool {
+    sessions
+        .get(mining_type)
+        .is_some_and(|active| matches!(active.handle, MinerHandle::Starting))
+}
+
+// Puts a launched miner in place of the Starting placeholder, at the session's current
+// intensity. A session stopped while its miner started gets that miner stopped too.
+async fn install<'a>(
+    sessions: &'a mut HashMap<String, ActiveSession>,
+    mining_type: &str,
+    handle: MinerHandle,
+) -> Result<&'a mut ActiveSession, AppError> {
+    if !is_starting(sessions, mining_type) {
+        stop_handle(mining_type, handle).await;
+        return Err(AppError::Mining(format!(
+            "{mining_type} mining was stopped while starting"
+        )));
+    }
+    let active = sessions
+        .get_mut(mining_type)
+        .expect("starting session is present");
+    if let Err(e) = apply_intensity(mining_type, &handle, active.session.intensity).await {
+        stop_handle(mining_type, handle).await;
+        return Err(e);
+    }
+    active.handle = handle;
+    active.exit_handled = false;
+    Ok(active)
+}
+
+async fn is_running(mining_type: &str, handle: &MinerHandle) -> bool {
+    match handle {
+        MinerHandle::Monitored { .. } => MINING_STATS.is_process_running(mining_type).await,
+        MinerHandle::Native { task, .. } => !task.is_finished(),
+        MinerHandle::Managed(name) => get_process_manager().is_process_running(name).await,
+        MinerHandle::Starting => true,
+    }
+}
+
+// Whether the miner exited cleanly, and how
+async fn exit_reason(mining_type: &str, handle: &MinerHandle) -> (bool, String) {
+    match handle {
+        MinerHandle::Monitored { .. } => match MINING_STATS.exit_status(mining_type).await {
+            Some(status) => (status.success(), format!("miner exited with {status}")),
+            None => (false, "miner process is gone".to_string()),
+        },
+        // Native miners only return on their own when the pool connection is lost
+        MinerHandle::Native { .. } => (false, "native miner stopped hashing".to_string()),
+        MinerHandle::Managed(name) => (true, format!("{name} is not running")),
+        MinerHandle::Starting => (true, "miner is starting".to_string()),
+    }
+}
+
+async fn stop_handle(mining_type: &str, handle: MinerHandle) {
+    match handle {
+        MinerHandle::Monitored { supervisor } => {
+            if let Some(supervisor) = supervisor {
+                supervisor.abort();
+            }
+            let _ = MINING_STATS.stop_monitoring(mining_type).await;
+        }
+        // The task disconnects from the pool once the miner returns
+        MinerHandle::Native { miner, .. } => miner.stop(),
+        MinerHandle::Managed(name) => {
+            // The process may already be dead
+            let _ = get_process_manager().stop_process(&name).await;
+        }
+        // Nothing runs yet; the launch in progress stops its miner when it finds the
+        // session gone
+        MinerHandle::Starting => {}
+    }
+}
+
+async fn apply_intensity(
+    mining_type: &str,
+    handle: &MinerHandle,
+    intensity: u8,
+) -> Result<(), AppError> {
+    match handle {
+        MinerHandle::Monitored { .. } => MINING_STATS.set_intensity(mining_type, intensity).await,
+        MinerHandle::Native { miner, .. } => miner.set_intensity(intensity),
+        MinerHandle::Managed(name) => {
+            // Unthrottled managed processes need no throttle task at all
+            if intensity < 100 {
+                get_process_manager()
+                    .set_process_intensity(name, intensity)
+                    .await?;
+            }
+        }
+        // Applied by `install` once the miner is up
+        MinerHandle::Starting => {}
+    }
+    Ok(())
+}
+
+async fn refresh(session: &mut MiningSession, handle: &MinerHandle) {
+    let now = Utc::now();
+    let stats = &mut session.stats;
+    stats.uptime = now
+        .signed_duration_since(session.started_at)
+        .num_seconds()
+        .max(0) as u64;
+    stats.last_update = now;
+    stats.threads = session.threads;
+    if let Some(temperature) = get_cpu_temperature().await {
+        stats.temperature = temperature;
+    }
+
+    match handle {
+        MinerHandle::Monitored { .. } => {
+            MINING_STATS.calculate_earnings(&session.mining_type).await;
+            if let Some(real) = MINING_STATS.get_stats(&session.mining_type).await {
+                stats.hashrate = real.hashrate;
+                stats.accepted_shares = real.accepted_shares as u64;
+                stats.rejected_shares = real.rejected_shares as u64;
+                stats.estimated_earnings = real.estimated_earnings;
+            }
+
+            // Report the pool currently mined on, which failover may have changed
+            stats.pool_switches = MINING_STATS.get_pool_switches(&session.mining_type).await;
+            if let Some(last_switch) = stats.pool_switches.last() {
+                stats.pool_url = last_switch.to_url.clone();
+            }
+        }
+        MinerHandle::Native { miner, .. } => {
+            let native = miner.get_stats().await;
+            stats.hashrate = native.hashrate;
+            stats.accepted_shares = native.accepted_shares;
+            stats.rejected_shares = native.rejected_shares;
+        }
+        // Managed miners report nothing beyond whether they run
+        MinerHandle::Managed(_) | MinerHandle::Starting => {}
+    }
+
+    session.running = is_running(&session.mining_type, handle).await;
+    if !session.running {
+        stats.hashrate = 0.0;
+    }
+}
+
+// Global session manager instance
+lazy_static::lazy_static! {
+    pub static ref MINING_SESSIONS: MiningSessionManager = MiningSessionManager::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    // Launches a native miner whose task runs until the miner is stopped
+    fn counting_launcher(launches: Arc<AtomicUsize>) -> MinerLauncher {
+        Arc::new(move || {
+            let launches = launches.clone();
+            Box::pin(async move {
+                launches.fetch_add(1, Ordering::SeqCst);
+                let miner = Arc::new(NativeMiner::new(1));
+                let task = tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                });
+                Ok(MinerHandle::Native { miner, task })
+            })
+        })
+    }
+
+    fn session(mining_type: &str) -> MiningSession {
+        MiningSession::new(
+            mining_type,
+            "SHA-256d (native)",
+            "stratum+tcp://127.0.0.1:3333",
+            "worker",
+            2,
+            80,
+        )
+        .with_power_consumption(30.0)
+    }
+
+    #[tokio::test]
+    async fn test_session_lifecycle() {
+        let manager = MiningSessionManager::new();
+        let launches = Arc::new(AtomicUsize::new(0));
+
+        manager
+            .start(session("bitcoin"), counting_launcher(launches.clone()))
+            .await
+            .unwrap();
+        assert!(manager.is_active("bitcoin").await);
+        assert!(manager
+            .start(session("bitcoin"), counting_launcher(launches.clone()))
+            .await
+            .is_err());
+
+        let status = manager.status("bitcoin").await.unwrap();
+        assert!(status.running);
+        assert_eq!(status.stats.threads, 2);
+        assert_eq!(status.stats.power_consumption, 30.0);
+        assert_eq!(status.stats.pool_url, "stratum+tcp://127.0.0.1:3333");
+
+        manager.set_intensity("bitcoin", 40).await.unwrap();
+        manager.restart("bitcoin").await.unwrap();
+        let status = manager.status("bitcoin").await.unwrap();
+        assert_eq!(status.restarts, 1);
+        assert_eq!(status.intensity, 40);
+        assert_eq!(launches.load(Ordering::SeqCst), 2);
+
+        assert_eq!(manager.sessions().await.len(), 1);
+        assert!(manager.stop("bitcoin").await.is_some());
+        assert!(manager.status("bitcoin").await.is_none());
+        assert!(manager.stop("bitcoin").await.is_none());
+        assert!(manager.restart("bitcoin").await.is_err());
+    }
+
+    // Like counting_launcher, but each launch waits for `release`
+    fn gated_launcher(release: Arc<tokio::sync::Notify>) -> MinerLauncher {
+        Arc::new(move || {
+            let release = release.clone();
+            Box::pin(async move {
+                release.notified().await;
+                Ok(MinerHandle::Native {
+                    miner: Arc::new(NativeMiner::new(1)),
+                    task: tokio::spawn(tokio::time::sleep(Duration::from_secs(60))),
+                })
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_slow_start_does_not_block_other_sessions() {
+        let manager = Arc::new(MiningSessionManager::new());
+        let launches = Arc::new(AtomicUsize::new(0));
+        manager
+            .start(session("bitcoin"), counting_launcher(launches))
+            .await
+            .unwrap();
+
+        let release = Arc::new(tokio::sync::Notify::new());
+        let starting = tokio::spawn({
+            let manager = manager.clone();
+            let launcher = gated_launcher(release.clone());
+            async move { manager.start(session("whive"), launcher).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The pending launch holds no lock
+        let status = tokio::time::timeout(Duration::from_secs(1), manager.status("bitcoin"))
+            .await
+            .expect("status blocked behind a starting session");
+        assert!(status.unwrap().running);
+        assert_eq!(manager.sessions().await.len(), 2);
+        let error = manager
+            .start(session("whive"), gated_launcher(release.clone()))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("already starting"), "{}", error);
+        manager.set_intensity("whive", 30).await.unwrap();
+
+        release.notify_one();
+        starting.await.unwrap().unwrap();
+        let status = manager.status("whive").await.unwrap();
+        assert!(status.running);
+        assert_eq!(status.intensity, 30);
+
+        // A session stopped while starting does not come back when its launch finishes
+        manager.stop("whive").await;
+        let starting = tokio::spawn({
+            let manager = manager.clone();
+            let launcher = gated_launcher(release.clone());
+            async move { manager.start(session("whive"), launcher).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(manager.stop("whive").await.is_some());
+        release.notify_one();
+        assert!(starting.await.unwrap().is_err());
+        assert!(manager.status("whive").await.is_none());
+        manager.stop("bitcoin").await;
+    }
+
+    #[tokio::test]
+    async fn test_exited_miner_is_reported_and_replaced() {
+        let manager = MiningSessionManager::new();
+        let exited: MinerLauncher = Arc::new(|| {
+            Box::pin(async {
+                Ok(MinerHandle::Native {
+                    miner: Arc::new(NativeMiner::new(1)),
+                    task: tokio::spawn(async {}),
+                })
+            })
+        });
+
+        manager.start(session("whive"), exited).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let status = manager.status("whive").await.unwrap();
+        assert!(!status.running);
+        assert_eq!(status.stats.hashrate, 0.0);
+        assert!(!manager.is_active("whive").await);
+
+        // A dead session does not block a new start
+        let launches = Arc::new(AtomicUsize::new(0));
+        manager
+            .start(session("whive"), counting_launcher(launches.clone()))
+            .await
+            .unwrap();
+        assert!(manager.status("whive").await.unwrap().running);
+        manager.stop("whive").await;
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_restarts_exited_miner() {
+        let manager = MiningSessionManager::new();
+        let launches = Arc::new(AtomicUsize::new(0));
+        let launcher: MinerLauncher = {
+            let launches = launches.clone();
+            Arc::new(move || {
+                launches.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async {
+                    Ok(MinerHandle::Native {
+                        miner: Arc::new(NativeMiner::new(1)),
+                        task: tokio::spawn(async {}),
+                    })
+                })
+            })
+        };
+        let policy = RestartPolicy {
+            max_retries: 2,
+            backoff_secs: 0,
+            ..RestartPolicy::on_failure()
+        };
+
+        manager
+            .start(session("whive").with_restart_policy(policy), launcher)
+            .await
+            .unwrap();
+        for _ in 0..5 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            manager.supervise_once().await;
+        }
+
+        // Two restarts are allowed, then the session is left down with its failure
+        assert_eq!(launches.load(Ordering::SeqCst), 3);
+        let status = manager.status("whive").await.unwrap();
+        assert_eq!(status.restarts, 2);
+        assert!(!status.running);
+        assert!(status.last_failure.is_some());
+    }
+}
@@ -1,10 +1,15 @@
+use crate::pool_failover::{PoolHealth, PoolSwitchEvent};
+use crate::process_logs::{LogStream, PROCESS_LOGS};
+use crate::process_registry::{self, RegistryEntry};
+use crate::throttle::ProcessThrottle;
 use crate::AppError;
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Child;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{info, warn};
 
 #[derive(Debug, Clone)]
 pub struct RealMiningStats {
@@ -19,6 +24,9 @@ pub struct RealMiningStats {
     pub last_share_time: Option<DateTime<Utc>>,
     pub total_hashes: u64,
     pub error_count: u32,
+    pub connection_failures: u32,
+    // Set by the first stratum failure, cleared once the miner hears from the pool again
+    pub pool_disconnected_since: Option<DateTime<Utc>>,
 }
 
 impl Default for RealMiningStats {
@@ -35,6 +43,27 @@ impl Default for RealMiningStats {
             last_share_time: None,
             total_hashes: 0,
             error_count: 0,
+            connection_failures: 0,
+            pool_disconnected_since: None,
+        }
+    }
+}
+
+impl RealMiningStats {
+    // Health of the pool the miner is on; counters restart with every miner process.
+    // A disconnect only counts once the miner has failed to get back for `disconnect_grace`.
+    pub fn pool_health(
+        &self,
+        disconnect_grace: chrono::Duration,
+        now: DateTime<Utc>,
+    ) -> PoolHealth {
+        let connected = self
+            .pool_disconnected_since
+            .is_none_or(|since| now.signed_duration_since(since) < disconnect_grace);
+        PoolHealth {
+            connected,
+            accepted_shares: self.accepted_shares as u64,
+            rejected_shares: self.rejected_shares as u64,
         }
     }
 }
@@ -42,6 +71,14 @@ impl Default for RealMiningStats {
 pub struct MiningStatsCollector {
     stats: Arc<Mutex<HashMap<String, RealMiningStats>>>,
     processes: Arc<Mutex<HashMap<String, Child>>>,
+    // Kept across miner restarts so pool downtime can be audited
+    pool_switches: Arc<Mutex<HashMap<String, Vec<PoolSwitchEvent>>>>,
+    // Requested intensity per mining type, reapplied when a miner is restarted
+    intensities: Arc<Mutex<HashMap<String, u8>>>,
+    throttles: Arc<Mutex<HashMap<String, ProcessThrottle>>>,
+    // Running miners, saved so the next run of the app can kill any it left behind
+    registered: Arc<Mutex<HashMap<String, RegistryEntry>>>,
+    registry: Option<PathBuf>,
 }
 
 impl Default for MiningStatsCollector {
@@ -55,6 +92,76 @@ impl MiningStatsCollector {
         Self {
             stats: Arc::new(Mutex::new(HashMap::new())),
             processes: Arc::new(Mutex::new(HashMap::new())),
+            pool_switches: Arc::new(Mutex::new(HashMap::new())),
+            intensities: Arc::new(Mutex::new(HashMap::new())),
+            throttles: Arc::new(Mutex::new(HashMap::new())),
+            registered: Arc::new(Mutex::new(HashMap::new())),
+            registry: None,
+        }
+    }
+
+    pub fn with_registry(path: PathBuf) -> Self {
+        Self {
+            registry: Some(path),
+            ..Self::new()
+        }
+    }
+
+    // Kills miners saved by an earlier run of the app that are still hashing. Their output
+    // pipes died with that run, so they cannot be monitored and would double up with a new start.
+    pub fn reap_orphans(&self) -> Vec<String> {
+        let Some(path) = &self.registry else {
+            return Vec::new();
+        };
+
+        let mut reaped = Vec::new();
+        for entry in process_registry::load(path) {
+            if process_registry::kill_matching(entry.pid, &entry.command, &entry.args) {
+                info!(
+                    "Killed miner {} left running by the last run (PID: {})",
+                    entry.name, entry.pid
+                );
+                reaped.push(entry.name);
+            }
+        }
+
+        if let Err(e) = process_registry::save(path, &[]) {
+            warn!("Failed to clear miner registry: {}", e);
+        }
+        reaped
+    }
+
+    // Records the miner for `reap_orphans`, or forgets it when `pid` is None
+    async fn register(&self, mining_type: &str, pid: Option<u32>) {
+        let Some(path) = &self.registry else {
+            return;
+        };
+
+        let mut registered = self.registered.lock().await;
+        let command_line = pid.and_then(|pid| Some((pid, process_registry::command_line(pid)?)));
+        match command_line {
+            Some((pid, (command, args))) => {
+                registered.insert(
+                    mining_type.to_string(),
+                    RegistryEntry {
+                        name: mining_type.to_string(),
+                        pid,
+                        command,
+                        args,
+                        working_dir: None,
+                        started_at: Utc::now(),
+                        pidfile: None,
+                    },
+                );
+            }
+            None => {
+                registered.remove(mining_type);
+            }
+        }
+
+        let entries: Vec<RegistryEntry> = registered.values().cloned().collect();
+        if let Err(e) = process_registry::save(path, &entries) {
+            warn!("Failed to save miner registry: {}", e);
         }
     }
 
@@ -72,35 +179,48 @@ impl MiningStatsCollector {
             stats_map.insert(mining_type.clone(), RealMiningStats::default());
         }
 
-        // Take stdout before moving child
-        let stdout = child.stdout.take();
+        // Take the output streams before moving child. cpuminer reports shares and
+        // stratum failures on stderr, so both streams are parsed.
+        let (line_sender, lines) = mpsc::unbounded_channel();
+        if let Some(stdout) = child.stdout.take() {
+            let sender = line_sender.clone();
+            PROCESS_LOGS.drain(&mining_type, LogStream::Stdout, stdout, move |line| {
+                let _ = sender.send(line.to_string());
+            });
+        }
+        if let Some(stderr) = child.stderr.take() {
+            PROCESS_LOGS.drain(&mining_type, LogStream::Stderr, stderr, move |line| {
+                let _ = line_sender.send(line.to_string());
+            });
+        }
+        let pid = child.id();
 
-        // Store the process
+        // Store the process, replacing the one it restarts
         {
             let mut processes = self.processes.lock().await;
-            processes.insert(mining_type.clone(), child);
+            if let Some(mut previous) = processes.insert(mining_type.clone(), child) {
+                let _ = previous.kill().await;
+            }
         }
+        self.apply_throttle(&mining_type, pid).await;
+        self.register(&mining_type, pid).await;
 
-        // Spawn task to monitor output
-        let stats_clone = Arc::clone(&stats);
-        let mining_type_clone = mining_type.clone();
+        tokio::spawn(Self::parse_lines(stats, mining_type, lines));
 
-        tokio::spawn(async move {
-            if let Some(stdout) = stdout {
-                let reader = BufReader::new(stdout);
-                let mut lines = reader.lines();
+        Ok(())
+    }
 
-                while let Ok(Some(line)) = lines.next_line().await {
-                    if let Err(e) =
-                        Self::parse_mining_output(&stats_clone, &mining_type_clone, &line).await
-                    {
-                        eprintln!("Error parsing mining output: {}", e);
-                    }
-                }
+    // Parses output lines until both of the miner's streams have closed
+    async fn parse_lines(
+        stats: Arc<Mutex<HashMap<String, RealMiningStats>>>,
+        mining_type: String,
+        mut lines: mpsc::UnboundedReceiver<String>,
+    ) {
+        while let Some(line) = lines.recv().await {
+            if let Err(e) = Self::parse_mining_output(&stats, &mining_type, &line).await {
+                eprintln!("Error parsing mining output: {}", e);
             }
-        });
-
-        Ok(())
+        }
     }
 
     async fn parse_mining_output(
@@ -119,10 +239,62 @@ impl MiningStatsCollector {
         } else if mining_type == "bitcoin" {
             Self::parse_bitcoin_output(current_stats, line);
         }
+        Self::track_pool_connection(current_stats, line);
 
         Ok(())
     }
 
+    // "accepted: 5/6 (83.33%)" reports accepted shares out of all submitted
+    fn parse_share_counts(stats: &mut RealMiningStats, line: &str) {
+        let Some(counts) = line.split("accepted: ").nth(1) else {
+            return;
+        };
+        let mut parts = counts.split('/');
+        let accepted = parts.next().and_then(|a| a.parse::<u32>().ok());
+        let total = parts
+            .next()
+            .and_then(|t| t.split(' ').next())
+            .and_then(|t| t.parse::<u32>().ok());
+
+        if let Some(accepted) = accepted {
+            stats.accepted_shares = accepted;
+            stats.last_share_time = Some(Utc::now());
+            if let Some(total) = total {
+                stats.rejected_shares = total.saturating_sub(accepted);
+            }
+        }
+    }
+
+    fn track_pool_connection(stats: &mut RealMiningStats, line: &str) {
+        if Self::is_connection_failure(line) {
+            stats.pool_disconnected_since.get_or_insert_with(Utc::now);
+        } else if line.contains("accepted:")
+            || line.contains("Stratum difficulty set")
+            || line.contains("Stratum requested work restart")
+        {
+            stats.pool_disconnected_since = None;
+        }
+    }
+
+    // Converts a rate to H/s by the unit on the line; cpuminer prints "khash/s", others "kH/s"
+    fn scale_hashrate(rate: f64, line: &str) -> f64 {
+        if line.contains("kH/s") || line.contains("khash/s") {
+            rate * 1000.0
+        } else if line.contains("MH/s") || line.contains("Mhash/s") {
+            rate * 1_000_000.0
+        } else if line.contains("GH/s") || line.contains("Ghash/s") {
+            rate * 1_000_000_000.0
+        } else {
+            rate
+        }
+    }
+
+    fn is_connection_failure(line: &str) -> bool {
+        line.contains("stratum_recv_line failed")
+            || line.contains("connection interrupted")
+            || line.contains("Stratum connection failed")
+    }
+
     fn parse_whive_output(stats: &mut RealMiningStats, line: &str) {
         // Parse cpuminer/minerd output for Whive (Yespower)
         // Example outputs:
@@ -131,15 +303,7 @@ impl MiningStatsCollector {
         // "[2024-01-01 12:00:00] stratum_recv_line failed"
 
         if line.contains("accepted:") {
-            // Parse accepted shares
-            if let Some(accepted_part) = line.split("accepted: ").nth(1) {
-                if let Some(shares_part) = accepted_part.split('/').next() {
-                    if let Ok(accepted) = shares_part.parse::<u32>() {
-                        stats.accepted_shares = accepted;
-                        stats.last_share_time = Some(Utc::now());
-                    }
-                }
-            }
+            Self::parse_share_counts(stats, line);
 
             // Parse hashrate from accepted line
             if let Some(hashrate_part) = line.split(", ").nth(1) {
@@ -159,6 +323,9 @@ impl MiningStatsCollector {
                     }
                 }
             }
+        } else if Self::is_connection_failure(line) {
+            stats.error_count += 1;
+            stats.connection_failures += 1;
         } else if line.contains("failed") || line.contains("error") {
             stats.error_count += 1;
         }
@@ -168,34 +335,21 @@ impl MiningStatsCollector {
         // Parse cpuminer output for Bitcoin (SHA-256d)
         // Example outputs:
         // "[2024-01-01 12:00:00] accepted: 1/1 (100.00%), 25.12 kH/s yes!"
+        // "[2024-01-01 12:00:00] accepted: 1/1 (100.00%), 25.12 khash/s (yay!!!)"
+        // "[2024-01-01 12:00:00] thread 0: 2097152 hashes, 6543.21 khash/s"
         // "[2024-01-01 12:00:00] CPU #0: 6.25 kH/s"
         // "[2024-01-01 12:00:00] stratum_recv_line failed"
 
-        if line.contains("accepted:") {
-            // Parse accepted shares
-            if let Some(accepted_part) = line.split("accepted: ").nth(1) {
-                if let Some(shares_part) = accepted_part.split('/').next() {
-                    if let Ok(accepted) = shares_part.parse::<u32>() {
-                        stats.accepted_shares = accepted;
-                        stats.last_share_time = Some(Utc::now());
-                    }
-                }
+        if line.contains("accepted:") || line.contains(" hashes, ") {
+            if line.contains("accepted:") {
+                Self::parse_share_counts(stats, line);
             }
 
             // Parse hashrate (handle kH/s, MH/s, etc.)
             if let Some(hashrate_part) = line.split(", ").nth(1) {
                 if let Some(rate_str) = hashrate_part.split(' ').next() {
                     if let Ok(rate) = rate_str.parse::<f64>() {
-                        // Convert to H/s
-                        if line.contains("kH/s") {
-                            stats.hashrate = rate * 1000.0;
-                        } else if line.contains("MH/s") {
-                            stats.hashrate = rate * 1_000_000.0;
-                        } else if line.contains("GH/s") {
-                            stats.hashrate = rate * 1_000_000_000.0;
-                        } else {
-                            stats.hashrate = rate;
-                        }
+                        stats.hashrate = Self::scale_hashrate(rate, line);
                     }
                 }
             }
@@ -204,18 +358,13 @@ impl MiningStatsCollector {
             if let Some(rate_part) = line.split(": ").nth(1) {
                 if let Some(rate_str) = rate_part.split(' ').next() {
                     if let Ok(rate) = rate_str.parse::<f64>() {
-                        // Convert to H/s based on unit
-                        let hashrate = if line.contains("kH/s") {
-                            rate * 1000.0
-                        } else if line.contains("MH/s") {
-                            rate * 1_000_000.0
-                        } else {
-                            rate
-                        };
-                        stats.hashrate = hashrate;
+                        stats.hashrate = Self::scale_hashrate(rate, line);
                     }
                 }
             }
+        } else if Self::is_connection_failure(line) {
+            stats.error_count += 1;
+            stats.connection_failures += 1;
         } else if line.contains("failed") || line.contains("error") {
             stats.error_count += 1;
         }
@@ -226,7 +375,83 @@ impl MiningStatsCollector {
         stats_map.get(mining_type).cloned()
     }
 
+    // False once the miner has exited on its own
+    pub async fn is_process_running(&self, mining_type: &str) -> bool {
+        let mut processes = self.processes.lock().await;
+        match processes.get_mut(mining_type) {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => false,
+        }
+    }
+
+    // How the miner exited, once it has
+    pub async fn exit_status(&self, mining_type: &str) -> Option<std::process::ExitStatus> {
+        let mut processes = self.processes.lock().await;
+        processes.get_mut(mining_type)?.try_wait().ok().flatten()
+    }
+
+    pub async fn record_pool_switch(&self, mining_type: &str, event: PoolSwitchEvent) {
+        let mut switches = self.pool_switches.lock().await;
+        switches
+            .entry(mining_type.to_string())
+            .or_default()
+            .push(event);
+    }
+
+    pub async fn get_pool_switches(&self, mining_type: &str) -> Vec<PoolSwitchEvent> {
+        let switches = self.pool_switches.lock().await;
+        switches.get(mining_type).cloned().unwrap_or_default()
+    }
+
+    pub async fn clear_pool_switches(&self, mining_type: &str) {
+        let mut switches = self.pool_switches.lock().await;
+        switches.remove(mining_type);
+    }
+
+    // Limits the miner to about `intensity` percent of its CPU time, now and after restarts
+    pub async fn set_intensity(&self, mining_type: &str, intensity: u8) {
+        self.intensities
+            .lock()
+            .await
+            .insert(mining_type.to_string(), intensity.min(100));
+        let pid = self
+            .processes
+            .lock()
+            .await
+            .get(mining_type)
+            .and_then(|child| child.id());
+        self.apply_throttle(mining_type, pid).await;
+    }
+
+    async fn apply_throttle(&self, mining_type: &str, pid: Option<u32>) {
+        let intensity = self
+            .intensities
+            .lock()
+            .await
+            .get(mining_type)
+            .copied()
+            .unwrap_or(100);
+        let mut throttles = self.throttles.lock().await;
+        match pid {
+            Some(pid) if intensity < 100 => match throttles.get(mining_type) {
+                Some(throttle) if throttle.pid() == pid => throttle.set_intensity(intensity),
+                _ => {
+                    throttles.insert(
+                        mining_type.to_string(),
+                        ProcessThrottle::start(pid, intensity),
+                    );
+                }
+            },
+            _ => {
+                throttles.remove(mining_type);
+            }
+        }
+    }
+
     pub async fn stop_monitoring(&self, mining_type: &str) -> Result<(), AppError> {
+        self.throttles.lock().await.remove(mining_type);
+        self.intensities.lock().await.remove(mining_type);
+
         // Stop the process
         {
             let mut processes = self.processes.lock().await;
@@ -234,6 +459,7 @@ impl MiningStatsCollector {
                 let _ = child.kill().await;
             }
         }
+        self.register(mining_type, None).await;
 
         // Remove stats
         {
@@ -274,5 +500,154 @@ impl MiningStatsCollector {
 
 // Global stats collector instance
 lazy_static::lazy_static! {
-    pub static ref MINING_STATS: MiningStatsCollector = MiningStatsCollector::new();
+    pub static ref MINING_STATS: MiningStatsCollector = process_registry::miner_registry_path()
+        .map_or_else(MiningStatsCollector::new, MiningStatsCollector::with_registry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool_failover::{PoolFailover, SwitchReason};
+    use crate::{FailoverPolicy, PoolEndpoint};
+    use std::process::Stdio;
+    use std::time::Duration;
+    use tokio::process::Command;
+
+    // Runs a stand-in miner that prints `lines` on stderr, as cpuminer does
+    async fn stats_after_stderr(
+        collector: &MiningStatsCollector,
+        lines: &[&str],
+    ) -> RealMiningStats {
+        let child = Command::new("sh")
+            .args(["-c", "printf '%s\\n' \"$@\" >&2", "sh"])
+            .args(lines)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        collector
+            .start_monitoring_process("bitcoin", child)
+            .await
+            .unwrap();
+
+        let last = lines.len() as u64;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let logged = PROCESS_LOGS.tail("bitcoin", Some(LogStream::Stderr), usize::MAX);
+                if logged
+                    .iter()
+                    .filter(|l| lines.contains(&l.line.as_str()))
+                    .count() as u64
+                    >= last
+                {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        // Lines reach the logs just before the parser
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        collector.get_stats("bitcoin").await.unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reaps_miners_left_by_last_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = dir.path().join("miners.json");
+        let collector = MiningStatsCollector::with_registry(registry.clone());
+
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id().unwrap();
+        collector
+            .start_monitoring_process("reaped_miner", child)
+            .await
+            .unwrap();
+        assert_eq!(process_registry::load(&registry)[0].pid, pid);
+
+        // The next run of the app kills the miner it can no longer monitor
+        let restarted = MiningStatsCollector::with_registry(registry.clone());
+        assert_eq!(restarted.reap_orphans(), vec!["reaped_miner"]);
+        assert!(process_registry::load(&registry).is_empty());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!process_registry::process_matches(
+            pid,
+            "sleep",
+            &["30".to_string()]
+        ));
+
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        collector
+            .start_monitoring_process("reaped_miner", child)
+            .await
+            .unwrap();
+        collector.stop_monitoring("reaped_miner").await.unwrap();
+        assert!(process_registry::load(&registry).is_empty());
+        assert!(restarted.reap_orphans().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pool_health_from_cpuminer_stderr() {
+        let collector = MiningStatsCollector::new();
+        let pools: Vec<PoolEndpoint> = ["primary", "backup"]
+            .iter()
+            .map(|name| PoolEndpoint {
+                url: format!("stratum+tcp://{name}.pool.com:3333"),
+                username: "worker".to_string(),
+                password: "x".to_string(),
+            })
+            .collect();
+        let mut failover = PoolFailover::new(pools, FailoverPolicy::default(), Utc::now());
+
+        let stats = stats_after_stderr(
+            &collector,
+            &[
+                "[2024-05-01 10:00:00] 4 miner threads started, using 'sha256d' algorithm.",
+                "[2024-05-01 10:00:00] Starting Stratum on stratum+tcp://primary.pool.com:3333",
+                "[2024-05-01 10:00:01] Stratum difficulty set to 0.001",
+                "[2024-05-01 10:00:05] thread 0: 2097152 hashes, 6543.21 khash/s",
+                "[2024-05-01 10:00:09] accepted: 15/20 (75.00%), 26172.84 khash/s (booooo)",
+            ],
+        )
+        .await;
+        assert_eq!((stats.accepted_shares, stats.rejected_shares), (15, 5));
+        assert_eq!(stats.hashrate, 26172840.0);
+        let grace = failover.disconnect_grace();
+        let event = failover
+            .evaluate(&stats.pool_health(grace, Utc::now()), Utc::now())
+            .unwrap();
+        assert_eq!(event.reason, SwitchReason::RejectRatio(0.25));
+
+        let stats = stats_after_stderr(
+            &collector,
+            &[
+                "[2024-05-01 10:05:00] Stratum difficulty set to 0.001",
+                "[2024-05-01 10:05:30] stratum_recv_line failed",
+                "[2024-05-01 10:05:30] Stratum connection interrupted",
+            ],
+        )
+        .await;
+        // cpuminer gets the grace period to reconnect by itself
+        assert!(stats.pool_disconnected_since.is_some());
+        assert!(stats.pool_health(grace, Utc::now()).connected);
+        let later = Utc::now() + grace;
+        let event = failover
+            .evaluate(&stats.pool_health(grace, later), later)
+            .unwrap();
+        assert_eq!(event.reason, SwitchReason::ConnectionLost);
+
+        // Hearing from the pool again clears the failure
+        let stats = stats_after_stderr(
+            &collector,
+            &[
+                "[2024-05-01 10:06:00] Stratum connection failed: Couldn't connect to server",
+                "[2024-05-01 10:06:30] Stratum requested work restart",
+            ],
+        )
+        .await;
+        assert!(stats.pool_health(grace, Utc::now() + grace).connected);
+    }
 }
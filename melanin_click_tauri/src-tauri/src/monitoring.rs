@@ -1,7 +1,7 @@
+use crate::benchmark::{self, BenchmarkConfig, BenchmarkResult};
 use crate::core::get_process_manager;
-use crate::mining_stats::MINING_STATS;
+use crate::mining_session::MINING_SESSIONS;
 use crate::{AppError, AppState, GpuDevice, MiningStats, SystemInfo};
-use std::collections::HashMap;
 use sysinfo::System;
 use tauri::State;
 
@@ -11,54 +11,25 @@ pub async fn get_real_mining_stats(
     mining_type: String,
     _state: State<'_, AppState>,
 ) -> Result<MiningStats, AppError> {
-    // Get real stats from the mining stats collector
-    if let Some(real_stats) = MINING_STATS.get_stats(&mining_type).await {
-        // Update temperature and earnings calculation
-        MINING_STATS
-            .update_temperature(&mining_type, get_cpu_temperature().await.unwrap_or(35.0))
-            .await;
-        MINING_STATS.calculate_earnings(&mining_type).await;
-
-        // Get updated stats
-        let updated_stats = MINING_STATS
-            .get_stats(&mining_type)
-            .await
-            .unwrap_or(real_stats.clone());
-
-        // Convert to the UI MiningStats format
-        Ok(MiningStats {
-            hashrate: updated_stats.hashrate,
-            accepted_shares: updated_stats.accepted_shares as u64,
-            rejected_shares: updated_stats.rejected_shares as u64,
-            uptime: updated_stats.uptime,
-            temperature: updated_stats.temperature,
-            power_consumption: updated_stats.power_consumption,
-            estimated_earnings: updated_stats.estimated_earnings,
-            pool_url: "Mining pool".to_string(), // Real pool info would come from config
-            algorithm: match mining_type.as_str() {
-                "whive" => "Yespower".to_string(),
-                "bitcoin" => "SHA-256d".to_string(),
-                _ => mining_type.clone(),
-            },
-            threads: 2, // This should come from config
-            last_update: chrono::Utc::now(),
-        })
-    } else {
-        // No mining process running
-        Ok(MiningStats {
-            hashrate: 0.0,
-            accepted_shares: 0,
-            rejected_shares: 0,
-            uptime: 0,
-            temperature: get_cpu_temperature().await.unwrap_or(35.0),
-            power_consumption: get_base_power_consumption().await,
-            estimated_earnings: 0.0,
-            pool_url: "Not mining".to_string(),
-            algorithm: mining_type.clone(),
-            threads: 0,
-            last_update: chrono::Utc::now(),
-        })
+    if let Some(session) = MINING_SESSIONS.status(&mining_type).await {
+        return Ok(session.stats);
     }
+
+    // No mining session
+    Ok(MiningStats {
+        hashrate: 0.0,
+        accepted_shares: 0,
+        rejected_shares: 0,
+        uptime: 0,
+        temperature: get_cpu_temperature().await.unwrap_or(35.0),
+        power_consumption: get_base_power_consumption().await,
+        estimated_earnings: 0.0,
+        pool_url: "Not mining".to_string(),
+        algorithm: mining_type.clone(),
+        threads: 0,
+        last_update: chrono::Utc::now(),
+        pool_switches: Vec::new(),
+    })
 }
 
 // Get Comprehensive System Information
@@ -140,35 +111,20 @@ pub async fn get_hardware_info() -> Result<crate::HardwareInfo, AppError> {
     })
 }
 
-// Benchmark Hardware Performance
+// Benchmark Hardware Performance by hashing each registered algorithm for real
 #[tauri::command]
-pub async fn benchmark_hardware() -> Result<HashMap<String, f64>, AppError> {
-    let mut results = HashMap::new();
-
-    // CPU benchmarks
-    let cpu_threads = num_cpus::get();
-
-    // Simulate Yespower benchmark
-    let yespower_hashrate = benchmark_yespower_cpu(cpu_threads).await?;
-    results.insert("CPU_Yespower".to_string(), yespower_hashrate);
-
-    // Simulate SHA-256 benchmark
-    let sha256_hashrate = benchmark_sha256_cpu(cpu_threads).await?;
-    results.insert("CPU_SHA256".to_string(), sha256_hashrate);
-
-    // GPU benchmarks (if available)
-    let gpu_devices = detect_gpu_devices().await?;
-    for (i, gpu) in gpu_devices.iter().enumerate() {
-        let gpu_hashrate = benchmark_gpu_sha256(gpu).await?;
-        results.insert(format!("GPU_{}_SHA256", i), gpu_hashrate);
-    }
-
-    Ok(results)
+pub async fn benchmark_hardware(
+    config: Option<BenchmarkConfig>,
+) -> Result<Vec<BenchmarkResult>, AppError> {
+    let config = config.unwrap_or_default();
+    tokio::task::spawn_blocking(move || benchmark::run_benchmarks(&config))
+        .await
+        .map_err(|e| AppError::Mining(format!("Benchmark task failed: {e}")))?
 }
 
 // Helper functions
 
-async fn get_cpu_temperature() -> Option<f64> {
+pub(crate) async fn get_cpu_temperature() -> Option<f64> {
     // Platform-specific temperature reading
     #[cfg(target_os = "linux")]
     {
@@ -320,52 +276,3 @@ async fn detect_gpu_devices() -> Result<Vec<GpuDevice>, AppError> {
 
     Ok(devices)
 }
-
-async fn benchmark_yespower_cpu(threads: usize) -> Result<f64, AppError> {
-    // Estimate Yespower performance based on CPU specs
-    let mut sys = System::new_all();
-    sys.refresh_all();
-
-    let cpu_frequency = sys
-        .cpus()
-        .first()
-        .map(|cpu| cpu.frequency())
-        .unwrap_or(2000) as f64;
-
-    // Yespower is memory-hard, so consider both frequency and thread count
-    let estimated_hashrate = (cpu_frequency / 1000.0) * threads as f64 * 400.0;
-
-    Ok(estimated_hashrate)
-}
-
-async fn benchmark_sha256_cpu(threads: usize) -> Result<f64, AppError> {
-    // Estimate SHA-256 performance
-    let mut sys = System::new_all();
-    sys.refresh_all();
-
-    let cpu_frequency = sys
-        .cpus()
-        .first()
-        .map(|cpu| cpu.frequency())
-        .unwrap_or(2000) as f64;
-
-    let estimated_hashrate = (cpu_frequency / 1000.0) * threads as f64 * 20.0;
-
-    Ok(estimated_hashrate)
-}
-
-async fn benchmark_gpu_sha256(gpu: &GpuDevice) -> Result<f64, AppError> {
-    // Estimate GPU SHA-256 performance based on vendor
-    match gpu.vendor.as_str() {
-        "NVIDIA" => {
-            // Rough estimation based on GPU memory (more memory = more powerful GPU)
-            let memory_gb = gpu.memory as f64 / (1024.0 * 1024.0 * 1024.0);
-            Ok(memory_gb * 2000.0 * 1000.0) // MH/s
-        }
-        "AMD" => {
-            let memory_gb = gpu.memory as f64 / (1024.0 * 1024.0 * 1024.0);
-            Ok(memory_gb * 1500.0 * 1000.0) // MH/s
-        }
-        _ => Ok(1000.0 * 1000.0), // 1 GH/s default
-    }
-}
@@ -0,0 +1,787 @@
+use crate::algorithms::{self, SHA256D_DIFF1};
+use crate::stratum::{PoolClient, StratumJob};
+use crate::throttle::DutyCycle;
+use crate::AppError;
+use serde::{Deserialize, Serialize};
+use sha2::digest::generic_array::GenericArray;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, Mutex};
+use tracing::{debug, error, info, warn};
+
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NativeMinerStats {
+    pub threads: usize,
+    pub hashrate: f64,
+    pub total_hashes: u64,
+    pub shares_found: u64,
+    pub accepted_shares: u64,
+    pub rejected_shares: u64,
+    pub current_job: Option<String>,
+}
+
+// A share that met the pool target, with fields formatted for mining.submit
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundShare {
+    pub job_id: String,
+    pub extranonce2: String,
+    pub ntime: String,
+    pub nonce: String,
+    pub hash: [u8; 32],
+}
+
+// Everything needed to build block headers for one job
+#[derive(Debug, Clone)]
+pub struct MiningWork {
+    job_id: String,
+    coinbase1: Vec<u8>,
+    extranonce1: Vec<u8>,
+    extranonce2_size: usize,
+    coinbase2: Vec<u8>,
+    merkle_branches: Vec<[u8; 32]>,
+    // Set for V2 standard jobs, which come with the merkle root already computed
+    merkle_root: Option<[u8; 32]>,
+    version: u32,
+    prev_hash: [u8; 32],
+    ntime: u32,
+    nbits: u32,
+    // Share target as a big-endian 256-bit number
+    target: [u8; 32],
+}
+
+impl MiningWork {
+    pub fn from_job(
+        job: &StratumJob,
+        extranonce1: &str,
+        extranonce2_size: usize,
+        difficulty: f64,
+    ) -> Result<Self, AppError> {
+        let merkle_root = match &job.merkle_root {
+            Some(root) => Some(decode_hash(root)?),
+            None => None,
+        };
+
+        // V1 sends the previous hash as eight byte-swapped 32-bit words,
+        // V2 sends it in header byte order
+        let mut prev_hash = decode_hash(&job.previous_hash)?;
+        if merkle_root.is_none() {
+            for word in prev_hash.chunks_mut(4) {
+                word.reverse();
+            }
+        }
+
+        Ok(Self {
+            job_id: job.job_id.clone(),
+            coinbase1: decode_hex(&job.coinbase1)?,
+            extranonce1: decode_hex(extranonce1)?,
+            extranonce2_size: if merkle_root.is_some() {
+                0
+            } else {
+                extranonce2_size
+            },
+            coinbase2: decode_hex(&job.coinbase2)?,
+            merkle_branches: job
+                .merkle_branches
+                .iter()
+                .map(|branch| decode_hash(branch))
+                .collect::<Result<_, _>>()?,
+            merkle_root,
+            version: parse_hex_u32(&job.version)?,
+            prev_hash,
+            ntime: parse_hex_u32(&job.ntime)?,
+            nbits: parse_hex_u32(&job.nbits)?,
+            target: difficulty_to_target(difficulty),
+        })
+    }
+
+    // Number of extranonce2 values available to roll through
+    fn extranonce2_count(&self) -> u64 {
+        match self.extranonce2_size {
+            0 => 1,
+            size if size >= 8 => u64::MAX,
+            size => 1u64 << (8 * size),
+        }
+    }
+
+    fn extranonce2_bytes(&self, extranonce2: u64) -> Vec<u8> {
+        let bytes = extranonce2.to_be_bytes();
+        let size = self.extranonce2_size.min(8);
+        let mut value = vec![0u8; self.extranonce2_size - size];
+        value.extend_from_slice(&bytes[8 - size..]);
+        value
+    }
+
+    pub fn merkle_root(&self, extranonce2: &[u8]) -> [u8; 32] {
+        if let Some(root) = self.merkle_root {
+            return root;
+        }
+
+        let coinbase = [
+            &self.coinbase1[..],
+            &self.extranonce1,
+            extranonce2,
+            &self.coinbase2,
+        ]
+        .concat();
+        self.merkle_branches
+            .iter()
+            .fold(sha256d(&coinbase), |root, branch| {
+                sha256d(&[&root[..], &branch[..]].concat())
+            })
+    }
+
+    // Block header with a zero nonce
+    pub fn header(&self, extranonce2: &[u8]) -> [u8; 80] {
+        let mut header = [0u8; 80];
+        header[0..4].copy_from_slice(&self.version.to_le_bytes());
+        header[4..36].copy_from_slice(&self.prev_hash);
+        header[36..68].copy_from_slice(&self.merkle_root(extranonce2));
+        header[68..72].copy_from_slice(&self.ntime.to_le_bytes());
+        header[72..76].copy_from_slice(&self.nbits.to_le_bytes());
+        header
+    }
+
+    fn share(&self, extranonce2: &[u8], nonce: u32, hash: [u8; 32]) -> FoundShare {
+        FoundShare {
+            job_id: self.job_id.clone(),
+            extranonce2: hex::encode(extranonce2),
+            ntime: format!("{:08x}", self.ntime),
+            nonce: format!("{:08x}", nonce),
+            hash,
+        }
+    }
+}
+
+// SHA-256 state after the first 64 header bytes, which do not depend on the nonce
+pub struct Midstate {
+    state: [u32; 8],
+    tail: [u8; 64],
+}
+
+impl Midstate {
+    pub fn new(header: &[u8; 80]) -> Self {
+        let mut state = SHA256_IV;
+        sha2::compress256(&mut state, &[*GenericArray::from_slice(&header[..64])]);
+
+        // Second block: remaining 16 header bytes plus padding for an 80 byte message
+        let mut tail = [0u8; 64];
+        tail[..16].copy_from_slice(&header[64..]);
+        tail[16] = 0x80;
+        tail[56..].copy_from_slice(&(80u64 * 8).to_be_bytes());
+
+        Self { state, tail }
+    }
+
+    // Double SHA-256 of the header with the given nonce
+    pub fn hash(&mut self, nonce: u32) -> [u8; 32] {
+        self.tail[12..16].copy_from_slice(&nonce.to_le_bytes());
+        let mut first = self.state;
+        sha2::compress256(&mut first, &[*GenericArray::from_slice(&self.tail)]);
+
+        let mut block = [0u8; 64];
+        for (chunk, word) in block.chunks_mut(4).zip(first.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        block[32] = 0x80;
+        block[56..].copy_from_slice(&(32u64 * 8).to_be_bytes());
+
+        let mut second = SHA256_IV;
+        sha2::compress256(&mut second, &[*GenericArray::from_slice(&block)]);
+
+        let mut hash = [0u8; 32];
+        for (chunk, word) in hash.chunks_mut(4).zip(second.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        hash
+    }
+
+    // Hashes `count` nonces from `start` and reports every hash at or below the target
+    pub fn scan(
+        &mut self,
+        start: u32,
+        count: u32,
+        target: &[u8; 32],
+        mut found: impl FnMut(u32, [u8; 32]),
+    ) {
+        // The last four hash bytes are the most significant; most hashes fail on them alone
+        let target_top = u32::from_be_bytes([target[0], target[1], target[2], target[3]]);
+        for offset in 0..count {
+            let nonce = start.wrapping_add(offset);
+            let hash = self.hash(nonce);
+            let top = u32::from_le_bytes([hash[28], hash[29], hash[30], hash[31]]);
+            if top <= target_top && meets_target(&hash, target) {
+                found(nonce, hash);
+            }
+        }
+    }
+}
+
+pub fn sha256d(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+// Hashes are little-endian numbers, the target a big-endian one
+pub fn meets_target(hash: &[u8; 32], target: &[u8; 32]) -> bool {
+    hash.iter().rev().cmp(target.iter()) != std::cmp::Ordering::Greater
+}
+
+// Pool share target for a SHA-256d Stratum difficulty, where difficulty 1 is 0xffff * 2^208
+pub fn difficulty_to_target(difficulty: f64) -> [u8; 32] {
+    scale_target(&SHA256D_DIFF1, difficulty)
+}
+
+// Divides a difficulty 1 target by the pool difficulty
+pub fn scale_target(diff1: &[u8; 32], difficulty: f64) -> [u8; 32] {
+    let diff1 = diff1
+        .iter()
+        .fold(0.0, |value, byte| value * 256.0 + *byte as f64);
+    let mut remaining = diff1 / difficulty.max(f64::MIN_POSITIVE);
+    let mut target = [0u8; 32];
+    for (index, byte) in target.iter_mut().enumerate() {
+        let place = 2f64.powi(8 * (31 - index as i32));
+        let value = (remaining / place).floor().min(255.0);
+        *byte = value as u8;
+        remaining -= value * place;
+    }
+    target
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, AppError> {
+    hex::decode(value).map_err(|_| AppError::Mining(format!("Invalid hex in job: {}", value)))
+}
+
+fn decode_hash(value: &str) -> Result<[u8; 32], AppError> {
+    decode_hex(value)?
+        .try_into()
+        .map_err(|_| AppError::Mining(format!("Expected a 32 byte hash in job: {}", value)))
+}
+
+fn parse_hex_u32(value: &str) -> Result<u32, AppError> {
+    u32::from_str_radix(value, 16)
+        .map_err(|_| AppError::Mining(format!("Invalid hex field in job: {}", value)))
+}
+
+// Searches one thread's slice of the nonce space for every extranonce2 in turn,
+// until the job changes or the space is exhausted
+#[allow(clippy::too_many_arguments)]
+fn search(
+    work: &MiningWork,
+    algorithm: &str,
+    thread_index: usize,
+    thread_count: usize,
+    job_epoch: u64,
+    epoch: &AtomicU64,
+    intensity: &AtomicU8,
+    hashes: &AtomicU64,
+    shares: &mpsc::UnboundedSender<FoundShare>,
+) {
+    let slice = (u32::MAX as u64 + 1) / thread_count as u64;
+    let first_nonce = slice * thread_index as u64;
+    let last_nonce = if thread_index + 1 == thread_count {
+        u32::MAX as u64
+    } else {
+        first_nonce + slice - 1
+    };
+
+    let mut hasher = match algorithms::create(algorithm) {
+        Ok(hasher) => hasher,
+        Err(e) => {
+            error!("Thread {} cannot hash: {}", thread_index, e);
+            return;
+        }
+    };
+
+    let mut duty = DutyCycle::new();
+    for extranonce2 in 0..work.extranonce2_count() {
+        let extranonce2 = work.extranonce2_bytes(extranonce2);
+        let header = work.header(&extranonce2);
+
+        let mut nonce = first_nonce;
+        while nonce <= last_nonce {
+            if epoch.load(Ordering::Relaxed) != job_epoch {
+                return;
+            }
+
+            let count = (last_nonce - nonce + 1).min(hasher.nonce_batch() as u64) as u32;
+            hasher.scan(
+                &header,
+                nonce as u32,
+                count,
+                &work.target,
+                &mut |nonce, hash| {
+                    let _ = shares.send(work.share(&extranonce2, nonce, hash));
+                },
+            );
+            hashes.fetch_add(count as u64, Ordering::Relaxed);
+            duty.pace(intensity.load(Ordering::Relaxed));
+            nonce += count as u64;
+        }
+    }
+
+    debug!(
+        "Thread {} exhausted the search space of job {}",
+        thread_index, work.job_id
+    );
+}
+
+// Shortest span the reported hashrate is averaged over
+const HASHRATE_WINDOW: Duration = Duration::from_secs(10);
+
+// Hash counter samples the hashrate is measured between. The older sample is only
+// moved forward once the newer one is a full window old, so the rate always covers
+// at least one window no matter how often it is polled
+struct HashrateWindow {
+    previous: (Instant, u64),
+    current: (Instant, u64),
+}
+
+impl HashrateWindow {
+    fn new(now: Instant) -> Self {
+        Self {
+            previous: (now, 0),
+            current: (now, 0),
+        }
+    }
+
+    fn rate(&mut self, hashes: u64, now: Instant) -> f64 {
+        if now.duration_since(self.current.0) >= HASHRATE_WINDOW {
+            self.previous = self.current;
+            self.current = (now, hashes);
+        }
+        let (sampled_at, sampled_hashes) = self.previous;
+        let elapsed = now.duration_since(sampled_at).as_secs_f64();
+        if elapsed > 0.0 {
+            hashes.saturating_sub(sampled_hashes) as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+// In-process miner: hashes pool jobs on a set of OS threads and submits shares
+// back through the pool client
+pub struct NativeMiner {
+    threads: usize,
+    // Registered name of the hash algorithm
+    algorithm: &'static str,
+    // Percentage of time the hashing threads spend hashing
+    intensity: Arc<AtomicU8>,
+    // Bumped on every new job, and on stop, to retire the threads hashing the old one
+    epoch: Arc<AtomicU64>,
+    hashes: Arc<AtomicU64>,
+    stats: Arc<Mutex<NativeMinerStats>>,
+    hashrate: Mutex<HashrateWindow>,
+    stop_sender: watch::Sender<bool>,
+}
+
+impl NativeMiner {
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (stop_sender, _) = watch::channel(false);
+
+        Self {
+            threads,
+            algorithm: "sha256d",
+            intensity: Arc::new(AtomicU8::new(100)),
+            epoch: Arc::new(AtomicU64::new(0)),
+            hashes: Arc::new(AtomicU64::new(0)),
+            stats: Arc::new(Mutex::new(NativeMinerStats {
+                threads,
+                ..Default::default()
+            })),
+            hashrate: Mutex::new(HashrateWindow::new(Instant::now())),
+            stop_sender,
+        }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: &'static str) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_intensity(self, intensity: u8) -> Self {
+        self.set_intensity(intensity);
+        self
+    }
+
+    // Takes effect on running threads after their current batch
+    pub fn set_intensity(&self, intensity: u8) {
+        self.intensity.store(intensity.min(100), Ordering::Relaxed);
+    }
+
+    // Mines jobs from a connected pool client until stopped or the job stream ends
+    pub async fn run(&self, client: Arc<PoolClient>) -> Result<(), AppError> {
+        let diff1_target = algorithms::create(self.algorithm)?.diff1_target();
+        let mut stop = self.stop_sender.subscribe();
+        let (share_sender, share_receiver) = mpsc::unbounded_channel();
+        let submitter = tokio::spawn(Self::submit_shares(
+            client.clone(),
+            share_receiver,
+            self.stats.clone(),
+        ));
+
+        info!(
+            "Native {} miner started with {} threads",
+            self.algorithm, self.threads
+        );
+
+        loop {
+            let job = tokio::select! {
+                job = client.get_next_job() => job,
+                _ = stop.changed() => break,
+            };
+            let Some(job) = job else {
+                warn!("Pool job stream ended");
+                break;
+            };
+
+            let pool_stats = client.get_stats().await;
+            let (extranonce1, extranonce2_size) = pool_stats
+                .subscription
+                .map(|s| (s.extranonce1, s.extranonce2_size))
+                .unwrap_or_default();
+            let work = match MiningWork::from_job(
+                &job,
+                &extranonce1,
+                extranonce2_size,
+                pool_stats.difficulty,
+            ) {
+                Ok(mut work) => {
+                    work.target = scale_target(&diff1_target, pool_stats.difficulty);
+                    Arc::new(work)
+                }
+                Err(e) => {
+                    error!("Skipping job {}: {}", job.job_id, e);
+                    continue;
+                }
+            };
+
+            let job_epoch = self.epoch.fetch_add(1, Ordering::SeqCst) + 1;
+            self.stats.lock().await.current_job = Some(job.job_id.clone());
+            debug!(
+                "Hashing job {} at difficulty {}",
+                job.job_id, pool_stats.difficulty
+            );
+
+            for thread_index in 0..self.threads {
+                let work = work.clone();
+                let algorithm = self.algorithm;
+                let thread_count = self.threads;
+                let epoch = self.epoch.clone();
+                let intensity = self.intensity.clone();
+                let hashes = self.hashes.clone();
+                let shares = share_sender.clone();
+                std::thread::spawn(move || {
+                    search(
+                        &work,
+                        algorithm,
+                        thread_index,
+                        thread_count,
+                        job_epoch,
+                        &epoch,
+                        &intensity,
+                        &hashes,
+                        &shares,
+                    )
+                });
+            }
+        }
+
+        // Retire the hashing threads; the submitter ends once they drop their senders
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        drop(share_sender);
+        let _ = submitter.await;
+        info!("Native {} miner stopped", self.algorithm);
+        Ok(())
+    }
+
+    async fn submit_shares(
+        client: Arc<PoolClient>,
+        mut shares: mpsc::UnboundedReceiver<FoundShare>,
+        stats: Arc<Mutex<NativeMinerStats>>,
+    ) {
+        while let Some(share) = shares.recv().await {
+            stats.lock().await.shares_found += 1;
+            let result = client
+                .submit_share(
+                    &share.job_id,
+                    &share.extranonce2,
+                    &share.ntime,
+                    &share.nonce,
+                )
+                .await;
+
+            let mut stats = stats.lock().await;
+            match result {
+                Ok(result) if result.accepted => stats.accepted_shares += 1,
+                Ok(result) => {
+                    stats.rejected_shares += 1;
+                    warn!(
+                        "Share for job {} rejected: {}",
+                        share.job_id,
+                        result.reason.unwrap_or_default()
+                    );
+                }
+                Err(e) => {
+                    stats.rejected_shares += 1;
+                    error!("Failed to submit share for job {}: {}", share.job_id, e);
+                }
+            }
+        }
+    }
+
+    pub fn stop(&self) {
+        let _ = self.stop_sender.send(true);
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub async fn get_stats(&self) -> NativeMinerStats {
+        let mut stats = self.stats.lock().await.clone();
+        stats.total_hashes = self.hashes.load(Ordering::Relaxed);
+        stats.hashrate = self
+            .hashrate
+            .lock()
+            .await
+            .rate(stats.total_hashes, Instant::now());
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Genesis block coinbase transaction
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+    const GENESIS_NONCE: u32 = 2083236893;
+
+    fn display_hash(hash: &[u8; 32]) -> String {
+        hex::encode(hash.iter().rev().copied().collect::<Vec<u8>>())
+    }
+
+    // The genesis block as a pool would hand it out, with the extranonces cut from its coinbase
+    fn genesis_job() -> (StratumJob, String) {
+        let (coinbase1, rest) = GENESIS_COINBASE.split_at(120);
+        let (extranonce1, rest) = rest.split_at(8);
+        let (_extranonce2, coinbase2) = rest.split_at(8);
+
+        let job = StratumJob {
+            job_id: "genesis".to_string(),
+            previous_hash: "00".repeat(32),
+            coinbase1: coinbase1.to_string(),
+            coinbase2: coinbase2.to_string(),
+            merkle_branches: vec![],
+            version: "00000001".to_string(),
+            nbits: "1d00ffff".to_string(),
+            ntime: format!("{:08x}", 1231006505),
+            clean_jobs: true,
+            merkle_root: None,
+        };
+        (job, extranonce1.to_string())
+    }
+
+    #[test]
+    fn test_genesis_header_from_job() {
+        let (job, extranonce1) = genesis_job();
+        let work = MiningWork::from_job(&job, &extranonce1, 4, 1.0).unwrap();
+        let extranonce2 = hex::decode(&GENESIS_COINBASE[128..136]).unwrap();
+
+        assert_eq!(
+            display_hash(&work.merkle_root(&extranonce2)),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+
+        let mut header = work.header(&extranonce2);
+        header[76..].copy_from_slice(&GENESIS_NONCE.to_le_bytes());
+        assert_eq!(
+            display_hash(&sha256d(&header)),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+    }
+
+    #[test]
+    fn test_midstate_matches_full_hash() {
+        let (job, extranonce1) = genesis_job();
+        let work = MiningWork::from_job(&job, &extranonce1, 4, 1.0).unwrap();
+        let mut header = work.header(&[0, 0, 0, 7]);
+        let mut midstate = Midstate::new(&header);
+
+        for nonce in [0, 1, 0xdeadbeef, u32::MAX] {
+            header[76..].copy_from_slice(&nonce.to_le_bytes());
+            assert_eq!(midstate.hash(nonce), sha256d(&header));
+        }
+    }
+
+    #[test]
+    fn test_scan_finds_genesis_nonce() {
+        let (job, extranonce1) = genesis_job();
+        let work = MiningWork::from_job(&job, &extranonce1, 4, 1.0).unwrap();
+        let extranonce2 = hex::decode(&GENESIS_COINBASE[128..136]).unwrap();
+        let mut midstate = Midstate::new(&work.header(&extranonce2));
+
+        let mut found = Vec::new();
+        midstate.scan(GENESIS_NONCE - 5000, 10000, &work.target, |nonce, hash| {
+            found.push(work.share(&extranonce2, nonce, hash))
+        });
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].nonce, format!("{:08x}", GENESIS_NONCE));
+        assert_eq!(found[0].extranonce2, &GENESIS_COINBASE[128..136]);
+        assert_eq!(found[0].ntime, "495fab29");
+    }
+
+    #[test]
+    fn test_hashrate_covers_recent_window_only() {
+        let start = Instant::now();
+        let mut window = HashrateWindow::new(start);
+
+        // A fast first window followed by a stall must not keep reporting the old speed
+        assert_eq!(window.rate(10_000, start + HASHRATE_WINDOW), 1_000.0);
+        assert_eq!(window.rate(10_000, start + HASHRATE_WINDOW * 2), 0.0);
+
+        // Polling within a window keeps averaging over at least a full window
+        let polled = start + HASHRATE_WINDOW * 2 + Duration::from_secs(1);
+        assert_eq!(window.rate(11_000, polled), 1_000.0 / 11.0);
+    }
+
+    #[test]
+    fn test_difficulty_to_target() {
+        let mut diff1 = [0u8; 32];
+        diff1[4] = 0xff;
+        diff1[5] = 0xff;
+        assert_eq!(difficulty_to_target(1.0), diff1);
+
+        let mut diff256 = [0u8; 32];
+        diff256[5] = 0xff;
+        diff256[6] = 0xff;
+        assert_eq!(difficulty_to_target(256.0), diff256);
+
+        // Difficulties below 1 widen the target past 32 leading zero bits
+        assert_eq!(difficulty_to_target(1.0 / 65536.0)[2..4], [0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_prev_hash_word_order() {
+        let (mut job, extranonce1) = genesis_job();
+        job.previous_hash = "00010203".repeat(8);
+        let work = MiningWork::from_job(&job, &extranonce1, 4, 1.0).unwrap();
+        assert_eq!(&work.header(&[0; 4])[4..8], &[3, 2, 1, 0]);
+
+        // V2 jobs carry the merkle root and the previous hash in header order
+        job.merkle_root = Some("11".repeat(32));
+        let work = MiningWork::from_job(&job, &extranonce1, 4, 1.0).unwrap();
+        let header = work.header(&[]);
+        assert_eq!(&header[4..8], &[0, 1, 2, 3]);
+        assert_eq!(&header[36..68], &[0x11; 32]);
+        assert_eq!(work.extranonce2_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_miner_submits_shares_to_pool() {
+        use serde_json::{json, Value};
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Mock pool at a difficulty low enough for a share every 65536 hashes or so
+        let pool = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut submitted = Vec::new();
+
+            while submitted.len() < 3 {
+                let line = lines.next_line().await.unwrap().unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let mut replies = vec![];
+                match request["method"].as_str().unwrap() {
+                    "mining.subscribe" => replies.push(json!({
+                        "id": request["id"],
+                        "result": [[["mining.notify", "1"]], "f000000f", 4],
+                        "error": null
+                    })),
+                    "mining.authorize" => {
+                        replies.push(json!({"id": request["id"], "result": true, "error": null}));
+                        replies.push(json!({
+                            "id": null, "method": "mining.set_difficulty", "params": [1.0 / 65536.0]
+                        }));
+                        replies.push(json!({
+                            "id": null,
+                            "method": "mining.notify",
+                            "params": ["job-1", "00".repeat(32), "0100", "0200", [], "20000000", "1d00ffff", "5f5e1000", true]
+                        }));
+                    }
+                    "mining.submit" => {
+                        submitted.push(request["params"].clone());
+                        replies.push(json!({"id": request["id"], "result": true, "error": null}));
+                    }
+                    _ => {}
+                }
+                for reply in replies {
+                    writer
+                        .write_all(format!("{}\n", reply).as_bytes())
+                        .await
+                        .unwrap();
+                }
+            }
+            submitted
+        });
+
+        let client = Arc::new(
+            PoolClient::from_url(&format!("stratum+tcp://127.0.0.1:{}", port), "worker", "x")
+                .unwrap(),
+        );
+        client.connect().await.unwrap();
+
+        let miner = Arc::new(NativeMiner::new(2));
+        let running = tokio::spawn({
+            let miner = miner.clone();
+            let client = client.clone();
+            async move { miner.run(client).await }
+        });
+
+        let submitted = tokio::time::timeout(std::time::Duration::from_secs(30), pool)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Every submitted share really meets the pool target
+        let job = StratumJob {
+            job_id: "job-1".to_string(),
+            previous_hash: "00".repeat(32),
+            coinbase1: "0100".to_string(),
+            coinbase2: "0200".to_string(),
+            merkle_branches: vec![],
+            version: "20000000".to_string(),
+            nbits: "1d00ffff".to_string(),
+            ntime: "5f5e1000".to_string(),
+            clean_jobs: true,
+            merkle_root: None,
+        };
+        let work = MiningWork::from_job(&job, "f000000f", 4, 1.0 / 65536.0).unwrap();
+        for share in &submitted {
+            assert_eq!(share[1], json!("job-1"));
+            assert_eq!(share[3], json!("5f5e1000"));
+            let extranonce2 = hex::decode(share[2].as_str().unwrap()).unwrap();
+            let nonce = u32::from_str_radix(share[4].as_str().unwrap(), 16).unwrap();
+            let mut header = work.header(&extranonce2);
+            header[76..].copy_from_slice(&nonce.to_le_bytes());
+            assert!(meets_target(&sha256d(&header), &work.target));
+        }
+
+        miner.stop();
+        running.await.unwrap().unwrap();
+        let stats = miner.get_stats().await;
+        assert!(stats.shares_found >= 3);
+        assert!(stats.accepted_shares >= 3);
+        assert!(stats.total_hashes > 0);
+        client.disconnect().await.unwrap();
+    }
+}
@@ -1,7 +1,13 @@
-use crate::core::{ensure_directory_exists, get_process_manager};
+use crate::config::get_config;
+use crate::core::{
+    ensure_directory_exists, get_process_manager, RestartPolicy, StopOutcome, StopPolicy,
+};
+use crate::rpc::{BlockchainInfo, MempoolInfo, NetworkInfo, RpcClient, RpcError};
 use crate::{AppError, AppState, NodeStatus};
+use serde_json::json;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tauri::State;
 
 #[tauri::command]
@@ -92,11 +98,21 @@ pub async fn run_bitcoin_mainnet(use_qt: Option<bool>) -> Result<String, AppErro
     let conf_arg = format!("-conf={}", conf_path.display());
     let mut args = vec![conf_arg.as_str()];
 
-    // Add daemon flag if using bitcoind
+    // Add daemon flag if using bitcoind; the daemon is followed through its pidfile
+    let pidfile = mainnet_conf_dir.join("bitcoind.pid");
+    let pid_arg = format!("-pid={}", pidfile.display());
     if !prefer_qt {
-        args.push("-daemon");
+        args.extend(["-daemon", pid_arg.as_str()]);
     }
-
+    process_manager
+        .set_pidfile("bitcoin_mainnet", Some(pidfile).filter(|_| !prefer_qt))
+        .await;
+
+    // With -daemon the launcher exits cleanly once the node has forked, so only a
+    // crash of a foreground node triggers a restart
+    process_manager
+        .set_restart_policy("bitcoin_mainnet", RestartPolicy::on_failure())
+        .await;
     let pid = process_manager
         .start_process("bitcoin_mainnet", &bitcoin_path, &args, None)
         .await?;
@@ -129,10 +145,18 @@ pub async fn run_bitcoin_pruned(use_qt: Option<bool>) -> Result<String, AppError
     let mut args = vec![conf_arg.as_str(), "-prune=550"];
 
     // Add daemon flag if using bitcoind
+    let pidfile = pruned_conf_dir.join("bitcoind.pid");
+    let pid_arg = format!("-pid={}", pidfile.display());
     if !prefer_qt {
-        args.push("-daemon");
+        args.extend(["-daemon", pid_arg.as_str()]);
     }
+    process_manager
+        .set_pidfile("bitcoin_pruned", Some(pidfile).filter(|_| !prefer_qt))
+        .await;
 
+    process_manager
+        .set_restart_policy("bitcoin_pruned", RestartPolicy::on_failure())
+        .await;
     let pid = process_manager
         .start_process("bitcoin_pruned", &bitcoin_path, &args, None)
         .await?;
@@ -156,10 +180,18 @@ pub async fn run_whive_node(use_qt: Option<bool>) -> Result<String, AppError> {
     let mut args = vec![];
 
     // Add daemon flag if using whived
+    let pidfile = home_dir.join(".whive").join("whived.pid");
+    let pid_arg = format!("-pid={}", pidfile.display());
     if !prefer_qt {
-        args.push("-daemon");
+        args.extend(["-daemon", pid_arg.as_str()]);
     }
+    process_manager
+        .set_pidfile("whive_node", Some(pidfile).filter(|_| !prefer_qt))
+        .await;
 
+    process_manager
+        .set_restart_policy("whive_node", RestartPolicy::on_failure())
+        .await;
     let pid = process_manager
         .start_process("whive_node", &whive_path, &args, None)
         .await?;
@@ -171,10 +203,32 @@ pub async fn run_whive_node(use_qt: Option<bool>) -> Result<String, AppError> {
     ))
 }
 
+// Nodes are stopped over RPC so they can flush their databases before exiting
+fn node_stop_policy(node_type: &str) -> StopPolicy {
+    StopPolicy {
+        timeout: Duration::from_secs(get_config().node_stop_timeout_seconds),
+        rpc_node: Some(node_type.to_string()),
+    }
+}
+
 #[tauri::command]
 pub async fn stop_node(node_type: String) -> Result<String, AppError> {
     let process_manager = get_process_manager();
+    // Set here rather than at start, so a node reattached after an app restart gets it too
+    process_manager
+        .set_stop_policy(&node_type, node_stop_policy(&node_type))
+        .await;
     process_manager.stop_process(&node_type).await?;
+    let forced = process_manager
+        .get_process_info(&node_type)
+        .await
+        .is_some_and(|info| info.stop_outcome == Some(StopOutcome::Forced));
+    if forced {
+        return Ok(format!(
+            "{} node did not shut down in time and was killed",
+            node_type
+        ));
+    }
     Ok(format!("{} node stopped successfully", node_type))
 }
 
@@ -183,18 +237,11 @@ pub async fn get_node_status(node_type: String) -> Result<NodeStatus, AppError>
     let process_manager = get_process_manager();
     let is_running = process_manager.is_process_running(&node_type).await;
 
-    // Try to get actual status via RPC if available
-    let (sync_progress, block_height, peer_count) = if is_running {
-        get_node_rpc_status(&node_type).await.unwrap_or((0.0, 0, 0))
-    } else {
-        (0.0, 0, 0)
-    };
-
-    Ok(NodeStatus {
+    let mut status = NodeStatus {
         is_running,
-        sync_progress,
-        block_height,
-        peer_count,
+        sync_progress: 0.0,
+        block_height: 0,
+        peer_count: 0,
         network: "mainnet".to_string(),
         data_dir: format!(
             "~/.{}",
@@ -217,7 +264,21 @@ pub async fn get_node_status(node_type: String) -> Result<NodeStatus, AppError>
                 "whive"
             }
         ),
-    })
+        headers: 0,
+        mempool_size: 0,
+        uptime_seconds: 0,
+        rpc_error: None,
+    };
+
+    // Try to get actual status via RPC if available
+    if is_running {
+        if let Err(e) = fill_rpc_status(&node_type, &mut status).await {
+            tracing::warn!("Could not query {} over RPC: {}", node_type, e);
+            status.rpc_error = Some(e.to_string());
+        }
+    }
+
+    Ok(status)
 }
 
 // Helper functions
@@ -402,13 +463,9 @@ async fn create_bitcoin_conf(conf_path: &Path, prune: bool) -> Result<(), AppErr
         "".to_string(),
         "# RPC Settings".to_string(),
         "server=1".to_string(),
-        "rpcuser=melanin".to_string(),
-        format!(
-            "rpcpassword={}",
-            std::env::var("BITCOIN_RPC_PASSWORD")
-                .expect("BITCOIN_RPC_PASSWORD environment variable must be set for security")
-        ),
-        "rpcport=8332".to_string(),
+        format!("rpcuser={}", get_config().bitcoin_rpc_user),
+        format!("rpcpassword={}", get_config().bitcoin_rpc_password),
+        format!("rpcport={}", get_config().bitcoin_rpc_port),
         "rpcbind=127.0.0.1".to_string(),
         "rpcallowip=127.0.0.1".to_string(),
         "".to_string(),
@@ -535,14 +592,40 @@ fn find_executable_in_path_sync(
     })
 }
 
-async fn get_node_rpc_status(node_type: &str) -> Result<(f64, u64, u32), AppError> {
-    // This would implement RPC calls to get actual node status
-    // For now, return mock data but this should be implemented
-    if node_type.contains("bitcoin") {
-        // TODO: Implement Bitcoin RPC calls
-        Ok((95.5, 850000, 8))
-    } else {
-        // TODO: Implement Whive RPC calls
-        Ok((88.2, 425000, 6))
+// Fills the live fields of `status` from a single batched RPC request
+async fn fill_rpc_status(node_type: &str, status: &mut NodeStatus) -> Result<(), AppError> {
+    fn parse<T: serde::de::DeserializeOwned>(
+        result: Option<Result<serde_json::Value, RpcError>>,
+    ) -> Result<T, AppError> {
+        let value = result.ok_or_else(|| AppError::Node("Missing RPC result".to_string()))??;
+        Ok(serde_json::from_value(value)?)
     }
+
+    let client = RpcClient::for_node(node_type);
+    let mut results = client
+        .batch(&[
+            ("getblockchaininfo", json!([])),
+            ("getnetworkinfo", json!([])),
+            ("getmempoolinfo", json!([])),
+            ("uptime", json!([])),
+        ])
+        .await?
+        .into_iter();
+    let blockchain: BlockchainInfo = parse(results.next())?;
+    let network: NetworkInfo = parse(results.next())?;
+    let mempool: MempoolInfo = parse(results.next())?;
+    let uptime: u64 = parse(results.next())?;
+
+    status.sync_progress = blockchain.verification_progress * 100.0;
+    status.block_height = blockchain.blocks;
+    status.headers = blockchain.headers;
+    status.network = match blockchain.chain.as_str() {
+        "main" => "mainnet".to_string(),
+        "test" => "testnet".to_string(),
+        chain => chain.to_string(),
+    };
+    status.peer_count = network.connections;
+    status.mempool_size = mempool.size;
+    status.uptime_seconds = uptime;
+    Ok(())
 }
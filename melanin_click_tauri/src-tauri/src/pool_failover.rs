@@ -0,0 +1,316 @@
+use crate::stratum::parse_stratum_url;
+use crate::{FailoverPolicy, PoolEndpoint};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SwitchReason {
+    ConnectionLost,
+    RejectRatio(f64),
+    Failback,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolSwitchEvent {
+    pub at: DateTime<Utc>,
+    pub from_url: String,
+    pub to_url: String,
+    pub reason: SwitchReason,
+}
+
+// Health of the active pool, with share counts since mining on it started
+#[derive(Debug, Clone, Default)]
+pub struct PoolHealth {
+    pub connected: bool,
+    pub accepted_shares: u64,
+    pub rejected_shares: u64,
+}
+
+impl PoolHealth {
+    pub fn reject_ratio(&self) -> f64 {
+        let total = self.accepted_shares + self.rejected_shares;
+        if total == 0 {
+            0.0
+        } else {
+            self.rejected_shares as f64 / total as f64
+        }
+    }
+}
+
+// Decides which pool of an ordered list to mine on; index 0 is the primary
+pub struct PoolFailover {
+    pools: Vec<PoolEndpoint>,
+    policy: FailoverPolicy,
+    current: usize,
+    active_since: DateTime<Utc>,
+}
+
+impl PoolFailover {
+    pub fn new(pools: Vec<PoolEndpoint>, policy: FailoverPolicy, now: DateTime<Utc>) -> Self {
+        Self {
+            pools,
+            policy,
+            current: 0,
+            active_since: now,
+        }
+    }
+
+    pub fn current(&self) -> &PoolEndpoint {
+        &self.pools[self.current]
+    }
+
+    pub fn is_on_primary(&self) -> bool {
+        self.current == 0
+    }
+
+    pub fn primary(&self) -> &PoolEndpoint {
+        &self.pools[0]
+    }
+
+    pub fn disconnect_grace(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.policy.disconnect_grace_secs as i64)
+    }
+
+    // Returns the switch to make, if any, given the health of the active pool
+    pub fn evaluate(&mut self, health: &PoolHealth, now: DateTime<Utc>) -> Option<PoolSwitchEvent> {
+        if self.pools.len() < 2 {
+            return None;
+        }
+
+        if !health.connected {
+            warn!("Lost connection to pool {}", self.current().url);
+            return Some(self.switch_to(self.next_index(), SwitchReason::ConnectionLost, now));
+        }
+
+        let shares = health.accepted_shares + health.rejected_shares;
+        let reject_ratio = health.reject_ratio();
+        if shares >= self.policy.min_shares_for_ratio && reject_ratio > self.policy.max_reject_ratio
+        {
+            warn!(
+                "Reject ratio {:.1}% on pool {} exceeds {:.1}%",
+                reject_ratio * 100.0,
+                self.current().url,
+                self.policy.max_reject_ratio * 100.0
+            );
+            return Some(self.switch_to(
+                self.next_index(),
+                SwitchReason::RejectRatio(reject_ratio),
+                now,
+            ));
+        }
+
+        None
+    }
+
+    // True once a backup pool has been mined on long enough to try the primary again
+    pub fn failback_due(&self, now: DateTime<Utc>) -> bool {
+        let active_for = now.signed_duration_since(self.active_since).num_seconds();
+        !self.is_on_primary() && active_for >= self.policy.failback_after_secs as i64
+    }
+
+    // Returns to the primary if it answered a probe; otherwise waits another failback
+    // period before trying it again
+    pub fn failback(
+        &mut self,
+        primary_reachable: bool,
+        now: DateTime<Utc>,
+    ) -> Option<PoolSwitchEvent> {
+        if !self.failback_due(now) {
+            return None;
+        }
+        if !primary_reachable {
+            warn!(
+                "Primary pool {} is still unreachable, staying on {}",
+                self.primary().url,
+                self.current().url
+            );
+            self.active_since = now;
+            return None;
+        }
+        Some(self.switch_to(0, SwitchReason::Failback, now))
+    }
+
+    fn next_index(&self) -> usize {
+        (self.current + 1) % self.pools.len()
+    }
+
+    fn switch_to(
+        &mut self,
+        index: usize,
+        reason: SwitchReason,
+        now: DateTime<Utc>,
+    ) -> PoolSwitchEvent {
+        let event = PoolSwitchEvent {
+            at: now,
+            from_url: self.current().url.clone(),
+            to_url: self.pools[index].url.clone(),
+            reason,
+        };
+        info!(
+            "Switching pool from {} to {} ({:?})",
+            event.from_url, event.to_url, event.reason
+        );
+
+        self.current = index;
+        self.active_since = now;
+        event
+    }
+}
+
+// True if the pool accepts a TCP connection
+pub async fn probe_pool(url: &str) -> bool {
+    let Ok((host, port)) = parse_stratum_url(url) else {
+        return false;
+    };
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((host.as_str(), port))).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            debug!("Probe of pool {} failed: {}", url, e);
+            false
+        }
+        Err(_) => {
+            debug!("Probe of pool {} timed out", url);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn pools() -> Vec<PoolEndpoint> {
+        ["primary", "backup-1", "backup-2"]
+            .iter()
+            .map(|name| PoolEndpoint {
+                url: format!("stratum+tcp://{}.pool.com:3333", name),
+                username: "worker".to_string(),
+                password: "x".to_string(),
+            })
+            .collect()
+    }
+
+    fn policy() -> FailoverPolicy {
+        FailoverPolicy {
+            max_reject_ratio: 0.1,
+            min_shares_for_ratio: 20,
+            failback_after_secs: 600,
+            disconnect_grace_secs: 60,
+        }
+    }
+
+    fn healthy(accepted: u64, rejected: u64) -> PoolHealth {
+        PoolHealth {
+            connected: true,
+            accepted_shares: accepted,
+            rejected_shares: rejected,
+        }
+    }
+
+    #[test]
+    fn test_fails_over_in_order_on_connection_loss() {
+        let start = Utc::now();
+        let mut failover = PoolFailover::new(pools(), policy(), start);
+        let down = PoolHealth::default();
+
+        let event = failover.evaluate(&down, start).unwrap();
+        assert_eq!(event.reason, SwitchReason::ConnectionLost);
+        assert_eq!(event.from_url, "stratum+tcp://primary.pool.com:3333");
+        assert_eq!(event.to_url, "stratum+tcp://backup-1.pool.com:3333");
+
+        failover.evaluate(&down, start).unwrap();
+        assert_eq!(
+            failover.current().url,
+            "stratum+tcp://backup-2.pool.com:3333"
+        );
+
+        // The list wraps around to the primary
+        failover.evaluate(&down, start).unwrap();
+        assert!(failover.is_on_primary());
+    }
+
+    #[test]
+    fn test_reject_ratio_needs_enough_shares() {
+        let start = Utc::now();
+        let mut failover = PoolFailover::new(pools(), policy(), start);
+
+        // 50% rejected, but too few shares to judge the pool
+        assert!(failover.evaluate(&healthy(5, 5), start).is_none());
+        assert!(failover.evaluate(&healthy(18, 2), start).is_none());
+
+        let event = failover.evaluate(&healthy(15, 5), start).unwrap();
+        assert_eq!(event.reason, SwitchReason::RejectRatio(0.25));
+        assert_eq!(
+            failover.current().url,
+            "stratum+tcp://backup-1.pool.com:3333"
+        );
+    }
+
+    #[test]
+    fn test_fails_back_after_healthy_period() {
+        let start = Utc::now();
+        let mut failover = PoolFailover::new(pools(), policy(), start);
+        failover.evaluate(&PoolHealth::default(), start).unwrap();
+
+        let almost = start + Duration::seconds(599);
+        assert!(failover.evaluate(&healthy(100, 1), almost).is_none());
+        assert!(!failover.failback_due(almost));
+        assert!(failover.failback(true, almost).is_none());
+
+        let due = start + Duration::seconds(600);
+        assert!(failover.evaluate(&healthy(100, 1), due).is_none());
+        assert!(failover.failback_due(due));
+        let event = failover.failback(true, due).unwrap();
+        assert_eq!(event.reason, SwitchReason::Failback);
+        assert_eq!(event.to_url, "stratum+tcp://primary.pool.com:3333");
+
+        // Staying healthy on the primary never triggers another switch
+        let later = start + Duration::seconds(3600);
+        assert!(failover.evaluate(&healthy(500, 2), later).is_none());
+        assert!(!failover.failback_due(later));
+    }
+
+    #[test]
+    fn test_failback_waits_for_primary() {
+        let start = Utc::now();
+        let mut failover = PoolFailover::new(pools(), policy(), start);
+        failover.evaluate(&PoolHealth::default(), start).unwrap();
+
+        // A primary that does not answer keeps the miner on the backup for another period
+        let due = start + Duration::seconds(600);
+        assert!(failover.failback(false, due).is_none());
+        assert!(!failover.is_on_primary());
+        assert!(!failover.failback_due(due + Duration::seconds(599)));
+
+        let event = failover
+            .failback(true, due + Duration::seconds(600))
+            .unwrap();
+        assert_eq!(event.reason, SwitchReason::Failback);
+        assert!(failover.is_on_primary());
+    }
+
+    #[tokio::test]
+    async fn test_probe_pool() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(probe_pool(&format!("stratum+tcp://127.0.0.1:{port}")).await);
+
+        drop(listener);
+        assert!(!probe_pool(&format!("stratum+tcp://127.0.0.1:{port}")).await);
+        assert!(!probe_pool("not a pool url").await);
+    }
+
+    #[test]
+    fn test_single_pool_never_switches() {
+        let start = Utc::now();
+        let mut failover = PoolFailover::new(pools()[..1].to_vec(), policy(), start);
+        assert!(failover.evaluate(&PoolHealth::default(), start).is_none());
+        assert!(failover.evaluate(&healthy(10, 90), start).is_none());
+    }
+}
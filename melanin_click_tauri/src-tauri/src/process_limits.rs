@@ -0,0 +1,280 @@
+use crate::AppError;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoPriority {
+    // Only gets disk time nobody else wants
+    Idle,
+    // Level 0 is served first, 7 last
+    BestEffort(u8),
+}
+
+// Scheduling and resource limits a child process starts with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessLimits {
+    pub nice: Option<i32>,
+    // Cores the process may run on; empty means all of them
+    pub cpu_affinity: Vec<usize>,
+    pub memory_limit_mb: Option<u64>,
+    pub io_priority: Option<IoPriority>,
+}
+
+impl ProcessLimits {
+    // Reads the `key:value` entries of MiningConfig.hardware_selection:
+    // `cpu:0-3,6`, `nice:10`, `memory:2048` (MB), `io:idle` and `io:best-effort:7`.
+    // Entries with other keys select devices and are left to the miners.
+    pub fn from_hardware_selection(entries: &[String]) -> Result<Self, AppError> {
+        let mut limits = Self::default();
+        for entry in entries {
+            let Some((key, value)) = entry.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "cpu" => limits.cpu_affinity.extend(parse_cores(value)?),
+                "nice" => {
+                    let nice = value
+                        .parse()
+                        .ok()
+                        .filter(|nice| (-20..=19).contains(nice))
+                        .ok_or_else(|| invalid(entry, "nice must be between -20 and 19"))?;
+                    limits.nice = Some(nice);
+                }
+                "memory" => {
+                    let megabytes = value
+                        .parse()
+                        .ok()
+                        .filter(|megabytes| *megabytes > 0)
+                        .ok_or_else(|| invalid(entry, "memory must be a positive number of MB"))?;
+                    limits.memory_limit_mb = Some(megabytes);
+                }
+                "io" => limits.io_priority = Some(parse_io_priority(entry, value)?),
+                _ => {}
+            }
+        }
+
+        limits.cpu_affinity.sort_unstable();
+        limits.cpu_affinity.dedup();
+        if let Some(core) = limits
+            .cpu_affinity
+            .iter()
+            .find(|core| **core >= num_cpus::get())
+        {
+            return Err(AppError::Validation(format!(
+                "CPU core {core} does not exist; this machine has {} cores",
+                num_cpus::get()
+            )));
+        }
+        Ok(limits)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Makes the command's process start with these limits, set between fork and exec
+    pub fn apply(&self, cmd: &mut Command) {
+        if self.is_empty() {
+            return;
+        }
+
+        #[cfg(unix)]
+        {
+            if !cfg!(any(target_os = "linux", target_os = "android"))
+                && (!self.cpu_affinity.is_empty() || self.io_priority.is_some())
+            {
+                warn!("CPU affinity and I/O priority are only supported on Linux");
+            }
+            let limits = self.clone();
+            // SAFETY: the closure makes only async-signal-safe system calls
+            unsafe {
+                cmd.pre_exec(move || limits.apply_to_current_process());
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = cmd;
+            warn!("Process limits are not supported on this platform");
+        }
+    }
+
+    #[cfg(unix)]
+    fn apply_to_current_process(&self) -> std::io::Result<()> {
+        if let Some(nice) = self.nice {
+            // SAFETY: plain system call on the calling process
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        if let Some(megabytes) = self.memory_limit_mb {
+            let bytes = megabytes.saturating_mul(1024 * 1024) as libc::rlim_t;
+            let limit = libc::rlimit {
+                rlim_cur: bytes,
+                rlim_max: bytes,
+            };
+            // SAFETY: `limit` is a valid rlimit for the duration of the call
+            if unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if !self.cpu_affinity.is_empty() {
+                // SAFETY: the set is zeroed before use and outlives the call
+                unsafe {
+                    let mut set: libc::cpu_set_t = std::mem::zeroed();
+                    for core in &self.cpu_affinity {
+                        libc::CPU_SET(*core, &mut set);
+                    }
+                    if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0
+                    {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+            }
+
+            if let Some(io_priority) = self.io_priority {
+                const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+                const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+                let value = match io_priority {
+                    IoPriority::BestEffort(level) => {
+                        (2 << IOPRIO_CLASS_SHIFT) | level as libc::c_int
+                    }
+                    IoPriority::Idle => 3 << IOPRIO_CLASS_SHIFT,
+                };
+                // SAFETY: ioprio_set takes only integer arguments
+                if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, value) } != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(entry: &str, reason: &str) -> AppError {
+    AppError::Validation(format!("Invalid hardware selection '{entry}': {reason}"))
+}
+
+// Cores as a list of numbers and ranges, such as `0-3,6`
+fn parse_cores(value: &str) -> Result<Vec<usize>, AppError> {
+    let mut cores = Vec::new();
+    for part in value.split(',').map(str::trim) {
+        let parsed = match part.split_once('-') {
+            Some((first, last)) => first
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .zip(last.trim().parse::<usize>().ok())
+                .filter(|(first, last)| first <= last)
+                .map(|(first, last)| (first..=last).collect::<Vec<_>>()),
+            None => part.parse().ok().map(|core| vec![core]),
+        };
+        cores.extend(
+            parsed
+                .ok_or_else(|| invalid(&format!("cpu:{value}"), "expected cores such as 0-3,6"))?,
+        );
+    }
+    Ok(cores)
+}
+
+fn parse_io_priority(entry: &str, value: &str) -> Result<IoPriority, AppError> {
+    match value.split_once(':') {
+        None if value == "idle" => Ok(IoPriority::Idle),
+        None if value == "best-effort" => Ok(IoPriority::BestEffort(4)),
+        Some(("best-effort", level)) => level
+            .trim()
+            .parse()
+            .ok()
+            .filter(|level| *level <= 7)
+            .map(IoPriority::BestEffort)
+            .ok_or_else(|| invalid(entry, "best-effort level must be between 0 and 7")),
+        _ => Err(invalid(entry, "expected idle or best-effort[:0-7]")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parses_hardware_selection() {
+        let limits = ProcessLimits::from_hardware_selection(&selection(&[
+            "cpu:0",
+            "GPU 0",
+            "nice:10",
+            "memory:512",
+            "io:best-effort:6",
+        ]))
+        .unwrap();
+        assert_eq!(
+            limits,
+            ProcessLimits {
+                nice: Some(10),
+                cpu_affinity: vec![0],
+                memory_limit_mb: Some(512),
+                io_priority: Some(IoPriority::BestEffort(6)),
+            }
+        );
+        assert!(ProcessLimits::from_hardware_selection(&selection(&["cpu"]))
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(parse_cores("0-2, 5").unwrap(), vec![0, 1, 2, 5]);
+        assert_eq!(
+            parse_io_priority("io:idle", "idle").unwrap(),
+            IoPriority::Idle
+        );
+        for bad in [
+            "nice:25",
+            "memory:0",
+            "io:realtime",
+            "cpu:3-1",
+            "cpu:100000",
+        ] {
+            assert!(
+                ProcessLimits::from_hardware_selection(&selection(&[bad])).is_err(),
+                "{bad} should be rejected"
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_child_starts_with_limits() {
+        let limits = ProcessLimits {
+            nice: Some(7),
+            cpu_affinity: vec![0],
+            memory_limit_mb: Some(4096),
+            io_priority: Some(IoPriority::Idle),
+        };
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "grep Cpus_allowed_list /proc/self/status; \
+             grep 'Max address space' /proc/self/limits; \
+             cut -d ' ' -f 19 /proc/self/stat",
+        ]);
+        limits.apply(&mut cmd);
+        let output = cmd.output().await.unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+
+        assert!(output.status.success());
+        assert!(lines[0].ends_with("\t0"), "{}", lines[0]);
+        assert!(lines[1].contains(&(4096u64 * 1024 * 1024).to_string()));
+        assert_eq!(lines[2], "7");
+    }
+}
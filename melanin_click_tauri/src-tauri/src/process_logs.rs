@@ -0,0 +1,381 @@
+use crate::AppError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+// Lines kept per process; older lines are dropped first
+pub const LOG_CAPACITY: usize = 1000;
+// Longer lines are split, so output that never ends a line cannot grow without bound
+pub const MAX_LINE_BYTES: usize = 4096;
+// Event carrying each new output line to the UI
+pub const LOG_EVENT: &str = "process-log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLine {
+    // Increases across all processes, so a client can ask for what it has not seen
+    pub seq: u64,
+    pub process: String,
+    pub stream: LogStream,
+    pub line: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Recent stdout and stderr of every process, in ring buffers
+pub struct ProcessLogs {
+    buffers: Mutex<HashMap<String, VecDeque<LogLine>>>,
+    capacity: usize,
+    next_seq: AtomicU64,
+    sender: broadcast::Sender<LogLine>,
+}
+
+impl Default for ProcessLogs {
+    fn default() -> Self {
+        Self::new(LOG_CAPACITY)
+    }
+}
+
+impl ProcessLogs {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(256);
+        Self {
+            buffers: Mutex::new(HashMap::new()),
+            capacity: capacity.max(1),
+            next_seq: AtomicU64::new(1),
+            sender,
+        }
+    }
+
+    // Sequence number the next line will get
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq.load(Ordering::SeqCst)
+    }
+
+    pub fn push(&self, process: &str, stream: LogStream, line: &str) {
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let log_line = LogLine {
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            process: process.to_string(),
+            stream,
+            line: line.to_string(),
+            timestamp: Utc::now(),
+        };
+
+        let buffer = buffers.entry(process.to_string()).or_default();
+        if buffer.len() == self.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(log_line.clone());
+        drop(buffers);
+
+        // Nobody listening is fine
+        let _ = self.sender.send(log_line);
+    }
+
+    // Reads lines until the stream closes, passing each to `on_line` after buffering it.
+    // Both \n and \r end a line, so progress output redrawn in place is kept too.
+    // Invalid UTF-8 is replaced rather than ending the read, which would leave the
+    // child blocked on a full pipe.
+    pub fn drain<R>(
+        &'static self,
+        process: &str,
+        stream: LogStream,
+        reader: R,
+        mut on_line: impl FnMut(&str) + Send + 'static,
+    ) -> JoinHandle<()>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let process = process.to_string();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            let mut line = Vec::with_capacity(MAX_LINE_BYTES);
+            let mut emit = |line: &mut Vec<u8>| {
+                let text = String::from_utf8_lossy(line);
+                self.push(&process, stream, &text);
+                on_line(&text);
+                line.clear();
+            };
+            // A \n right after a \r or a split line ends the line already emitted
+            let mut line_ended = false;
+
+            loop {
+                let available = match reader.fill_buf().await {
+                    Ok(available) => available,
+                    Err(e) => {
+                        warn!("Stopped reading {:?} of {}: {}", stream, process, e);
+                        break;
+                    }
+                };
+                if available.is_empty() {
+                    if !line.is_empty() {
+                        emit(&mut line);
+                    }
+                    break;
+                }
+
+                if line_ended && available[0] == b'\n' {
+                    line_ended = false;
+                    reader.consume(1);
+                    continue;
+                }
+
+                let room = MAX_LINE_BYTES - line.len();
+                match available.iter().position(|b| *b == b'\n' || *b == b'\r') {
+                    Some(end) if end <= room => {
+                        line_ended = available[end] == b'\r';
+                        line.extend_from_slice(&available[..end]);
+                        reader.consume(end + 1);
+                        emit(&mut line);
+                    }
+                    _ => {
+                        let taken = available.len().min(room);
+                        line.extend_from_slice(&available[..taken]);
+                        reader.consume(taken);
+                        line_ended = line.len() == MAX_LINE_BYTES;
+                        if line_ended {
+                            emit(&mut line);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    // Last `count` lines, optionally from one stream only
+    pub fn tail(&self, process: &str, stream: Option<LogStream>, count: usize) -> Vec<LogLine> {
+        self.select(
+            process,
+            |line| stream.is_none_or(|stream| line.stream == stream),
+            count,
+        )
+    }
+
+    // Last `count` lines containing `pattern`, ignoring case
+    pub fn search(&self, process: &str, pattern: &str, count: usize) -> Vec<LogLine> {
+        let pattern = pattern.to_lowercase();
+        self.select(
+            process,
+            |line| line.line.to_lowercase().contains(&pattern),
+            count,
+        )
+    }
+
+    // Lines numbered `seq` or later
+    pub fn since(&self, process: &str, seq: u64) -> Vec<LogLine> {
+        self.select(process, |line| line.seq >= seq, usize::MAX)
+    }
+
+    fn select(
+        &self,
+        process: &str,
+        filter: impl Fn(&LogLine) -> bool,
+        count: usize,
+    ) -> Vec<LogLine> {
+        let buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(buffer) = buffers.get(process) else {
+            return Vec::new();
+        };
+        let mut lines: Vec<LogLine> = buffer
+            .iter()
+            .rev()
+            .filter(|line| filter(line))
+            .take(count)
+            .cloned()
+            .collect();
+        lines.reverse();
+        lines
+    }
+
+    pub fn processes(&self) -> Vec<String> {
+        let buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut names: Vec<String> = buffers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LogLine> {
+        self.sender.subscribe()
+    }
+}
+
+// Global log buffer instance
+lazy_static::lazy_static! {
+    pub static ref PROCESS_LOGS: ProcessLogs = ProcessLogs::default();
+}
+
+// Pushes every new output line to the UI as a `process-log` event
+pub async fn forward_to_ui(app: AppHandle) {
+    let mut receiver = PROCESS_LOGS.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(line) => {
+                if let Err(e) = app.emit(LOG_EVENT, line) {
+                    warn!("Failed to emit process output: {}", e);
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Dropped {} process output lines for the UI", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+// Recent output of a process: the last `lines` lines, those matching `pattern`,
+// or those after `since_seq`
+#[tauri::command]
+pub async fn get_process_logs(
+    process: String,
+    lines: Option<usize>,
+    pattern: Option<String>,
+    stream: Option<LogStream>,
+    since_seq: Option<u64>,
+) -> Result<Vec<LogLine>, AppError> {
+    let mut found = match pattern {
+        Some(pattern) => PROCESS_LOGS.search(&process, &pattern, usize::MAX),
+        None => PROCESS_LOGS.tail(&process, stream, usize::MAX),
+    };
+    found.retain(|line| {
+        stream.is_none_or(|stream| line.stream == stream)
+            && since_seq.is_none_or(|seq| line.seq >= seq)
+    });
+
+    let skip = found.len().saturating_sub(lines.unwrap_or(100));
+    Ok(found.split_off(skip))
+}
+
+#[tauri::command]
+pub async fn list_logged_processes() -> Result<Vec<String>, AppError> {
+    Ok(PROCESS_LOGS.processes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_keeps_latest_lines() {
+        let logs = ProcessLogs::new(3);
+        for i in 0..5 {
+            logs.push("miner", LogStream::Stdout, &format!("line {i}"));
+        }
+        logs.push("node", LogStream::Stderr, "other process");
+
+        let tail: Vec<String> = logs
+            .tail("miner", None, 10)
+            .into_iter()
+            .map(|line| line.line)
+            .collect();
+        assert_eq!(tail, vec!["line 2", "line 3", "line 4"]);
+        assert_eq!(logs.tail("miner", None, 1)[0].line, "line 4");
+        assert!(logs.tail("missing", None, 10).is_empty());
+        assert_eq!(logs.processes(), vec!["miner", "node"]);
+    }
+
+    #[test]
+    fn test_search_and_stream_filters() {
+        let logs = ProcessLogs::new(10);
+        logs.push("miner", LogStream::Stdout, "accepted: 1/1");
+        logs.push("miner", LogStream::Stderr, "Stratum connection FAILED");
+        let since = logs.next_seq();
+        logs.push("miner", LogStream::Stdout, "accepted: 2/3");
+
+        assert_eq!(logs.search("miner", "accepted", 10).len(), 2);
+        assert_eq!(
+            logs.search("miner", "failed", 10)[0].stream,
+            LogStream::Stderr
+        );
+        assert_eq!(logs.tail("miner", Some(LogStream::Stderr), 10).len(), 1);
+        let new_lines = logs.since("miner", since);
+        assert_eq!(new_lines.len(), 1);
+        assert_eq!(new_lines[0].line, "accepted: 2/3");
+    }
+
+    #[tokio::test]
+    async fn test_drain_buffers_and_broadcasts() {
+        let mut receiver = PROCESS_LOGS.subscribe();
+        let (reader, mut writer) = tokio::io::duplex(64);
+        let seen = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let task = PROCESS_LOGS.drain("drain_test", LogStream::Stderr, reader, {
+            let seen = seen.clone();
+            move |line| seen.lock().unwrap().push(line.to_string())
+        });
+
+        tokio::io::AsyncWriteExt::write_all(&mut writer, b"one\ntwo\n")
+            .await
+            .unwrap();
+        drop(writer);
+        task.await.unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), vec!["one", "two"]);
+        assert_eq!(PROCESS_LOGS.tail("drain_test", None, 10).len(), 2);
+        loop {
+            let line = receiver.recv().await.unwrap();
+            if line.process == "drain_test" {
+                assert_eq!(line.line, "one");
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_keeps_reading_after_invalid_utf8() {
+        let (reader, mut writer) = tokio::io::duplex(64);
+        let task = PROCESS_LOGS.drain("utf8_test", LogStream::Stdout, reader, |_| {});
+
+        tokio::io::AsyncWriteExt::write_all(&mut writer, b"bad \xff\xfe byte\r\nafter\nlast")
+            .await
+            .unwrap();
+        drop(writer);
+        task.await.unwrap();
+
+        let lines: Vec<String> = PROCESS_LOGS
+            .tail("utf8_test", None, 10)
+            .into_iter()
+            .map(|line| line.line)
+            .collect();
+        assert_eq!(lines, vec!["bad \u{fffd}\u{fffd} byte", "after", "last"]);
+    }
+
+    #[tokio::test]
+    async fn test_drain_splits_carriage_returns_and_long_lines() {
+        let (reader, mut writer) = tokio::io::duplex(64);
+        let task = PROCESS_LOGS.drain("split_test", LogStream::Stderr, reader, |_| {});
+
+        let mut output = b"10%\r20%\r\ndone\n".to_vec();
+        output.extend(std::iter::repeat_n(b'x', 2 * MAX_LINE_BYTES + 100));
+        output.extend(b"\n");
+        output.extend(std::iter::repeat_n(b'y', MAX_LINE_BYTES));
+        output.extend(b"\nlast\n");
+        tokio::io::AsyncWriteExt::write_all(&mut writer, &output)
+            .await
+            .unwrap();
+        drop(writer);
+        task.await.unwrap();
+
+        let lengths: Vec<usize> = PROCESS_LOGS
+            .tail("split_test", None, 10)
+            .iter()
+            .map(|line| line.line.len())
+            .collect();
+        let max = MAX_LINE_BYTES;
+        assert_eq!(lengths, vec![3, 3, 4, max, max, 100, max, 4]);
+        let lines = PROCESS_LOGS.tail("split_test", None, 10);
+        assert_eq!(lines[1].line, "20%");
+        assert_eq!(lines[7].line, "last");
+    }
+}
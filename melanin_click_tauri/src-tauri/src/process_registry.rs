@@ -0,0 +1,158 @@
+use crate::AppError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessStatus, System, UpdateKind};
+
+// A managed process as saved across app restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub pid: u32,
+    pub command: String,
+    pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+    pub started_at: DateTime<Utc>,
+    // Written by a daemonized process; holds the pid once it has forked
+    pub pidfile: Option<PathBuf>,
+}
+
+pub fn registry_path() -> Option<PathBuf> {
+    Some(
+        dirs::home_dir()?
+            .join(".melanin_click")
+            .join("processes.json"),
+    )
+}
+
+// Miners whose output the app parses; they cannot be reattached, only cleaned up
+pub fn miner_registry_path() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".melanin_click").join("miners.json"))
+}
+
+pub fn load(path: &Path) -> Vec<RegistryEntry> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(path: &Path, entries: &[RegistryEntry]) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(entries)?)?;
+    Ok(())
+}
+
+pub fn read_pidfile(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+// True if `pid` is alive and still runs `command` with `args`, so a pid reused by
+// another program is not mistaken for ours
+pub fn process_matches(pid: u32, command: &str, args: &[String]) -> bool {
+    let mut system = System::new();
+    let process_pid = Pid::from(pid as usize);
+    let refresh = ProcessRefreshKind::new().with_cmd(UpdateKind::Always);
+    if !system.refresh_process_specifics(process_pid, refresh) {
+        return false;
+    }
+    let Some(process) = system.process(process_pid) else {
+        return false;
+    };
+    if matches!(
+        process.status(),
+        ProcessStatus::Zombie | ProcessStatus::Dead
+    ) {
+        return false;
+    }
+
+    let cmd = process.cmd();
+    let program = cmd
+        .first()
+        .map(|arg| file_name(arg))
+        .unwrap_or_else(|| process.name().to_string());
+    let cmd_args = cmd.get(1..).unwrap_or_default();
+    program == file_name(command) && args.iter().all(|arg| cmd_args.contains(arg))
+}
+
+// Program and arguments a running process was started with
+pub fn command_line(pid: u32) -> Option<(String, Vec<String>)> {
+    let mut system = System::new();
+    let process_pid = Pid::from(pid as usize);
+    let refresh = ProcessRefreshKind::new().with_cmd(UpdateKind::Always);
+    if !system.refresh_process_specifics(process_pid, refresh) {
+        return None;
+    }
+    let (program, args) = system.process(process_pid)?.cmd().split_first()?;
+    Some((program.clone(), args.to_vec()))
+}
+
+// Kills `pid` only while it still runs `command` with `args`
+pub fn kill_matching(pid: u32, command: &str, args: &[String]) -> bool {
+    if !process_matches(pid, command, args) {
+        return false;
+    }
+    let mut system = System::new();
+    let process_pid = Pid::from(pid as usize);
+    system.refresh_process_specifics(process_pid, ProcessRefreshKind::new());
+    system
+        .process(process_pid)
+        .is_some_and(|process| process.kill())
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("processes.json");
+        assert!(load(&path).is_empty());
+
+        let entry = RegistryEntry {
+            name: "bitcoin_mainnet".to_string(),
+            pid: 4242,
+            command: "/opt/bitcoin/bin/bitcoind".to_string(),
+            args: vec!["-daemon".to_string()],
+            working_dir: None,
+            started_at: Utc::now(),
+            pidfile: Some(dir.path().join("bitcoind.pid")),
+        };
+        save(&path, std::slice::from_ref(&entry)).unwrap();
+        assert_eq!(load(&path), vec![entry]);
+
+        std::fs::write(dir.path().join("bitcoind.pid"), "4243\n").unwrap();
+        assert_eq!(read_pidfile(&dir.path().join("bitcoind.pid")), Some(4243));
+        assert_eq!(read_pidfile(&dir.path().join("missing.pid")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_matches_command_line() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        // Let the fork reach exec before its command line is read
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        assert!(process_matches(pid, "/bin/sleep", &["30".to_string()]));
+        assert!(!process_matches(pid, "sleep", &["31".to_string()]));
+        assert!(!process_matches(pid, "bitcoind", &[]));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(!process_matches(pid, "sleep", &[]));
+    }
+}
@@ -0,0 +1,611 @@
+use crate::config::get_config;
+use base64::{engine::general_purpose, Engine as _};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+// Error code a node answers with while it is still loading its block index
+pub const RPC_IN_WARMUP: i64 = -28;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    // The node could not be reached or did not answer in time
+    #[error("could not reach the node: {0}")]
+    Transport(String),
+    #[error("authentication failed: {0}")]
+    Auth(String),
+    // Error object returned by the node
+    #[error("{}", describe_rpc_error(*code, message))]
+    Rpc { code: i64, message: String },
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+impl RpcError {
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            RpcError::Rpc { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    pub fn is_warming_up(&self) -> bool {
+        self.code() == Some(RPC_IN_WARMUP)
+    }
+
+    // Whether trying the same request again later could succeed
+    fn is_retryable(&self) -> bool {
+        matches!(self, RpcError::Transport(_)) || self.is_warming_up()
+    }
+}
+
+fn describe_rpc_error(code: i64, message: &str) -> String {
+    if code == RPC_IN_WARMUP {
+        format!("node is still warming up ({message})")
+    } else {
+        format!("error {code}: {message}")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RpcAuth {
+    UserPass { user: String, password: String },
+    // Rewritten by the node on every start, so it is read for each request
+    CookieFile(PathBuf),
+}
+
+impl RpcAuth {
+    fn header(&self) -> Result<String, RpcError> {
+        let credentials = match self {
+            RpcAuth::UserPass { user, password } => format!("{user}:{password}"),
+            RpcAuth::CookieFile(path) => std::fs::read_to_string(path)
+                .map_err(|e| RpcError::Auth(format!("cannot read cookie {}: {e}", path.display())))?
+                .trim()
+                .to_string(),
+        };
+        Ok(format!(
+            "Basic {}",
+            general_purpose::STANDARD.encode(credentials)
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockchainInfo {
+    pub chain: String,
+    pub blocks: u64,
+    pub headers: u64,
+    #[serde(rename = "bestblockhash")]
+    pub best_block_hash: String,
+    pub difficulty: f64,
+    #[serde(rename = "verificationprogress")]
+    pub verification_progress: f64,
+    #[serde(rename = "initialblockdownload", default)]
+    pub initial_block_download: bool,
+    #[serde(default)]
+    pub pruned: bool,
+    #[serde(default)]
+    pub size_on_disk: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkInfo {
+    pub version: u64,
+    pub subversion: String,
+    #[serde(rename = "protocolversion")]
+    pub protocol_version: u64,
+    pub connections: u32,
+    #[serde(default)]
+    pub connections_in: u32,
+    #[serde(default)]
+    pub connections_out: u32,
+    #[serde(rename = "networkactive", default)]
+    pub network_active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub id: u64,
+    pub addr: String,
+    #[serde(default)]
+    pub subver: String,
+    #[serde(default)]
+    pub inbound: bool,
+    #[serde(default)]
+    pub synced_blocks: i64,
+    #[serde(rename = "pingtime")]
+    pub ping_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MempoolInfo {
+    // Number of transactions
+    pub size: u64,
+    pub bytes: u64,
+    #[serde(default)]
+    pub usage: u64,
+    #[serde(rename = "mempoolminfee", default)]
+    pub mempool_min_fee: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MiningInfo {
+    pub blocks: u64,
+    pub difficulty: f64,
+    // Hashes per second, estimated over the last 120 blocks
+    #[serde(rename = "networkhashps")]
+    pub network_hashps: Option<f64>,
+    #[serde(rename = "pooledtx", default)]
+    pub pooled_tx: u64,
+    #[serde(default)]
+    pub chain: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: Value,
+    error: Option<RpcErrorObject>,
+    id: Value,
+}
+
+// Async JSON-RPC client for bitcoind and whived
+pub struct RpcClient {
+    url: String,
+    auth: RpcAuth,
+    timeout: Duration,
+    retries: u32,
+    client: reqwest::Client,
+    next_id: AtomicU64,
+}
+
+impl RpcClient {
+    pub fn new(url: &str, auth: RpcAuth) -> Self {
+        Self {
+            url: url.to_string(),
+            auth,
+            timeout: Duration::from_secs(30),
+            retries: 3,
+            client: reqwest::Client::new(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Further attempts after a failed one, for connection errors and a node still warming up
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    // Client for one of our nodes, preferring its cookie file over the configured password
+    pub fn for_node(node_type: &str) -> Self {
+        let config = get_config();
+        let (url, user, password, data_dir) = if node_type.contains("bitcoin") {
+            (
+                config.get_bitcoin_rpc_url(),
+                &config.bitcoin_rpc_user,
+                &config.bitcoin_rpc_password,
+                ".bitcoin",
+            )
+        } else {
+            (
+                config.get_whive_rpc_url(),
+                &config.whive_rpc_user,
+                &config.whive_rpc_password,
+                ".whive",
+            )
+        };
+
+        let cookie = dirs::home_dir().map(|home| home.join(data_dir).join(".cookie"));
+        let auth = match cookie.filter(|cookie| cookie.exists()) {
+            Some(cookie) => RpcAuth::CookieFile(cookie),
+            None => RpcAuth::UserPass {
+                user: user.clone(),
+                password: password.clone(),
+            },
+        };
+
+        Self::new(&url, auth)
+            .with_timeout(Duration::from_secs(config.network_timeout_seconds))
+            .with_retries(config.max_retry_attempts)
+    }
+
+    pub async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RpcError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({ "jsonrpc": "1.0", "id": id, "method": method, "params": params });
+        let response: RpcResponse = self.send(&request).await?;
+        parse_result(method, response)
+    }
+
+    // Sends several calls in one request; each result is returned in the order of `calls`
+    pub async fn batch(
+        &self,
+        calls: &[(&str, Value)],
+    ) -> Result<Vec<Result<Value, RpcError>>, RpcError> {
+        let first_id = self
+            .next_id
+            .fetch_add(calls.len() as u64, Ordering::Relaxed);
+        let requests: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (method, params))| {
+                let id = first_id + i as u64;
+                json!({ "jsonrpc": "1.0", "id": id, "method": method, "params": params })
+            })
+            .collect();
+
+        let mut responses: Vec<RpcResponse> = self.send(&Value::Array(requests)).await?;
+        Ok(calls
+            .iter()
+            .enumerate()
+            .map(|(i, (method, _))| {
+                let id = first_id + i as u64;
+                match responses
+                    .iter()
+                    .position(|response| response.id == json!(id))
+                {
+                    Some(index) => parse_result(method, responses.swap_remove(index)),
+                    None => Err(RpcError::InvalidResponse(format!(
+                        "no response to {method} in batch"
+                    ))),
+                }
+            })
+            .collect())
+    }
+
+    pub async fn get_blockchain_info(&self) -> Result<BlockchainInfo, RpcError> {
+        self.call("getblockchaininfo", json!([])).await
+    }
+
+    pub async fn get_network_info(&self) -> Result<NetworkInfo, RpcError> {
+        self.call("getnetworkinfo", json!([])).await
+    }
+
+    pub async fn get_peer_info(&self) -> Result<Vec<PeerInfo>, RpcError> {
+        self.call("getpeerinfo", json!([])).await
+    }
+
+    pub async fn get_mempool_info(&self) -> Result<MempoolInfo, RpcError> {
+        self.call("getmempoolinfo", json!([])).await
+    }
+
+    pub async fn get_mining_info(&self) -> Result<MiningInfo, RpcError> {
+        self.call("getmininginfo", json!([])).await
+    }
+
+    // Estimated network hashes per second over the last 120 blocks
+    pub async fn get_network_hashps(&self) -> Result<f64, RpcError> {
+        self.call("getnetworkhashps", json!([])).await
+    }
+
+    // Seconds since the node started
+    pub async fn uptime(&self) -> Result<u64, RpcError> {
+        self.call("uptime", json!([])).await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, body: &Value) -> Result<T, RpcError> {
+        let mut attempt = 0;
+        loop {
+            match self.send_once(body).await {
+                Ok(response) => return Ok(response),
+                Err(e) if e.is_retryable() && attempt < self.retries => {
+                    attempt += 1;
+                    debug!("RPC request to {} failed, retrying: {}", self.url, e);
+                    tokio::time::sleep(Duration::from_millis(250) * attempt).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn send_once<T: DeserializeOwned>(&self, body: &Value) -> Result<T, RpcError> {
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Authorization", self.auth.header()?)
+            .timeout(self.timeout)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| RpcError::Transport(e.to_string()))?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return Err(RpcError::Auth(format!(
+                "credentials rejected (HTTP {status})"
+            )));
+        }
+
+        // Nodes answer RPC errors with an HTTP error status and a JSON body
+        let text = response
+            .text()
+            .await
+            .map_err(|e| RpcError::Transport(e.to_string()))?;
+        let parsed: Value = serde_json::from_str(&text).map_err(|_| {
+            let reason = format!("HTTP {status}: {}", text.trim());
+            if status.is_server_error() {
+                RpcError::Transport(reason)
+            } else {
+                RpcError::InvalidResponse(reason)
+            }
+        })?;
+        if let Some(error) = parsed.get("error").filter(|error| !error.is_null()) {
+            if let Ok(error) = serde_json::from_value::<RpcErrorObject>(error.clone()) {
+                if error.code == RPC_IN_WARMUP {
+                    warn!("Node at {} is still warming up", self.url);
+                    return Err(RpcError::Rpc {
+                        code: error.code,
+                        message: error.message,
+                    });
+                }
+            }
+        }
+        serde_json::from_value(parsed).map_err(|e| RpcError::InvalidResponse(e.to_string()))
+    }
+}
+
+fn parse_result<T: DeserializeOwned>(method: &str, response: RpcResponse) -> Result<T, RpcError> {
+    if let Some(error) = response.error {
+        debug!("{} failed with RPC error {}", method, error.code);
+        return Err(RpcError::Rpc {
+            code: error.code,
+            message: error.message,
+        });
+    }
+    serde_json::from_value(response.result)
+        .map_err(|e| RpcError::InvalidResponse(format!("{method}: {e}")))
+}
+
+// Local HTTP JSON-RPC server standing in for a node in tests
+#[cfg(test)]
+pub(crate) mod mock_server {
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Minimal HTTP JSON-RPC server; `handler` maps headers and body to a status and body
+    pub async fn serve(
+        handler: impl Fn(&str, Value) -> Option<(u16, String)> + Send + Sync + 'static,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handler = Arc::new(handler);
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    let (headers, body) = loop {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request).to_string();
+                        if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+                            let length = headers
+                                .lines()
+                                .find_map(|line| {
+                                    line.to_lowercase()
+                                        .strip_prefix("content-length:")
+                                        .map(|v| v.trim().parse::<usize>().unwrap())
+                                })
+                                .unwrap_or(0);
+                            if body.len() >= length {
+                                break (headers.to_string(), body.to_string());
+                            }
+                        }
+                    };
+                    // None drops the connection without an answer
+                    let Some((status, body)) =
+                        handler(&headers, serde_json::from_str(&body).unwrap())
+                    else {
+                        return;
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        url
+    }
+
+    pub fn reply(request: &Value, result: Value) -> String {
+        json!({ "result": result, "error": null, "id": request["id"] }).to_string()
+    }
+
+    pub fn error(request: &Value, code: i64, message: &str) -> Value {
+        let error = json!({ "code": code, "message": message });
+        json!({ "result": null, "error": error, "id": request["id"] })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock_server::{error, reply, serve};
+    use super::*;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_typed_calls_with_password_and_cookie_auth() {
+        let url = serve(|headers, request| {
+            let authorized = headers.contains(&format!(
+                "Basic {}",
+                general_purpose::STANDARD.encode("melanin:secret")
+            )) || headers.contains(&format!(
+                "Basic {}",
+                general_purpose::STANDARD.encode("__cookie__:abc123")
+            ));
+            if !authorized {
+                return Some((401, String::new()));
+            }
+            let result = match request["method"].as_str().unwrap() {
+                "getblockchaininfo" => json!({
+                    "chain": "main", "blocks": 850000, "headers": 850010,
+                    "bestblockhash": "00ab", "difficulty": 1.5,
+                    "verificationprogress": 0.9995, "initialblockdownload": false,
+                    "pruned": true, "size_on_disk": 5000
+                }),
+                "getpeerinfo" => json!([{ "id": 1, "addr": "1.2.3.4:8333", "pingtime": 0.05 }]),
+                "uptime" => json!(3600),
+                _ => return Some((500, error(&request, -32601, "Method not found").to_string())),
+            };
+            Some((200, reply(&request, result)))
+        })
+        .await;
+
+        let client = RpcClient::new(
+            &url,
+            RpcAuth::UserPass {
+                user: "melanin".to_string(),
+                password: "secret".to_string(),
+            },
+        );
+        let info = client.get_blockchain_info().await.unwrap();
+        assert_eq!(info.blocks, 850000);
+        assert_eq!(info.verification_progress, 0.9995);
+        assert!(info.pruned);
+        assert_eq!(
+            client.get_peer_info().await.unwrap()[0].ping_time,
+            Some(0.05)
+        );
+
+        // Method errors carry the node's code and message
+        let err = client.get_mempool_info().await.unwrap_err();
+        assert_eq!(err.code(), Some(-32601));
+        let err = err.to_string();
+        assert!(
+            err.contains("-32601") && err.contains("Method not found"),
+            "{err}"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let cookie = dir.path().join(".cookie");
+        std::fs::write(&cookie, "__cookie__:abc123\n").unwrap();
+        let client = RpcClient::new(&url, RpcAuth::CookieFile(cookie));
+        assert_eq!(client.uptime().await.unwrap(), 3600);
+
+        let client = RpcClient::new(
+            &url,
+            RpcAuth::UserPass {
+                user: "melanin".to_string(),
+                password: "wrong".to_string(),
+            },
+        );
+        let err = client.uptime().await.unwrap_err();
+        assert!(matches!(err, RpcError::Auth(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_batch_matches_responses_by_id() {
+        let url = serve(|_, request| {
+            let mut responses: Vec<Value> = request
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|call| match call["method"].as_str().unwrap() {
+                    "uptime" => json!({ "result": 42, "error": null, "id": call["id"] }),
+                    _ => error(call, -1, "boom"),
+                })
+                .collect();
+            responses.reverse();
+            Some((200, Value::Array(responses).to_string()))
+        })
+        .await;
+
+        let client = RpcClient::new(
+            &url,
+            RpcAuth::UserPass {
+                user: "u".to_string(),
+                password: "p".to_string(),
+            },
+        );
+        let results = client
+            .batch(&[("uptime", json!([])), ("getnetworkinfo", json!([]))])
+            .await
+            .unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), &json!(42));
+        assert!(results[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("boom"));
+    }
+
+    #[tokio::test]
+    async fn test_retries_dropped_connections_and_warmup() {
+        let attempts = Arc::new(AtomicU64::new(0));
+        let url = serve({
+            let attempts = attempts.clone();
+            move |_, request| match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => None,
+                1 => Some((
+                    503,
+                    error(&request, -28, "Loading block index...").to_string(),
+                )),
+                _ => Some((200, reply(&request, json!(7)))),
+            }
+        })
+        .await;
+        let auth = RpcAuth::UserPass {
+            user: "u".to_string(),
+            password: "p".to_string(),
+        };
+
+        let client = RpcClient::new(&url, auth.clone()).with_retries(2);
+        assert_eq!(client.uptime().await.unwrap(), 7);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Without retries the warmup error reaches the caller, told apart from other errors
+        attempts.store(1, Ordering::SeqCst);
+        let client = RpcClient::new(&url, auth).with_retries(0);
+        let err = client.uptime().await.unwrap_err();
+        assert!(err.is_warming_up());
+        assert!(err.to_string().contains("warming up"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let client = RpcClient::new(
+            &url,
+            RpcAuth::UserPass {
+                user: "u".to_string(),
+                password: "p".to_string(),
+            },
+        )
+        .with_timeout(Duration::from_millis(200))
+        .with_retries(0);
+
+        // The connection is accepted by the backlog but never answered
+        let started = std::time::Instant::now();
+        assert!(matches!(client.uptime().await, Err(RpcError::Transport(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(listener);
+    }
+}
@@ -1,8 +1,23 @@
+use crate::algorithms;
+use crate::block_builder::{address_script, BlockCandidate};
+use crate::rpc::{RpcAuth, RpcClient};
+use crate::AppError;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use reqwest::Client;
-use base64::{Engine as _, engine::general_purpose};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, Mutex};
+use tracing::{debug, error, info, warn};
+
+// Template refresh interval for nodes that do not offer longpoll
+const TEMPLATE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+// The node holds a longpoll open until its template changes
+const LONGPOLL_TIMEOUT: Duration = Duration::from_secs(600);
+const TEMPLATE_RETRY_DELAY: Duration = Duration::from_secs(5);
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+const NTIME_ROLL_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SoloMiningConfig {
@@ -16,17 +31,187 @@ pub struct SoloMiningConfig {
     pub cryptocurrency: String, // "bitcoin" or "whive"
 }
 
+impl SoloMiningConfig {
+    // Client for the node of the configured cryptocurrency
+    fn rpc_client(&self) -> Result<RpcClient, AppError> {
+        let (url, user, password) = match self.cryptocurrency.as_str() {
+            "bitcoin" => (
+                &self.bitcoin_rpc_url,
+                &self.bitcoin_rpc_user,
+                &self.bitcoin_rpc_password,
+            ),
+            "whive" => (
+                &self.whive_rpc_url,
+                &self.whive_rpc_user,
+                &self.whive_rpc_password,
+            ),
+            other => return Err(invalid_cryptocurrency(other)),
+        };
+        Ok(RpcClient::new(
+            url,
+            RpcAuth::UserPass {
+                user: user.clone(),
+                password: password.clone(),
+            },
+        ))
+    }
+
+    // Proof-of-work algorithm of the configured cryptocurrency
+    fn algorithm(&self) -> Result<&'static str, AppError> {
+        match self.cryptocurrency.as_str() {
+            "bitcoin" => Ok("sha256d"),
+            "whive" => Ok("yespower"),
+            other => Err(invalid_cryptocurrency(other)),
+        }
+    }
+}
+
+fn invalid_cryptocurrency(name: &str) -> AppError {
+    AppError::Validation(format!("Invalid cryptocurrency specified: {name}"))
+}
+
+// Parameters of getblocktemplate (BIP22, BIP23)
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateRequest {
+    pub capabilities: Vec<String>,
+    pub rules: Vec<String>,
+    // Longpoll id of the current template; the node answers once it has a newer one
+    #[serde(rename = "longpollid", skip_serializing_if = "Option::is_none")]
+    pub longpoll_id: Option<String>,
+}
+
+impl Default for TemplateRequest {
+    fn default() -> Self {
+        Self {
+            capabilities: vec!["coinbasetxn".to_string(), "longpoll".to_string()],
+            rules: vec!["segwit".to_string()],
+            longpoll_id: None,
+        }
+    }
+}
+
+// A transaction to include after the coinbase, in template order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateTransaction {
+    // Hex of the serialized transaction, including witness data
+    pub data: String,
+    pub txid: String,
+    // Witness txid; the same as txid for a transaction without witness data
+    pub hash: String,
+    // 1-based positions of earlier template transactions this one spends
+    #[serde(default)]
+    pub depends: Vec<usize>,
+    // Satoshis; the node leaves out values it does not know
+    pub fee: Option<u64>,
+    pub sigops: Option<u64>,
+    pub weight: Option<u64>,
+}
+
+impl TemplateTransaction {
+    pub fn bytes(&self) -> Result<Vec<u8>, AppError> {
+        hex::decode(&self.data).map_err(|e| {
+            AppError::Validation(format!("Invalid data for transaction {}: {e}", self.txid))
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockTemplate {
     pub version: u32,
+    // Soft forks the block must follow, e.g. `csv` or `!segwit`
+    #[serde(default)]
+    pub rules: Vec<String>,
+    #[serde(rename(deserialize = "previousblockhash"))]
     pub previous_block_hash: String,
-    pub transactions: Vec<String>,
+    pub transactions: Vec<TemplateTransaction>,
+    // Hex data the coinbase script must carry, keyed by purpose
+    #[serde(rename(deserialize = "coinbaseaux"), default)]
+    pub coinbase_aux: HashMap<String, String>,
+    // Subsidy plus the fees of all template transactions
+    #[serde(rename(deserialize = "coinbasevalue"))]
     pub coinbase_value: u64,
+    // Passed back to getblocktemplate to wait for a newer template
+    #[serde(rename(deserialize = "longpollid"))]
+    pub longpoll_id: Option<String>,
     pub target: String,
+    #[serde(rename(deserialize = "mintime"))]
     pub min_time: u64,
+    // Parts of the block the miner may change, such as `time` or `transactions`
+    #[serde(default)]
+    pub mutable: Vec<String>,
+    #[serde(rename(deserialize = "noncerange"), default)]
+    pub nonce_range: String,
+    #[serde(rename(deserialize = "sigoplimit"))]
+    pub sigop_limit: Option<u64>,
+    #[serde(rename(deserialize = "sizelimit"))]
+    pub size_limit: Option<u64>,
+    #[serde(rename(deserialize = "weightlimit"))]
+    pub weight_limit: Option<u64>,
+    #[serde(rename(deserialize = "curtime"))]
     pub cur_time: u64,
     pub bits: String,
     pub height: u64,
+    // Script of the coinbase output committing to the witness data (BIP141)
+    pub default_witness_commitment: Option<String>,
+}
+
+impl BlockTemplate {
+    // Bytes to push in the coinbase script, from the coinbaseaux values
+    pub fn coinbase_flags(&self) -> Result<Vec<u8>, AppError> {
+        let mut keys: Vec<&String> = self.coinbase_aux.keys().collect();
+        keys.sort();
+        let mut flags = Vec::new();
+        for key in keys {
+            flags.extend(hex::decode(&self.coinbase_aux[key]).map_err(|e| {
+                AppError::Validation(format!("Invalid coinbaseaux value for {key}: {e}"))
+            })?);
+        }
+        Ok(flags)
+    }
+
+    pub fn total_fees(&self) -> u64 {
+        self.transactions.iter().filter_map(|tx| tx.fee).sum()
+    }
+
+    // Weight of the template transactions, when the node reported all of them
+    pub fn transactions_weight(&self) -> Option<u64> {
+        self.transactions.iter().map(|tx| tx.weight).sum()
+    }
+
+    pub fn has_witness_data(&self) -> bool {
+        self.transactions.iter().any(|tx| tx.hash != tx.txid)
+    }
+
+    // Checks the parts a block is assembled from before any work is spent on it
+    pub fn validate(&self) -> Result<(), AppError> {
+        for (index, tx) in self.transactions.iter().enumerate() {
+            tx.bytes()?;
+            if let Some(depend) = tx
+                .depends
+                .iter()
+                .find(|depend| **depend == 0 || **depend > index)
+            {
+                return Err(AppError::Validation(format!(
+                    "Transaction {} depends on transaction {depend}, which does not precede it",
+                    tx.txid
+                )));
+            }
+        }
+        self.coinbase_flags()?;
+        if let Some(commitment) = &self.default_witness_commitment {
+            hex::decode(commitment).map_err(|e| {
+                AppError::Validation(format!("Invalid default_witness_commitment: {e}"))
+            })?;
+        }
+        if let (Some(weight), Some(limit)) = (self.transactions_weight(), self.weight_limit) {
+            if weight > limit {
+                return Err(AppError::Validation(format!(
+                    "Template transactions weigh {weight}, more than the limit of {limit}"
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,15 +220,22 @@ pub struct SoloMiningStats {
     pub current_block_height: u64,
     pub difficulty: f64,
     pub network_hashrate: String,
+    // Hashrate of this machine's solo mining threads
+    pub local_hashrate: String,
     pub blocks_found: u64,
     pub mining_address: String,
 }
 
+#[derive(Clone)]
 pub struct SoloMiner {
     config: Arc<Mutex<Option<SoloMiningConfig>>>,
     stats: Arc<Mutex<SoloMiningStats>>,
-    client: Client,
     is_mining: Arc<Mutex<bool>>,
+    threads: usize,
+    // Bumped on every new template, and on stop, to retire the threads hashing the old one
+    epoch: Arc<AtomicU64>,
+    hashes: Arc<AtomicU64>,
+    stop_sender: Arc<watch::Sender<bool>>,
 }
 
 impl SoloMiner {
@@ -54,225 +246,660 @@ impl SoloMiner {
                 is_active: false,
                 current_block_height: 0,
                 difficulty: 0.0,
-                network_hashrate: "0 H/s".to_string(),
+                network_hashrate: format_hashrate(0.0),
+                local_hashrate: format_hashrate(0.0),
                 blocks_found: 0,
                 mining_address: String::new(),
             })),
-            client: Client::new(),
             is_mining: Arc::new(Mutex::new(false)),
+            threads: num_cpus::get(),
+            epoch: Arc::new(AtomicU64::new(0)),
+            hashes: Arc::new(AtomicU64::new(0)),
+            stop_sender: Arc::new(watch::channel(false).0),
         }
     }
 
-    pub async fn configure(&self, config: SoloMiningConfig) -> Result<(), String> {
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub async fn configure(&self, config: SoloMiningConfig) -> Result<(), AppError> {
+        // Blocks found must be spendable, so the address has to turn into a script
+        address_script(&config.mining_address)?;
+
         // Validate RPC connection
-        self.test_rpc_connection(&config).await?;
-        
+        config.rpc_client()?.get_blockchain_info().await?;
+
         let mut conf = self.config.lock().await;
         *conf = Some(config.clone());
 
         let mut stats = self.stats.lock().await;
         stats.mining_address = config.mining_address;
-        
-        Ok(())
-    }
-
-    async fn test_rpc_connection(&self, config: &SoloMiningConfig) -> Result<(), String> {
-        let (rpc_url, rpc_user, rpc_password) = match config.cryptocurrency.as_str() {
-            "bitcoin" => (&config.bitcoin_rpc_url, &config.bitcoin_rpc_user, &config.bitcoin_rpc_password),
-            "whive" => (&config.whive_rpc_url, &config.whive_rpc_user, &config.whive_rpc_password),
-            _ => return Err("Invalid cryptocurrency specified".to_string()),
-        };
-
-        let auth = general_purpose::STANDARD.encode(format!("{}:{}", rpc_user, rpc_password));
-        
-        let rpc_request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "getblockchaininfo",
-            "params": []
-        });
-
-        let response = self.client
-            .post(rpc_url)
-            .header("Authorization", format!("Basic {}", auth))
-            .header("Content-Type", "application/json")
-            .json(&rpc_request)
-            .send()
-            .await
-            .map_err(|e| format!("RPC connection failed: {}", e))?;
-
-        if !response.status().is_success() {
-            return Err(format!("RPC error: {}", response.status()));
-        }
 
         Ok(())
     }
 
-    pub async fn get_block_template(&self) -> Result<BlockTemplate, String> {
-        let config = self.config.lock().await;
-        let config = config.as_ref().ok_or("Solo mining not configured")?;
-
-        let (rpc_url, rpc_user, rpc_password) = match config.cryptocurrency.as_str() {
-            "bitcoin" => (&config.bitcoin_rpc_url, &config.bitcoin_rpc_user, &config.bitcoin_rpc_password),
-            "whive" => (&config.whive_rpc_url, &config.whive_rpc_user, &config.whive_rpc_password),
-            _ => return Err("Invalid cryptocurrency specified".to_string()),
-        };
-
-        let auth = general_purpose::STANDARD.encode(format!("{}:{}", rpc_user, rpc_password));
-        
-        let rpc_request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "getblocktemplate",
-            "params": [{
-                "rules": ["segwit"]
-            }]
-        });
-
-        let response = self.client
-            .post(rpc_url)
-            .header("Authorization", format!("Basic {}", auth))
-            .header("Content-Type", "application/json")
-            .json(&rpc_request)
-            .send()
+    async fn rpc_client(&self) -> Result<RpcClient, AppError> {
+        self.config
+            .lock()
             .await
-            .map_err(|e| format!("Failed to get block template: {}", e))?;
-
-        let json_response: serde_json::Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
-
-        if let Some(error) = json_response.get("error") {
-            return Err(format!("RPC error: {}", error));
-        }
-
-        let result = json_response.get("result")
-            .ok_or("Missing result in response")?;
-
-        // Parse block template (simplified)
-        let block_template = BlockTemplate {
-            version: result.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-            previous_block_hash: result.get("previousblockhash")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string(),
-            transactions: vec![], // Simplified
-            coinbase_value: result.get("coinbasevalue").and_then(|v| v.as_u64()).unwrap_or(0),
-            target: result.get("target").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-            min_time: result.get("mintime").and_then(|v| v.as_u64()).unwrap_or(0),
-            cur_time: result.get("curtime").and_then(|v| v.as_u64()).unwrap_or(0),
-            bits: result.get("bits").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-            height: result.get("height").and_then(|v| v.as_u64()).unwrap_or(0),
-        };
+            .as_ref()
+            .ok_or_else(|| AppError::Mining("Solo mining not configured".to_string()))?
+            .rpc_client()
+    }
 
-        Ok(block_template)
+    pub async fn get_block_template(&self) -> Result<BlockTemplate, AppError> {
+        fetch_template(&self.rpc_client().await?, None).await
     }
 
-    pub async fn submit_block(&self, block_hex: String) -> Result<bool, String> {
+    // Block from `template` paying the configured mining address
+    pub async fn build_candidate(
+        &self,
+        template: &BlockTemplate,
+        extranonce: u64,
+    ) -> Result<BlockCandidate, AppError> {
         let config = self.config.lock().await;
-        let config = config.as_ref().ok_or("Solo mining not configured")?;
-
-        let (rpc_url, rpc_user, rpc_password) = match config.cryptocurrency.as_str() {
-            "bitcoin" => (&config.bitcoin_rpc_url, &config.bitcoin_rpc_user, &config.bitcoin_rpc_password),
-            "whive" => (&config.whive_rpc_url, &config.whive_rpc_user, &config.whive_rpc_password),
-            _ => return Err("Invalid cryptocurrency specified".to_string()),
-        };
-
-        let auth = general_purpose::STANDARD.encode(format!("{}:{}", rpc_user, rpc_password));
-        
-        let rpc_request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "submitblock",
-            "params": [block_hex]
-        });
-
-        let response = self.client
-            .post(rpc_url)
-            .header("Authorization", format!("Basic {}", auth))
-            .header("Content-Type", "application/json")
-            .json(&rpc_request)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to submit block: {}", e))?;
+        let config = config
+            .as_ref()
+            .ok_or_else(|| AppError::Mining("Solo mining not configured".to_string()))?;
+        BlockCandidate::new(
+            template,
+            &address_script(&config.mining_address)?,
+            extranonce,
+        )
+    }
 
-        let json_response: serde_json::Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+    pub async fn submit_block(&self, block_hex: String) -> Result<bool, AppError> {
+        let client = self.rpc_client().await?;
+        // submitblock answers null on success and a reason otherwise
+        let rejection: Option<String> = client.call("submitblock", json!([block_hex])).await?;
 
-        if let Some(error) = json_response.get("error") {
-            if error.is_null() {
-                // Success - increment blocks found
+        match rejection.as_deref() {
+            None => {
                 let mut stats = self.stats.lock().await;
                 stats.blocks_found += 1;
-                return Ok(true);
-            } else {
-                return Err(format!("Block submission error: {}", error));
+                Ok(true)
             }
+            // Accepted, but not yet known to extend the best chain
+            Some("inconclusive") => Ok(false),
+            Some(reason) => Err(AppError::Mining(format!(
+                "Node rejected the block: {reason}"
+            ))),
         }
-
-        Ok(false)
     }
 
-    pub async fn start_mining(&self) -> Result<(), String> {
+    pub async fn start_mining(&self) -> Result<(), AppError> {
+        let (client, longpoll_client, payout, algorithm) = {
+            let config = self.config.lock().await;
+            let config = config
+                .as_ref()
+                .ok_or_else(|| AppError::Mining("Solo mining not configured".to_string()))?;
+            (
+                config.rpc_client()?,
+                config
+                    .rpc_client()?
+                    .with_timeout(LONGPOLL_TIMEOUT)
+                    .with_retries(0),
+                address_script(&config.mining_address)?,
+                config.algorithm()?,
+            )
+        };
+
         let mut is_mining = self.is_mining.lock().await;
         if *is_mining {
-            return Err("Mining already active".to_string());
+            return Err(AppError::Mining("Mining already active".to_string()));
         }
-        
+
         *is_mining = true;
         let mut stats = self.stats.lock().await;
         stats.is_active = true;
-        
+
+        // Subscribe before spawning so a stop right after this call is not missed
+        self.stop_sender.send_replace(false);
+        let stop = self.stop_sender.subscribe();
+        let miner = self.clone();
+        tokio::spawn(async move {
+            miner
+                .work_loop(client, longpoll_client, payout, algorithm, stop)
+                .await
+        });
+
         Ok(())
     }
 
-    pub async fn stop_mining(&self) -> Result<(), String> {
+    pub async fn stop_mining(&self) -> Result<(), AppError> {
         let mut is_mining = self.is_mining.lock().await;
         *is_mining = false;
-        
+        self.stop_sender.send_replace(true);
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+
         let mut stats = self.stats.lock().await;
         stats.is_active = false;
-        
+        stats.local_hashrate = format_hashrate(0.0);
+
         Ok(())
     }
 
+    // Keeps the hashing threads on the newest template until stopped, submitting the
+    // blocks they find
+    async fn work_loop(
+        self,
+        client: RpcClient,
+        longpoll_client: RpcClient,
+        payout: Vec<u8>,
+        algorithm: &'static str,
+        mut stop: watch::Receiver<bool>,
+    ) {
+        let payout = Arc::new(payout);
+        let (block_sender, mut blocks) = mpsc::unbounded_channel();
+        let mut stats_timer = tokio::time::interval(STATS_INTERVAL);
+        let mut hashrate_sample = (Instant::now(), self.hashes.load(Ordering::Relaxed));
+        let mut tip: Option<String> = None;
+        let mut pending_template = Box::pin(next_template(
+            &client,
+            &longpoll_client,
+            None,
+            Duration::ZERO,
+        ));
+
+        info!("Solo mining started with {} threads", self.threads);
+        loop {
+            tokio::select! {
+                _ = stop.changed() => break,
+                _ = stats_timer.tick() => {
+                    let (sampled_at, sampled_hashes) = hashrate_sample;
+                    let hashes = self.hashes.load(Ordering::Relaxed);
+                    let hashrate = (hashes - sampled_hashes) as f64
+                        / sampled_at.elapsed().as_secs_f64().max(f64::EPSILON);
+                    hashrate_sample = (Instant::now(), hashes);
+                    self.stats.lock().await.local_hashrate = format_hashrate(hashrate);
+                    self.refresh_network_stats(&client).await;
+                }
+                Some(block) = blocks.recv() => {
+                    self.submit_found_block(&block).await;
+                    // The tip has most likely moved on to this block
+                    pending_template = Box::pin(next_template(
+                        &client,
+                        &longpoll_client,
+                        None,
+                        Duration::ZERO,
+                    ));
+                }
+                result = &mut pending_template => {
+                    let (longpoll_id, delay) = match result {
+                        Ok(template) => {
+                            if tip.as_ref() != Some(&template.previous_block_hash) {
+                                info!("Solo mining on a new tip at height {}", template.height);
+                                tip = Some(template.previous_block_hash.clone());
+                            }
+                            let longpoll_id = template.longpoll_id.clone();
+                            self.start_workers(
+                                Arc::new(template),
+                                payout.clone(),
+                                algorithm,
+                                &block_sender,
+                            );
+                            self.refresh_network_stats(&client).await;
+                            (longpoll_id, TEMPLATE_REFRESH_INTERVAL)
+                        }
+                        Err(e) => {
+                            warn!("Failed to fetch a block template: {}", e);
+                            (None, TEMPLATE_RETRY_DELAY)
+                        }
+                    };
+                    pending_template = Box::pin(next_template(
+                        &client,
+                        &longpoll_client,
+                        longpoll_id,
+                        delay,
+                    ));
+                }
+            }
+        }
+
+        // stop_mining has already retired the hashing threads; bumping the epoch here
+        // could retire those of a loop started since
+        info!("Solo mining stopped");
+    }
+
+    // Replaces the work of every hashing thread with the new template
+    fn start_workers(
+        &self,
+        template: Arc<BlockTemplate>,
+        payout: Arc<Vec<u8>>,
+        algorithm: &'static str,
+        blocks: &mpsc::UnboundedSender<BlockCandidate>,
+    ) {
+        let job_epoch = self.epoch.fetch_add(1, Ordering::SeqCst) + 1;
+        debug!("Hashing block template at height {}", template.height);
+
+        for thread_index in 0..self.threads {
+            let template = template.clone();
+            let payout = payout.clone();
+            let thread_count = self.threads;
+            let epoch = self.epoch.clone();
+            let hashes = self.hashes.clone();
+            let blocks = blocks.clone();
+            std::thread::spawn(move || {
+                search(
+                    &template,
+                    &payout,
+                    algorithm,
+                    thread_index,
+                    thread_count,
+                    job_epoch,
+                    &epoch,
+                    &hashes,
+                    &blocks,
+                )
+            });
+        }
+    }
+
+    async fn submit_found_block(&self, block: &BlockCandidate) {
+        info!("Found a block at height {}, submitting it", block.height);
+        match self.submit_block(block.to_hex()).await {
+            Ok(true) => info!("Block at height {} accepted", block.height),
+            Ok(false) => warn!(
+                "Block at height {} accepted but not yet on the best chain",
+                block.height
+            ),
+            Err(e) => error!("Failed to submit block at height {}: {}", block.height, e),
+        }
+    }
+
+    // Height, difficulty and network hashrate as the node sees them
+    async fn refresh_network_stats(&self, client: &RpcClient) {
+        let info = match client.get_mining_info().await {
+            Ok(info) => info,
+            Err(e) => {
+                debug!("getmininginfo failed: {}", e);
+                return;
+            }
+        };
+        // Older nodes leave networkhashps out of getmininginfo
+        let network_hashps = match info.network_hashps {
+            Some(hashps) => Some(hashps),
+            None => client.get_network_hashps().await.ok(),
+        };
+
+        let mut stats = self.stats.lock().await;
+        stats.current_block_height = info.blocks;
+        stats.difficulty = info.difficulty;
+        if let Some(hashps) = network_hashps {
+            stats.network_hashrate = format_hashrate(hashps);
+        }
+    }
+
     pub async fn get_stats(&self) -> SoloMiningStats {
         self.stats.lock().await.clone()
     }
 }
 
+async fn fetch_template(
+    client: &RpcClient,
+    longpoll_id: Option<String>,
+) -> Result<BlockTemplate, AppError> {
+    let request = TemplateRequest {
+        longpoll_id,
+        ..Default::default()
+    };
+    let template: BlockTemplate = client.call("getblocktemplate", json!([request])).await?;
+    template.validate()?;
+    Ok(template)
+}
+
+// Waits on the node's longpoll when the last template had one, otherwise for `delay`
+async fn next_template(
+    client: &RpcClient,
+    longpoll_client: &RpcClient,
+    longpoll_id: Option<String>,
+    delay: Duration,
+) -> Result<BlockTemplate, AppError> {
+    match longpoll_id {
+        Some(longpoll_id) => fetch_template(longpoll_client, Some(longpoll_id)).await,
+        None => {
+            tokio::time::sleep(delay).await;
+            fetch_template(client, None).await
+        }
+    }
+}
+
+// Searches the nonce space of one thread's extranonces in turn, rolling ntime as it
+// goes, until a block is found or the template changes
+#[allow(clippy::too_many_arguments)]
+fn search(
+    template: &BlockTemplate,
+    payout: &[u8],
+    algorithm: &str,
+    thread_index: usize,
+    thread_count: usize,
+    job_epoch: u64,
+    epoch: &AtomicU64,
+    hashes: &AtomicU64,
+    blocks: &mpsc::UnboundedSender<BlockCandidate>,
+) {
+    let mut hasher = match algorithms::create(algorithm) {
+        Ok(hasher) => hasher,
+        Err(e) => {
+            error!("Solo mining thread {} cannot hash: {}", thread_index, e);
+            return;
+        }
+    };
+    let roll_time = template.mutable.is_empty() || template.mutable.iter().any(|m| m == "time");
+
+    // Threads take every `thread_count`th extranonce, so their coinbases never collide
+    let mut extranonce = thread_index as u64;
+    loop {
+        let mut candidate = match BlockCandidate::new(template, payout, extranonce) {
+            Ok(candidate) => candidate,
+            Err(e) => {
+                error!("Cannot build a block from the template: {}", e);
+                return;
+            }
+        };
+
+        let mut rolled_at = Instant::now();
+        let mut nonce = 0u64;
+        while nonce <= u32::MAX as u64 {
+            if epoch.load(Ordering::Relaxed) != job_epoch {
+                return;
+            }
+            if roll_time && rolled_at.elapsed() >= NTIME_ROLL_INTERVAL {
+                let now = chrono::Utc::now().timestamp() as u64;
+                candidate.set_time(now.max(template.min_time) as u32);
+                rolled_at = Instant::now();
+            }
+
+            let count = (u32::MAX as u64 - nonce + 1).min(hasher.nonce_batch() as u64) as u32;
+            let mut found = None;
+            hasher.scan(
+                candidate.header(),
+                nonce as u32,
+                count,
+                candidate.target(),
+                &mut |nonce, _| {
+                    found.get_or_insert(nonce);
+                },
+            );
+            hashes.fetch_add(count as u64, Ordering::Relaxed);
+
+            if let Some(nonce) = found {
+                candidate.set_nonce(nonce);
+                let _ = blocks.send(candidate);
+                return;
+            }
+            nonce += count as u64;
+        }
+        extranonce += thread_count as u64;
+    }
+}
+
+fn format_hashrate(hashrate: f64) -> String {
+    let units = [
+        "H/s", "kH/s", "MH/s", "GH/s", "TH/s", "PH/s", "EH/s", "ZH/s",
+    ];
+    let mut value = hashrate.max(0.0);
+    let mut unit = 0;
+    while value >= 1000.0 && unit < units.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, units[unit])
+}
+
 // Tauri commands for solo mining
 #[tauri::command]
 pub async fn configure_solo_mining(
     solo_miner: tauri::State<'_, SoloMiner>,
     config: SoloMiningConfig,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     solo_miner.configure(config).await
 }
 
 #[tauri::command]
-pub async fn start_solo_mining(
-    solo_miner: tauri::State<'_, SoloMiner>,
-) -> Result<(), String> {
+pub async fn start_solo_mining(solo_miner: tauri::State<'_, SoloMiner>) -> Result<(), AppError> {
     solo_miner.start_mining().await
 }
 
 #[tauri::command]
-pub async fn stop_solo_mining(
-    solo_miner: tauri::State<'_, SoloMiner>,
-) -> Result<(), String> {
+pub async fn stop_solo_mining(solo_miner: tauri::State<'_, SoloMiner>) -> Result<(), AppError> {
     solo_miner.stop_mining().await
 }
 
 #[tauri::command]
 pub async fn get_solo_mining_stats(
     solo_miner: tauri::State<'_, SoloMiner>,
-) -> Result<SoloMiningStats, String> {
+) -> Result<SoloMiningStats, AppError> {
     Ok(solo_miner.get_stats().await)
 }
 
 #[tauri::command]
 pub async fn get_solo_block_template(
     solo_miner: tauri::State<'_, SoloMiner>,
-) -> Result<BlockTemplate, String> {
+) -> Result<BlockTemplate, AppError> {
     solo_miner.get_block_template().await
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use serde_json::{json, Value};
+
+    // Template as returned by a regtest bitcoind at height 101, with a legacy transaction
+    // and a segwit one spending it
+    pub fn regtest_template() -> Value {
+        json!({
+            "capabilities": ["proposal"],
+            "version": 536870912,
+            "rules": ["csv", "!segwit", "taproot"],
+            "vbavailable": {},
+            "vbrequired": 0,
+            "previousblockhash":
+                "3f6a2d4a1c0b8e1f5d7a9c2b4e6f8a0c1e3b5d7f9a2c4e6b8d0f1a3c5e7b9d2f",
+            "transactions": [
+                {
+                    "data": "0200000001111111111111111111111111111111111111111111111111111111\
+                             11111111110000000000fdffffff01f0ca052a01000000160014000000000000\
+                             000000000000000000000000000000000000",
+                    "txid": "6b6f29e50cbfcb20973eed92547c43a5a05dc09b92790ec66cc0649f344e9d05",
+                    "hash": "6b6f29e50cbfcb20973eed92547c43a5a05dc09b92790ec66cc0649f344e9d05",
+                    "depends": [],
+                    "fee": 10000,
+                    "sigops": 4,
+                    "weight": 328
+                },
+                {
+                    "data": "02000000000101059d4e349f64c06cc60e79929bc05da0a5437c5492ed3e9720\
+                             cbbf0ce5296f6b0000000000fdffffff01e0a3052a0100000016001407070707\
+                             0707070707070707070707070707070702473030303030303030303030303030\
+                             3030303030303030303030303030303030303030303030303030303030303030\
+                             3030303030303030303030303030303030303030303030303021020303030303\
+                             03030303030303030303030303030303030303030303030303030300000000",
+                    "txid": "eb3e7c89fa4d495169e2af086e8d4e2c3e22fd4c7e74b1dab4d2f2509d885291",
+                    "hash": "cb7e6f5c5b994db806baeebf435bf3fab4e596a1e9c4ed0ec3525eb5b953572a",
+                    "depends": [1],
+                    "fee": 10000,
+                    "sigops": 1,
+                    "weight": 437
+                }
+            ],
+            "coinbaseaux": { "flags": "" },
+            "coinbasevalue": 5000020000u64,
+            "longpollid": "3f6a2d4a1c0b8e1f5d7a9c2b4e6f8a0c1e3b5d7f9a2c4e6b8d0f1a3c5e7b9d2f2",
+            "target": "7fffff0000000000000000000000000000000000000000000000000000000000",
+            "mintime": 1700000000,
+            "mutable": ["time", "transactions", "prevblock"],
+            "noncerange": "00000000ffffffff",
+            "sigoplimit": 80000,
+            "sizelimit": 4000000,
+            "weightlimit": 4000000,
+            "curtime": 1700000600,
+            "bits": "207fffff",
+            "height": 101,
+            "default_witness_commitment":
+                "6a24aa21a9ed0715b569809657a079a8ad3f60b89a474d897f85fdcc95c85fb40c232fe13d04"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::regtest_template;
+    use super::*;
+    use crate::rpc::mock_server::{error, reply, serve};
+    use serde_json::Value;
+
+    fn config(url: &str) -> SoloMiningConfig {
+        SoloMiningConfig {
+            bitcoin_rpc_url: url.to_string(),
+            bitcoin_rpc_user: "melanin".to_string(),
+            bitcoin_rpc_password: "secret".to_string(),
+            whive_rpc_url: String::new(),
+            whive_rpc_user: String::new(),
+            whive_rpc_password: String::new(),
+            mining_address: "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string(),
+            cryptocurrency: "bitcoin".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_template_and_submit_over_rpc() {
+        let url = serve(|_, request| {
+            let body = match request["method"].as_str().unwrap() {
+                "getblockchaininfo" => error(&request, -28, "Loading block index...").to_string(),
+                "getblocktemplate" => {
+                    assert_eq!(request["params"][0]["rules"][0], "segwit");
+                    reply(&request, regtest_template())
+                }
+                "submitblock" if request["params"][0] == "good" => reply(&request, Value::Null),
+                _ => reply(&request, json!("bad-txnmrklroot")),
+            };
+            Some((200, body))
+        })
+        .await;
+
+        let miner = SoloMiner::new();
+        assert!(matches!(
+            miner.get_block_template().await,
+            Err(AppError::Mining(_))
+        ));
+
+        // A node that is still loading is reported as such
+        match miner.configure(config(&url)).await {
+            Err(AppError::Rpc(e)) => assert!(e.is_warming_up()),
+            other => panic!("unexpected result {other:?}"),
+        }
+        *miner.config.lock().await = Some(config(&url));
+
+        let template = miner.get_block_template().await.unwrap();
+        assert_eq!(template.height, 101);
+        assert_eq!(template.transactions.len(), 2);
+        let candidate = miner.build_candidate(&template, 0).await.unwrap();
+        assert_eq!(candidate.height, 101);
+
+        assert!(miner.submit_block("good".to_string()).await.unwrap());
+        assert!(miner.submit_block("bad".to_string()).await.is_err());
+        assert_eq!(miner.get_stats().await.blocks_found, 1);
+    }
+
+    #[test]
+    fn test_parses_full_template() {
+        let template: BlockTemplate = serde_json::from_value(regtest_template()).unwrap();
+        template.validate().unwrap();
+
+        assert_eq!(
+            template.previous_block_hash,
+            "3f6a2d4a1c0b8e1f5d7a9c2b4e6f8a0c1e3b5d7f9a2c4e6b8d0f1a3c5e7b9d2f"
+        );
+        assert_eq!(template.coinbase_value, 5000020000);
+        assert_eq!(template.total_fees(), 20000);
+        assert_eq!(template.transactions_weight(), Some(765));
+        assert_eq!(template.weight_limit, Some(4000000));
+        assert_eq!(template.sigop_limit, Some(80000));
+        assert!(template.longpoll_id.is_some());
+        assert!(template.coinbase_flags().unwrap().is_empty());
+        assert!(template
+            .default_witness_commitment
+            .as_deref()
+            .unwrap()
+            .starts_with("6a24aa21a9ed"));
+        assert!(template.has_witness_data());
+
+        let spend = &template.transactions[1];
+        assert_eq!(spend.depends, vec![1]);
+        assert_eq!(spend.fee, Some(10000));
+        assert_eq!(spend.bytes().unwrap().len(), 191);
+
+        // A transaction may only depend on one listed before it
+        let mut bad = regtest_template();
+        bad["transactions"][0]["depends"] = json!([2]);
+        let template: BlockTemplate = serde_json::from_value(bad).unwrap();
+        assert!(matches!(template.validate(), Err(AppError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_work_loop_submits_found_block() {
+        let submitted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = submitted.clone();
+        let url = serve(move |_, request| {
+            let body = match request["method"].as_str().unwrap() {
+                // Longpolls are dropped, so the loop falls back to polling
+                "getblocktemplate" if !request["params"][0]["longpollid"].is_null() => return None,
+                "getblocktemplate" => reply(&request, regtest_template()),
+                "getmininginfo" => reply(
+                    &request,
+                    json!({ "blocks": 100, "difficulty": 4.6e-10, "chain": "regtest" }),
+                ),
+                "getnetworkhashps" => reply(&request, json!(2500000.0)),
+                "submitblock" => {
+                    let block = request["params"][0].as_str().unwrap().to_string();
+                    seen.lock().unwrap().push(block);
+                    reply(&request, Value::Null)
+                }
+                _ => return None,
+            };
+            Some((200, body))
+        })
+        .await;
+
+        let miner = SoloMiner::new().with_threads(2);
+        *miner.config.lock().await = Some(config(&url));
+        miner.start_mining().await.unwrap();
+        assert!(miner.start_mining().await.is_err());
+
+        // Half of all hashes meet the regtest target, so a block turns up at once
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while miner.get_stats().await.blocks_found == 0 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        miner.stop_mining().await.unwrap();
+
+        let stats = miner.get_stats().await;
+        assert!(!stats.is_active);
+        assert_eq!(stats.current_block_height, 100);
+        assert_eq!(stats.network_hashrate, "2.50 MH/s");
+
+        let block = hex::decode(&submitted.lock().unwrap()[0]).unwrap();
+        let template: BlockTemplate = serde_json::from_value(regtest_template()).unwrap();
+        let target: [u8; 32] = hex::decode(&template.target).unwrap().try_into().unwrap();
+        assert!(crate::native_miner::meets_target(
+            &crate::native_miner::sha256d(&block[..80]),
+            &target
+        ));
+        // Header, three transactions, and the template's two unchanged at the end
+        assert_eq!(block[80], 3);
+        assert!(hex::encode(&block).ends_with(&template.transactions[1].data));
+    }
+
+    #[test]
+    fn test_format_hashrate() {
+        assert_eq!(format_hashrate(0.0), "0.00 H/s");
+        assert_eq!(format_hashrate(999.0), "999.00 H/s");
+        assert_eq!(format_hashrate(2500000.0), "2.50 MH/s");
+        assert_eq!(format_hashrate(6.5e20), "650.00 EH/s");
+    }
+}
@@ -1,12 +1,24 @@
+use crate::stratum_v2::Sv2Client;
 use crate::AppError;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::Instant;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf,
+};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio::time::{timeout, Duration};
+use tokio_rustls::TlsConnector;
 use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +32,27 @@ pub struct StratumJob {
     pub nbits: String,
     pub ntime: String,
     pub clean_jobs: bool,
+    // Set for Stratum V2 standard-channel jobs, which carry a finished merkle root
+    // instead of coinbase parts and branches
+    #[serde(default)]
+    pub merkle_root: Option<String>,
+}
+
+// Session parameters from the mining.subscribe result, needed to build the coinbase:
+// coinbase1 + extranonce1 + extranonce2 + coinbase2
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StratumSubscription {
+    pub subscription_ids: Vec<(String, String)>,
+    pub extranonce1: String,
+    pub extranonce2_size: usize,
+}
+
+// Pool-requested redirect received via client.reconnect
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectRequest {
+    pub host: String,
+    pub port: u16,
+    pub wait: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,11 +61,24 @@ pub struct StratumStats {
     pub pool_url: String,
     pub worker_name: String,
     pub difficulty: f64,
+    pub subscription: Option<StratumSubscription>,
     pub accepted_shares: u64,
     pub rejected_shares: u64,
     pub last_share_time: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_reject_code: Option<i64>,
+    pub last_reject_reason: Option<String>,
     pub connection_time: chrono::DateTime<chrono::Utc>,
     pub ping: Option<u64>,
+    pub reconnect_count: u64,
+    pub last_error: Option<String>,
+}
+
+// Pool verdict for a submitted share
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareResult {
+    pub accepted: bool,
+    pub error_code: Option<i64>,
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,16 +90,113 @@ pub struct StratumMessage {
     pub error: Option<Value>,
 }
 
-pub struct StratumClient {
-    pool_url: String,
-    pool_port: u16,
+// Error object returned by the pool, e.g. [23, "Low difficulty share", null]
+#[derive(Debug, Clone)]
+struct StratumRpcError {
+    code: Option<i64>,
+    message: String,
+}
+
+type StratumResponse = Result<Value, StratumRpcError>;
+
+// Request we sent and are still waiting on, keyed by its JSON-RPC id
+struct PendingRequest {
+    method: String,
+    sent_at: Instant,
+    responder: Option<oneshot::Sender<StratumResponse>>,
+}
+
+type PendingRequests = Arc<Mutex<HashMap<u64, PendingRequest>>>;
+
+// Jobs are tagged with the connection epoch they arrived on
+type EpochJob = (u64, StratumJob);
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+// Upper bound on the wait a pool may ask for in client.reconnect
+const MAX_RECONNECT_WAIT: Duration = Duration::from_secs(300);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Either a plain TCP connection or TLS on top of one, depending on the pool URL
+trait PoolStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> PoolStream for T {}
+
+type PoolConnection = Box<dyn PoolStream>;
+
+// TLS settings for stratum+ssl:// pools. A pinned fingerprint trusts exactly that
+// certificate; accept_self_signed skips chain validation for private pools
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TlsOptions {
+    // Hex SHA-256 of the pool's DER certificate, colon separators allowed
+    pub pinned_fingerprint: Option<String>,
+    pub accept_self_signed: bool,
+}
+
+// Redial schedule used after the pool connection drops
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconnectPolicy {
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    // None keeps retrying for as long as the client is running
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 1_000,
+            max_delay_ms: 60_000,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // Capped exponential backoff with equal jitter: half of the delay is fixed, half random
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_delay_ms
+            .saturating_mul(1u64 << attempt.min(20));
+        let capped = exponential.min(self.max_delay_ms).max(1);
+        let half = capped / 2;
+        let jitter = (uuid::Uuid::new_v4().as_u128() as u64) % (capped - half + 1);
+        Duration::from_millis(half + jitter)
+    }
+}
+
+// Why the read loop for a connection ended
+enum ConnectionExit {
+    Shutdown,
+    Closed(String),
+    Redirect(ReconnectRequest),
+}
+
+// State shared between the client handle and its connection supervisor task
+#[derive(Clone)]
+struct Session {
     username: String,
     password: String,
-    stream: Arc<Mutex<Option<TcpStream>>>,
+    pool_host: String,
+    pool_port: u16,
+    // None connects over plain TCP
+    tls: Option<TlsOptions>,
+    // The read half is owned by the supervisor while it reads; writes go through
+    // a channel to a per-connection writer task so they never wait on reads
+    reader: Arc<Mutex<Option<BufReader<ReadHalf<PoolConnection>>>>>,
+    outgoing: Arc<Mutex<Option<mpsc::Sender<String>>>>,
     stats: Arc<Mutex<StratumStats>>,
-    message_id: AtomicU64,
-    job_receiver: Arc<Mutex<Option<mpsc::Receiver<StratumJob>>>>,
-    job_sender: mpsc::Sender<StratumJob>,
+    message_id: Arc<AtomicU64>,
+    pending: PendingRequests,
+    // Bumped whenever a connection is lost so its queued jobs are never handed out
+    job_epoch: Arc<AtomicU64>,
+    job_sender: mpsc::Sender<EpochJob>,
+}
+
+pub struct StratumClient {
+    session: Session,
+    reconnect_policy: ReconnectPolicy,
+    job_receiver: Arc<Mutex<Option<mpsc::Receiver<EpochJob>>>>,
     shutdown_sender: mpsc::Sender<()>,
     shutdown_receiver: Arc<Mutex<Option<mpsc::Receiver<()>>>>,
 }
@@ -68,125 +211,273 @@ impl StratumClient {
             pool_url: pool_url.to_string(),
             worker_name: username.to_string(),
             difficulty: 1.0,
+            subscription: None,
             accepted_shares: 0,
             rejected_shares: 0,
             last_share_time: None,
+            last_reject_code: None,
+            last_reject_reason: None,
             connection_time: chrono::Utc::now(),
             ping: None,
+            reconnect_count: 0,
+            last_error: None,
         };
 
-        Self {
-            pool_url: pool_url.to_string(),
-            pool_port,
+        let session = Session {
             username: username.to_string(),
             password: password.to_string(),
-            stream: Arc::new(Mutex::new(None)),
+            pool_host: pool_url.to_string(),
+            pool_port,
+            tls: None,
+            reader: Arc::new(Mutex::new(None)),
+            outgoing: Arc::new(Mutex::new(None)),
             stats: Arc::new(Mutex::new(stats)),
-            message_id: AtomicU64::new(1),
-            job_receiver: Arc::new(Mutex::new(Some(job_receiver))),
+            message_id: Arc::new(AtomicU64::new(1)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            job_epoch: Arc::new(AtomicU64::new(0)),
             job_sender,
+        };
+
+        Self {
+            session,
+            reconnect_policy: ReconnectPolicy::default(),
+            job_receiver: Arc::new(Mutex::new(Some(job_receiver))),
             shutdown_sender,
             shutdown_receiver: Arc::new(Mutex::new(Some(shutdown_receiver))),
         }
     }
 
+    // Builds a client from a stratum+tcp:// or stratum+ssl:// URL, enabling TLS for the latter
+    pub fn from_url(url: &str, username: &str, password: &str) -> Result<Self, AppError> {
+        let (host, port) = parse_stratum_url(url)?;
+        let client = Self::new(&host, port, username, password);
+
+        if url.starts_with("stratum+ssl://") {
+            Ok(client.with_tls(TlsOptions::default()))
+        } else {
+            Ok(client)
+        }
+    }
+
+    pub fn with_tls(mut self, options: TlsOptions) -> Self {
+        self.session.tls = Some(options);
+        self
+    }
+
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
     pub async fn connect(&self) -> Result<(), AppError> {
+        if self.shutdown_receiver.lock().await.is_none() {
+            return Err(AppError::Stratum(
+                "Stratum client has already been started".to_string(),
+            ));
+        }
+
+        let session = &self.session;
+        let stream =
+            Self::dial(&session.pool_host, session.pool_port, session.tls.as_ref()).await?;
+        session.attach(stream).await;
+
         info!(
-            "Connecting to Stratum pool: {}:{}",
-            self.pool_url, self.pool_port
+            "Connected to Stratum pool successfully: {}",
+            session.pool_host
         );
 
-        let address = format!("{}:{}", self.pool_url, self.pool_port);
-        let tcp_stream = timeout(Duration::from_secs(10), TcpStream::connect(&address))
-            .await
-            .map_err(|_| AppError::Stratum("Connection timeout".to_string()))?
-            .map_err(|e| AppError::Stratum(format!("Failed to connect: {}", e)))?;
+        // Queue the handshake before the supervisor starts reading, so a connection
+        // dropped this early is left to the reconnect path instead of racing it
+        let subscribe_response = session.subscribe().await?;
+        let authorize_response = session.authorize().await?;
 
-        {
-            let mut stream = self.stream.lock().await;
-            *stream = Some(tcp_stream);
-        }
+        // Start the supervised message loop
+        let shutdown_receiver = self
+            .shutdown_receiver
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| AppError::Stratum("Shutdown receiver already taken".to_string()))?;
+        tokio::spawn(Self::supervise(
+            session.clone(),
+            self.reconnect_policy.clone(),
+            shutdown_receiver,
+        ));
 
-        {
-            let mut stats = self.stats.lock().await;
-            stats.connected = true;
-            stats.connection_time = chrono::Utc::now();
+        if let Err(e) = Self::check_handshake(subscribe_response, authorize_response).await {
+            let _ = self.disconnect().await;
+            return Err(e);
         }
 
-        info!("Connected to Stratum pool successfully: {}", self.pool_url);
+        Ok(())
+    }
 
-        // Start the message handling loop
-        self.start_message_loop().await?;
+    async fn check_handshake(
+        subscribe_response: oneshot::Receiver<StratumResponse>,
+        authorize_response: oneshot::Receiver<StratumResponse>,
+    ) -> Result<(), AppError> {
+        Self::wait_for_response("mining.subscribe", subscribe_response)
+            .await?
+            .map_err(|e| AppError::Stratum(format!("Subscription rejected: {}", e.describe())))?;
 
-        // Send subscription and authorization
-        self.subscribe().await?;
-        self.authorize().await?;
+        let authorized = Self::wait_for_response("mining.authorize", authorize_response)
+            .await?
+            .map_err(|e| AppError::Stratum(format!("Authorization rejected: {}", e.describe())))?;
+        if authorized.as_bool() != Some(true) {
+            return Err(AppError::Stratum(
+                "Authorization rejected by pool".to_string(),
+            ));
+        }
 
         Ok(())
     }
 
-    async fn start_message_loop(&self) -> Result<(), AppError> {
-        let stream_clone = Arc::clone(&self.stream);
-        let stats_clone = Arc::clone(&self.stats);
-        let job_sender = self.job_sender.clone();
-        let mut shutdown_receiver = {
-            let mut receiver_guard = self.shutdown_receiver.lock().await;
-            receiver_guard
-                .take()
-                .ok_or_else(|| AppError::Stratum("Shutdown receiver already taken".to_string()))?
+    async fn dial(
+        host: &str,
+        port: u16,
+        tls: Option<&TlsOptions>,
+    ) -> Result<PoolConnection, AppError> {
+        info!("Connecting to Stratum pool: {}:{}", host, port);
+
+        let address = format!("{}:{}", host, port);
+        let tcp_stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(&address))
+            .await
+            .map_err(|_| AppError::Stratum("Connection timeout".to_string()))?
+            .map_err(|e| AppError::Stratum(format!("Failed to connect: {}", e)))?;
+
+        let Some(options) = tls else {
+            return Ok(Box::new(tcp_stream));
         };
 
-        tokio::spawn(async move {
-            let mut stream_guard = stream_clone.lock().await;
-            if let Some(ref mut stream) = *stream_guard {
-                let (reader, _writer) = stream.split();
-                let mut buf_reader = BufReader::new(reader);
-                let mut line = String::new();
-
-                loop {
-                    tokio::select! {
-                        result = buf_reader.read_line(&mut line) => {
-                            match result {
-                                Ok(0) => {
-                                    warn!("Connection closed by pool");
-                                    break;
-                                }
-                                Ok(_) => {
-                                    if let Err(e) = Self::handle_message(&line, &stats_clone, &job_sender).await {
-                                        error!("Error handling message: {}", e);
-                                    }
-                                    line.clear();
-                                }
-                                Err(e) => {
-                                    error!("Error reading from stream: {}", e);
-                                    break;
-                                }
-                            }
-                        }
-                        _ = shutdown_receiver.recv() => {
-                            info!("Received shutdown signal");
-                            break;
-                        }
-                    }
+        let connector = tls_connector(options)?;
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|e| AppError::Stratum(format!("Invalid TLS server name {}: {}", host, e)))?;
+        let tls_stream = timeout(CONNECT_TIMEOUT, connector.connect(server_name, tcp_stream))
+            .await
+            .map_err(|_| AppError::Stratum("TLS handshake timeout".to_string()))?
+            .map_err(|e| AppError::Stratum(format!("TLS handshake failed: {}", e)))?;
+
+        Ok(Box::new(tls_stream))
+    }
+
+    // Owns the connection for the lifetime of the client: reads until the pool goes away,
+    // then redials and replays the handshake until shut down or out of attempts
+    async fn supervise(
+        session: Session,
+        policy: ReconnectPolicy,
+        mut shutdown_receiver: mpsc::Receiver<()>,
+    ) {
+        loop {
+            let exit = session.read_until_closed(&mut shutdown_receiver).await;
+            session.detach().await;
+
+            let redirect = match exit {
+                ConnectionExit::Shutdown => break,
+                ConnectionExit::Closed(reason) => {
+                    warn!("Stratum connection lost: {}", reason);
+                    session.record_error(reason).await;
+                    None
                 }
+                ConnectionExit::Redirect(request) => {
+                    info!(
+                        "Pool requested reconnect to {}:{}",
+                        request.host, request.port
+                    );
+                    Some(request)
+                }
+            };
 
-                // Update connection status
-                let mut stats = stats_clone.lock().await;
-                stats.connected = false;
+            if !Self::redial(&session, &policy, redirect, &mut shutdown_receiver).await {
+                break;
             }
-        });
+        }
 
-        Ok(())
+        info!("Stratum connection supervisor stopped");
     }
 
+    // Returns false when the client shut down or the policy ran out of attempts
+    async fn redial(
+        session: &Session,
+        policy: &ReconnectPolicy,
+        redirect: Option<ReconnectRequest>,
+        shutdown_receiver: &mut mpsc::Receiver<()>,
+    ) -> bool {
+        let mut attempt: u32 = 0;
+
+        loop {
+            // A pool redirect gets one attempt on its own terms before normal backoff resumes
+            let (host, port, delay) = match (&redirect, attempt) {
+                (Some(request), 0) => (request.host.clone(), request.port, request.wait),
+                _ => (
+                    session.pool_host.clone(),
+                    session.pool_port,
+                    policy.delay_for_attempt(attempt),
+                ),
+            };
+
+            debug!(
+                "Reconnecting to {}:{} in {}ms (attempt {})",
+                host,
+                port,
+                delay.as_millis(),
+                attempt + 1
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown_receiver.recv() => return false,
+            }
+
+            let result = match Self::dial(&host, port, session.tls.as_ref()).await {
+                Ok(stream) => {
+                    session.attach(stream).await;
+                    match session.replay_handshake().await {
+                        Ok((subscribe, authorize)) => tokio::select! {
+                            result = session.read_handshake(subscribe, authorize) => result,
+                            _ = shutdown_receiver.recv() => return false,
+                        },
+                        Err(e) => Err(e),
+                    }
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(()) => {
+                    let mut stats = session.stats.lock().await;
+                    stats.reconnect_count += 1;
+                    info!(
+                        "Reconnected to Stratum pool {}:{} (reconnect #{})",
+                        host, port, stats.reconnect_count
+                    );
+                    return true;
+                }
+                Err(e) => {
+                    session.detach().await;
+                    session.record_error(e.to_string()).await;
+                    attempt += 1;
+
+                    if let Some(max_attempts) = policy.max_attempts {
+                        if attempt >= max_attempts {
+                            error!(
+                                "Giving up on Stratum pool after {} reconnect attempts",
+                                attempt
+                            );
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns a reconnect request when the pool asks us to move to another endpoint
     async fn handle_message(
         line: &str,
-        stats: &Arc<Mutex<StratumStats>>,
-        job_sender: &mpsc::Sender<StratumJob>,
-    ) -> Result<(), AppError> {
+        session: &Session,
+    ) -> Result<Option<ReconnectRequest>, AppError> {
         let line = line.trim();
         if line.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
 
         debug!("Received message: {}", line);
@@ -218,11 +509,13 @@ impl StratumClient {
                                     nbits: params_array[6].as_str().unwrap_or("").to_string(),
                                     ntime: params_array[7].as_str().unwrap_or("").to_string(),
                                     clean_jobs: params_array[8].as_bool().unwrap_or(false),
+                                    merkle_root: None,
                                 };
 
                                 info!("Received new mining job: {}", job.job_id);
 
-                                if let Err(e) = job_sender.send(job).await {
+                                let epoch = session.job_epoch.load(Ordering::SeqCst);
+                                if let Err(e) = session.job_sender.send((epoch, job)).await {
                                     error!("Failed to send job: {}", e);
                                 }
                             }
@@ -234,72 +527,206 @@ impl StratumClient {
                         if let Some(params_array) = params.as_array() {
                             if let Some(difficulty) = params_array.first().and_then(|v| v.as_f64())
                             {
-                                let mut stats_guard = stats.lock().await;
+                                let mut stats_guard = session.stats.lock().await;
                                 stats_guard.difficulty = difficulty;
                                 info!("Difficulty updated: {}", difficulty);
                             }
                         }
                     }
                 }
+                "mining.set_extranonce" => {
+                    let params = message.params.as_ref().and_then(|p| p.as_array());
+                    let extranonce1 = params
+                        .and_then(|p| p.first())
+                        .and_then(|v| v.as_str())
+                        .filter(|v| is_hex(v));
+                    let extranonce2_size = params.and_then(|p| p.get(1)).and_then(|v| v.as_u64());
+
+                    match (extranonce1, extranonce2_size) {
+                        (Some(extranonce1), Some(extranonce2_size)) => {
+                            let mut stats_guard = session.stats.lock().await;
+                            let subscription_ids = stats_guard
+                                .subscription
+                                .take()
+                                .map(|s| s.subscription_ids)
+                                .unwrap_or_default();
+                            stats_guard.subscription = Some(StratumSubscription {
+                                subscription_ids,
+                                extranonce1: extranonce1.to_string(),
+                                extranonce2_size: extranonce2_size as usize,
+                            });
+                            info!(
+                                "Extranonce updated: extranonce1={}, extranonce2_size={}",
+                                extranonce1, extranonce2_size
+                            );
+                        }
+                        _ => warn!("Ignoring malformed mining.set_extranonce: {:?}", params),
+                    }
+                }
+                "client.reconnect" => {
+                    let params = message.params.as_ref().and_then(|p| p.as_array());
+                    return Ok(Some(Self::parse_reconnect_request(
+                        params.map(|p| p.as_slice()).unwrap_or(&[]),
+                        (&session.pool_host, session.pool_port),
+                    )));
+                }
                 _ => {
                     debug!("Unhandled method: {}", method);
                 }
             }
-        } else if let Some(result) = &message.result {
-            // Handle responses to our requests
-            if result.as_bool() == Some(true) {
-                debug!("Request successful: {}", message.id.unwrap_or(0));
-            } else {
-                warn!("Request failed: {}", result);
-            }
+        } else if let Some(id) = message.id {
+            Self::handle_response(id, &message, session).await;
         } else if let Some(error) = &message.error {
             error!("Received error: {}", error);
         }
 
-        Ok(())
+        Ok(None)
     }
 
-    async fn send_message(&self, message: Value) -> Result<(), AppError> {
-        let message_str = format!("{}\n", serde_json::to_string(&message)?);
-        debug!("Sending message: {}", message_str.trim());
+    // client.reconnect params are [host, port, wait_seconds], all optional
+    fn parse_reconnect_request(params: &[Value], pool_endpoint: (&str, u16)) -> ReconnectRequest {
+        let (pool_host, pool_port) = pool_endpoint;
 
-        let mut stream_guard = self.stream.lock().await;
-        if let Some(ref mut stream) = *stream_guard {
-            stream
-                .write_all(message_str.as_bytes())
-                .await
-                .map_err(|e| AppError::Stratum(format!("Failed to send message: {}", e)))?;
-            stream
-                .flush()
-                .await
-                .map_err(|e| AppError::Stratum(format!("Failed to flush stream: {}", e)))?;
-        } else {
-            return Err(AppError::Stratum("Not connected".to_string()));
-        }
+        let requested_host = params
+            .first()
+            .and_then(|v| v.as_str())
+            .filter(|h| !h.is_empty());
+        let host = match requested_host {
+            Some(host) if is_same_pool_domain(pool_host, host) => host.to_string(),
+            Some(host) => {
+                warn!(
+                    "Refusing client.reconnect to foreign host {}, staying on {}",
+                    host, pool_host
+                );
+                pool_host.to_string()
+            }
+            None => pool_host.to_string(),
+        };
 
-        Ok(())
-    }
+        // Ports arrive as either numbers or strings depending on the pool software
+        let port = params
+            .get(1)
+            .and_then(|v| {
+                v.as_u64()
+                    .or_else(|| v.as_str().and_then(|s| s.parse::<u64>().ok()))
+            })
+            .and_then(|p| u16::try_from(p).ok())
+            .filter(|p| *p > 0)
+            .unwrap_or(pool_port);
 
-    async fn subscribe(&self) -> Result<(), AppError> {
-        let id = self.message_id.fetch_add(1, Ordering::SeqCst);
-        let message = json!({
-            "id": id,
-            "method": "mining.subscribe",
-            "params": ["Melanin Click Miner", null]
-        });
+        let wait_seconds = params
+            .get(2)
+            .and_then(|v| {
+                v.as_u64()
+                    .or_else(|| v.as_str().and_then(|s| s.parse::<u64>().ok()))
+            })
+            .unwrap_or(0);
 
-        self.send_message(message).await
+        ReconnectRequest {
+            host,
+            port,
+            wait: Duration::from_secs(wait_seconds).min(MAX_RECONNECT_WAIT),
+        }
     }
 
-    async fn authorize(&self) -> Result<(), AppError> {
-        let id = self.message_id.fetch_add(1, Ordering::SeqCst);
-        let message = json!({
-            "id": id,
-            "method": "mining.authorize",
-            "params": [self.username, self.password]
-        });
+    async fn handle_response(id: u64, message: &StratumMessage, session: &Session) {
+        let request = {
+            let mut pending_guard = session.pending.lock().await;
+            pending_guard.remove(&id)
+        };
+
+        let Some(request) = request else {
+            warn!("Received response for unknown request id: {}", id);
+            return;
+        };
+
+        let mut response: StratumResponse = match &message.error {
+            Some(error) => Err(StratumRpcError::from_value(error)),
+            None => Ok(message.result.clone().unwrap_or(Value::Null)),
+        };
+
+        if request.method == "mining.subscribe" {
+            if let Ok(result) = &response {
+                match parse_subscribe_result(result) {
+                    Some(subscription) => {
+                        info!(
+                            "Subscribed: extranonce1={}, extranonce2_size={}",
+                            subscription.extranonce1, subscription.extranonce2_size
+                        );
+                        session.stats.lock().await.subscription = Some(subscription);
+                    }
+                    None => {
+                        response = Err(StratumRpcError {
+                            code: None,
+                            message: format!("Malformed mining.subscribe result: {}", result),
+                        });
+                    }
+                }
+            }
+        }
+
+        {
+            let mut stats_guard = session.stats.lock().await;
+            stats_guard.ping = Some(request.sent_at.elapsed().as_millis() as u64);
 
-        self.send_message(message).await
+            if request.method == "mining.submit" {
+                match &response {
+                    Ok(Value::Bool(true)) => {
+                        stats_guard.accepted_shares += 1;
+                        stats_guard.last_share_time = Some(chrono::Utc::now());
+                        info!("Share accepted (request {})", id);
+                    }
+                    Ok(_) => {
+                        stats_guard.rejected_shares += 1;
+                        stats_guard.last_reject_code = None;
+                        stats_guard.last_reject_reason = Some("Rejected by pool".to_string());
+                        warn!("Share rejected (request {}) without reason", id);
+                    }
+                    Err(e) => {
+                        stats_guard.rejected_shares += 1;
+                        stats_guard.last_reject_code = e.code;
+                        stats_guard.last_reject_reason = Some(e.message.clone());
+                        warn!("Share rejected (request {}): {}", id, e.describe());
+                    }
+                }
+            } else if let Err(e) = &response {
+                warn!("{} failed: {}", request.method, e.describe());
+                stats_guard.last_error =
+                    Some(format!("{} failed: {}", request.method, e.describe()));
+            } else if request.method == "mining.authorize"
+                && response.as_ref().ok() != Some(&Value::Bool(true))
+            {
+                warn!("Pool refused authorization for {}", session.username);
+                stats_guard.last_error = Some(format!(
+                    "Authorization rejected for worker {}",
+                    session.username
+                ));
+            } else {
+                debug!("{} succeeded (request {})", request.method, id);
+            }
+        }
+
+        if let Some(responder) = request.responder {
+            // The caller may have timed out and dropped the receiver already
+            let _ = responder.send(response);
+        }
+    }
+
+    async fn wait_for_response(
+        method: &str,
+        receiver: oneshot::Receiver<StratumResponse>,
+    ) -> Result<StratumResponse, AppError> {
+        match timeout(RESPONSE_TIMEOUT, receiver).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(AppError::Stratum(format!(
+                "Connection closed before {} response",
+                method
+            ))),
+            Err(_) => Err(AppError::Stratum(format!(
+                "Timed out waiting for {} response",
+                method
+            ))),
+        }
     }
 
     pub async fn submit_share(
@@ -308,36 +735,56 @@ impl StratumClient {
         extranonce2: &str,
         ntime: &str,
         nonce: &str,
-    ) -> Result<(), AppError> {
-        let id = self.message_id.fetch_add(1, Ordering::SeqCst);
-        let message = json!({
-            "id": id,
-            "method": "mining.submit",
-            "params": [self.username, job_id, extranonce2, ntime, nonce]
-        });
-
+    ) -> Result<ShareResult, AppError> {
         info!("Submitting share: job_id={}, nonce={}", job_id, nonce);
-        self.send_message(message).await?;
+        let response = self
+            .session
+            .send_request(
+                "mining.submit",
+                json!([self.session.username, job_id, extranonce2, ntime, nonce]),
+            )
+            .await?;
 
-        // Update statistics
-        let mut stats = self.stats.lock().await;
-        stats.accepted_shares += 1;
-        stats.last_share_time = Some(chrono::Utc::now());
+        // Accepted/rejected counters are updated by the message loop once the pool answers
+        let share_result = match Self::wait_for_response("mining.submit", response).await? {
+            Ok(result) => ShareResult {
+                accepted: result.as_bool() == Some(true),
+                error_code: None,
+                reason: None,
+            },
+            Err(e) => ShareResult {
+                accepted: false,
+                error_code: e.code,
+                reason: Some(e.message),
+            },
+        };
 
-        Ok(())
+        Ok(share_result)
     }
 
     pub async fn get_stats(&self) -> StratumStats {
-        let stats = self.stats.lock().await;
+        let stats = self.session.stats.lock().await;
         stats.clone()
     }
 
+    pub async fn get_subscription(&self) -> Option<StratumSubscription> {
+        let stats = self.session.stats.lock().await;
+        stats.subscription.clone()
+    }
+
     pub async fn get_next_job(&self) -> Option<StratumJob> {
         let mut receiver_guard = self.job_receiver.lock().await;
-        if let Some(ref mut receiver) = *receiver_guard {
-            receiver.recv().await
-        } else {
-            None
+        let receiver = receiver_guard.as_mut()?;
+
+        loop {
+            let (epoch, job) = receiver.recv().await?;
+            if epoch == self.session.job_epoch.load(Ordering::SeqCst) {
+                return Some(job);
+            }
+            debug!(
+                "Discarding stale job {} from a previous connection",
+                job.job_id
+            );
         }
     }
 
@@ -349,14 +796,12 @@ impl StratumClient {
             warn!("Failed to send shutdown signal: {}", e);
         }
 
-        // Close the stream
-        let mut stream_guard = self.stream.lock().await;
-        if let Some(stream) = stream_guard.take() {
-            drop(stream);
-        }
+        // Close the connection; dropping the outgoing sender stops the writer task
+        self.session.reader.lock().await.take();
+        self.session.outgoing.lock().await.take();
 
         // Update connection status
-        let mut stats = self.stats.lock().await;
+        let mut stats = self.session.stats.lock().await;
         stats.connected = false;
 
         info!("Disconnected from Stratum pool");
@@ -364,11 +809,376 @@ impl StratumClient {
     }
 
     pub async fn is_connected(&self) -> bool {
-        let stats = self.stats.lock().await;
+        let stats = self.session.stats.lock().await;
         stats.connected
     }
 }
 
+// Pool connection behind a single job/stats interface, with the protocol chosen by URL
+// scheme: stratum+tcp:// and stratum+ssl:// speak V1, stratum2+tcp:// speaks V2
+pub enum PoolClient {
+    V1(StratumClient),
+    V2(Sv2Client),
+}
+
+impl PoolClient {
+    pub fn from_url(url: &str, username: &str, password: &str) -> Result<Self, AppError> {
+        if url.starts_with("stratum2+tcp://") {
+            Ok(Self::V2(Sv2Client::from_url(url, username)?))
+        } else {
+            Ok(Self::V1(StratumClient::from_url(url, username, password)?))
+        }
+    }
+
+    pub async fn connect(&self) -> Result<(), AppError> {
+        match self {
+            Self::V1(client) => client.connect().await,
+            Self::V2(client) => client.connect().await,
+        }
+    }
+
+    // Fields are hex as in mining.submit. V2 standard channels have no extranonce2,
+    // and the share is submitted with the version of the job it was found on
+    pub async fn submit_share(
+        &self,
+        job_id: &str,
+        extranonce2: &str,
+        ntime: &str,
+        nonce: &str,
+    ) -> Result<ShareResult, AppError> {
+        match self {
+            Self::V1(client) => client.submit_share(job_id, extranonce2, ntime, nonce).await,
+            Self::V2(client) => {
+                let job_id = job_id
+                    .parse::<u32>()
+                    .map_err(|_| AppError::Stratum(format!("Invalid job id: {}", job_id)))?;
+                let version = client.job_version(job_id).await.ok_or_else(|| {
+                    AppError::Stratum(format!("Job {} is no longer active", job_id))
+                })?;
+                client
+                    .submit_share(
+                        job_id,
+                        parse_hex_u32(nonce)?,
+                        parse_hex_u32(ntime)?,
+                        version,
+                    )
+                    .await
+            }
+        }
+    }
+
+    pub async fn get_stats(&self) -> StratumStats {
+        match self {
+            Self::V1(client) => client.get_stats().await,
+            Self::V2(client) => client.get_stats().await,
+        }
+    }
+
+    pub async fn get_next_job(&self) -> Option<StratumJob> {
+        match self {
+            Self::V1(client) => client.get_next_job().await,
+            Self::V2(client) => client.get_next_job().await,
+        }
+    }
+
+    pub async fn disconnect(&self) -> Result<(), AppError> {
+        match self {
+            Self::V1(client) => client.disconnect().await,
+            Self::V2(client) => client.disconnect().await,
+        }
+    }
+
+    pub async fn is_connected(&self) -> bool {
+        match self {
+            Self::V1(client) => client.is_connected().await,
+            Self::V2(client) => client.is_connected().await,
+        }
+    }
+}
+
+fn parse_hex_u32(value: &str) -> Result<u32, AppError> {
+    u32::from_str_radix(value, 16)
+        .map_err(|_| AppError::Stratum(format!("Invalid hex field: {}", value)))
+}
+
+impl Session {
+    async fn attach(&self, stream: PoolConnection) {
+        let (reader, writer) = tokio::io::split(stream);
+        let (outgoing_sender, outgoing_receiver) = mpsc::channel(100);
+        tokio::spawn(Self::write_loop(writer, outgoing_receiver));
+
+        *self.reader.lock().await = Some(BufReader::new(reader));
+        *self.outgoing.lock().await = Some(outgoing_sender);
+
+        let mut stats = self.stats.lock().await;
+        stats.connected = true;
+        stats.connection_time = chrono::Utc::now();
+    }
+
+    // Tear down connection state: waiting requests fail and queued jobs become stale
+    async fn detach(&self) {
+        self.reader.lock().await.take();
+        self.outgoing.lock().await.take();
+        self.stats.lock().await.connected = false;
+        self.job_epoch.fetch_add(1, Ordering::SeqCst);
+
+        // Dropping the responders fails every request still waiting on this connection
+        self.pending.lock().await.clear();
+    }
+
+    async fn record_error(&self, error: String) {
+        let mut stats = self.stats.lock().await;
+        stats.last_error = Some(error);
+    }
+
+    async fn read_until_closed(
+        &self,
+        shutdown_receiver: &mut mpsc::Receiver<()>,
+    ) -> ConnectionExit {
+        let Some(mut buf_reader) = self.reader.lock().await.take() else {
+            return ConnectionExit::Closed("Not connected".to_string());
+        };
+
+        let mut line = String::new();
+
+        loop {
+            tokio::select! {
+                result = buf_reader.read_line(&mut line) => {
+                    match result {
+                        Ok(0) => {
+                            return ConnectionExit::Closed("Connection closed by pool".to_string());
+                        }
+                        Ok(_) => {
+                            let result = StratumClient::handle_message(&line, self).await;
+                            line.clear();
+
+                            match result {
+                                Ok(Some(request)) => return ConnectionExit::Redirect(request),
+                                Ok(None) => {}
+                                Err(e) => error!("Error handling message: {}", e),
+                            }
+                        }
+                        Err(e) => {
+                            return ConnectionExit::Closed(format!("Error reading from stream: {}", e));
+                        }
+                    }
+                }
+                _ = shutdown_receiver.recv() => {
+                    info!("Received shutdown signal");
+                    return ConnectionExit::Shutdown;
+                }
+            }
+        }
+    }
+
+    async fn send_message(&self, message: Value) -> Result<(), AppError> {
+        let message_str = format!("{}\n", serde_json::to_string(&message)?);
+        debug!("Sending message: {}", message_str.trim());
+
+        // Clone the sender so the lock is not held while the writer catches up
+        let outgoing = self
+            .outgoing
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| AppError::Stratum("Not connected".to_string()))?;
+
+        outgoing
+            .send(message_str)
+            .await
+            .map_err(|_| AppError::Stratum("Failed to send message: connection closed".to_string()))
+    }
+
+    // Drains queued messages onto the socket until the connection is detached
+    async fn write_loop(
+        mut writer: WriteHalf<PoolConnection>,
+        mut outgoing: mpsc::Receiver<String>,
+    ) {
+        while let Some(message) = outgoing.recv().await {
+            let result = match writer.write_all(message.as_bytes()).await {
+                Ok(()) => writer.flush().await,
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = result {
+                warn!("Failed to write to Stratum pool: {}", e);
+                break;
+            }
+        }
+
+        let _ = writer.shutdown().await;
+    }
+
+    // Send a request and register it so the response can be matched by id
+    async fn send_tracked(
+        &self,
+        method: &str,
+        params: Value,
+        responder: Option<oneshot::Sender<StratumResponse>>,
+    ) -> Result<(), AppError> {
+        let id = self.message_id.fetch_add(1, Ordering::SeqCst);
+
+        {
+            let mut pending = self.pending.lock().await;
+            pending.insert(
+                id,
+                PendingRequest {
+                    method: method.to_string(),
+                    sent_at: Instant::now(),
+                    responder,
+                },
+            );
+        }
+
+        let message = json!({
+            "id": id,
+            "method": method,
+            "params": params
+        });
+
+        if let Err(e) = self.send_message(message).await {
+            self.pending.lock().await.remove(&id);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn send_request(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<oneshot::Receiver<StratumResponse>, AppError> {
+        let (responder, receiver) = oneshot::channel();
+        self.send_tracked(method, params, Some(responder)).await?;
+        Ok(receiver)
+    }
+
+    fn subscribe_params() -> Value {
+        json!(["Melanin Click Miner", null])
+    }
+
+    fn authorize_params(&self) -> Value {
+        json!([self.username, self.password])
+    }
+
+    async fn subscribe(&self) -> Result<oneshot::Receiver<StratumResponse>, AppError> {
+        self.send_request("mining.subscribe", Self::subscribe_params())
+            .await
+    }
+
+    async fn authorize(&self) -> Result<oneshot::Receiver<StratumResponse>, AppError> {
+        self.send_request("mining.authorize", self.authorize_params())
+            .await
+    }
+
+    // Resends the handshake on a fresh connection; the reconnect only counts once
+    // both replies come back through `read_handshake`
+    async fn replay_handshake(
+        &self,
+    ) -> Result<
+        (
+            oneshot::Receiver<StratumResponse>,
+            oneshot::Receiver<StratumResponse>,
+        ),
+        AppError,
+    > {
+        let subscribe_response = self.subscribe().await?;
+        let authorize_response = self.authorize().await?;
+        Ok((subscribe_response, authorize_response))
+    }
+
+    // Dispatches pool messages until both handshake replies are in. The reader stays in
+    // place, along with anything it buffered, for the read loop that takes over afterwards
+    async fn read_handshake(
+        &self,
+        subscribe_response: oneshot::Receiver<StratumResponse>,
+        authorize_response: oneshot::Receiver<StratumResponse>,
+    ) -> Result<(), AppError> {
+        let mut reader_guard = self.reader.lock().await;
+        let reader = reader_guard
+            .as_mut()
+            .ok_or_else(|| AppError::Stratum("Not connected".to_string()))?;
+
+        let handshake = StratumClient::check_handshake(subscribe_response, authorize_response);
+        tokio::pin!(handshake);
+        let mut line = String::new();
+
+        loop {
+            tokio::select! {
+                // Replies are dispatched between reads, so check them first
+                biased;
+                result = &mut handshake => return result,
+                result = reader.read_line(&mut line) => {
+                    match result {
+                        Ok(0) => {
+                            return Err(AppError::Stratum(
+                                "Connection closed by pool during handshake".to_string(),
+                            ));
+                        }
+                        Ok(_) => {
+                            let result = StratumClient::handle_message(&line, self).await;
+                            line.clear();
+
+                            match result {
+                                Ok(Some(_)) => warn!("Ignoring reconnect request during handshake"),
+                                Ok(None) => {}
+                                Err(e) => error!("Error handling message: {}", e),
+                            }
+                        }
+                        Err(e) => {
+                            let reason = format!("Error reading from stream: {}", e);
+                            return Err(AppError::Stratum(reason));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl StratumRpcError {
+    // Pools send either [code, message, traceback] or {"code": .., "message": ..}
+    fn from_value(error: &Value) -> Self {
+        if let Some(parts) = error.as_array() {
+            return Self {
+                code: parts.first().and_then(|v| v.as_i64()),
+                message: parts
+                    .get(1)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            };
+        }
+
+        if let Some(object) = error.as_object() {
+            return Self {
+                code: object.get("code").and_then(|v| v.as_i64()),
+                message: object
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            };
+        }
+
+        Self {
+            code: None,
+            message: error
+                .as_str()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| error.to_string()),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.code {
+            Some(code) => format!("{} (code {})", self.message, code),
+            None => self.message.clone(),
+        }
+    }
+}
+
 // Utility functions for Stratum protocol
 impl StratumMessage {
     #[allow(dead_code)]
@@ -389,6 +1199,178 @@ impl StratumMessage {
     }
 }
 
+// mining.subscribe result: [[["mining.notify", "<id>"], ...], "<extranonce1>", <extranonce2_size>]
+fn parse_subscribe_result(result: &Value) -> Option<StratumSubscription> {
+    let parts = result.as_array()?;
+
+    let extranonce1 = parts.get(1)?.as_str().filter(|v| is_hex(v))?;
+    let extranonce2_size = parts.get(2)?.as_u64()?;
+
+    // Some pools send a single ["mining.notify", "<id>"] pair instead of a list of pairs
+    let subscription_ids = match parts.first().and_then(|v| v.as_array()) {
+        Some(entries) if entries.first().is_some_and(|e| e.is_string()) => {
+            subscription_pair(entries).into_iter().collect()
+        }
+        Some(entries) => entries
+            .iter()
+            .filter_map(|entry| entry.as_array().and_then(|pair| subscription_pair(pair)))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Some(StratumSubscription {
+        subscription_ids,
+        extranonce1: extranonce1.to_string(),
+        extranonce2_size: extranonce2_size as usize,
+    })
+}
+
+fn subscription_pair(pair: &[Value]) -> Option<(String, String)> {
+    Some((
+        pair.first()?.as_str()?.to_string(),
+        pair.get(1)?.as_str()?.to_string(),
+    ))
+}
+
+fn is_hex(value: &str) -> bool {
+    hex::decode(value).is_ok()
+}
+
+// Only follow redirects to the configured pool host or one of its subdomains. Sibling
+// hosts are refused, since under a suffix like co.uk they may belong to anyone.
+fn is_same_pool_domain(pool_host: &str, requested_host: &str) -> bool {
+    let pool_host = pool_host.trim_end_matches('.').to_ascii_lowercase();
+    let requested_host = requested_host.trim_end_matches('.').to_ascii_lowercase();
+    if pool_host == requested_host {
+        return true;
+    }
+
+    if pool_host.parse::<std::net::IpAddr>().is_ok() {
+        return false;
+    }
+    requested_host.ends_with(&format!(".{pool_host}"))
+}
+
+fn tls_connector(options: &TlsOptions) -> Result<TlsConnector, AppError> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let roots = Arc::new(RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    });
+    let webpki = WebPkiServerVerifier::builder_with_provider(roots, provider.clone())
+        .build()
+        .map_err(|e| AppError::Stratum(format!("Failed to load TLS roots: {}", e)))?;
+
+    let pinned_fingerprint = options
+        .pinned_fingerprint
+        .as_deref()
+        .map(parse_fingerprint)
+        .transpose()?;
+    if options.accept_self_signed && pinned_fingerprint.is_none() {
+        warn!("Accepting any certificate from the pool: chain validation is disabled");
+    }
+
+    let verifier = PoolCertVerifier {
+        webpki,
+        provider: provider.clone(),
+        pinned_fingerprint,
+        accept_self_signed: options.accept_self_signed,
+    };
+
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| AppError::Stratum(format!("Failed to configure TLS: {}", e)))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+fn parse_fingerprint(fingerprint: &str) -> Result<Vec<u8>, AppError> {
+    let digits: String = fingerprint.chars().filter(|c| *c != ':').collect();
+    match hex::decode(digits.trim()) {
+        Ok(bytes) if bytes.len() == 32 => Ok(bytes),
+        _ => Err(AppError::Stratum(format!(
+            "Invalid SHA-256 certificate fingerprint: {}",
+            fingerprint
+        ))),
+    }
+}
+
+// Certificate checks for pool connections: a pinned fingerprint must match the leaf
+// and is trusted on its own, otherwise the chain goes through webpki unless
+// self-signed certificates were explicitly allowed
+#[derive(Debug)]
+struct PoolCertVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    provider: Arc<CryptoProvider>,
+    pinned_fingerprint: Option<Vec<u8>>,
+    accept_self_signed: bool,
+}
+
+impl ServerCertVerifier for PoolCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(pinned) = &self.pinned_fingerprint {
+            let fingerprint = Sha256::digest(end_entity.as_ref());
+            if fingerprint.as_slice() != pinned.as_slice() {
+                return Err(rustls::Error::General(format!(
+                    "Pool certificate fingerprint {} does not match the pinned fingerprint",
+                    hex::encode(fingerprint)
+                )));
+            }
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        if self.accept_self_signed {
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        self.webpki
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
 // Parse pool URL from stratum+tcp://host:port format
 pub fn parse_stratum_url(url: &str) -> Result<(String, u16), AppError> {
     let url = url
@@ -430,4 +1412,574 @@ mod tests {
         assert!(parse_stratum_url("invalid://url").is_err());
         assert!(parse_stratum_url("stratum+tcp://pool.com").is_err());
     }
+
+    async fn pending_with(
+        client: &StratumClient,
+        entries: &[(u64, &str)],
+    ) -> Vec<oneshot::Receiver<StratumResponse>> {
+        let mut receivers = Vec::new();
+        for (id, method) in entries {
+            let (responder, receiver) = oneshot::channel();
+            client.session.pending.lock().await.insert(
+                *id,
+                PendingRequest {
+                    method: method.to_string(),
+                    sent_at: Instant::now(),
+                    responder: Some(responder),
+                },
+            );
+            receivers.push(receiver);
+        }
+        receivers
+    }
+
+    #[tokio::test]
+    async fn test_submit_responses_update_share_counts() {
+        let client = StratumClient::new("pool.example.com", 3333, "worker", "x");
+        let receivers = pending_with(&client, &[(7, "mining.submit"), (8, "mining.submit")]).await;
+
+        StratumClient::handle_message(r#"{"id":7,"result":true,"error":null}"#, &client.session)
+            .await
+            .unwrap();
+        StratumClient::handle_message(
+            r#"{"id":8,"result":null,"error":[23,"Low difficulty share",null]}"#,
+            &client.session,
+        )
+        .await
+        .unwrap();
+
+        let stats = client.get_stats().await;
+        assert_eq!(stats.accepted_shares, 1);
+        assert_eq!(stats.rejected_shares, 1);
+        assert_eq!(stats.last_reject_code, Some(23));
+        assert_eq!(
+            stats.last_reject_reason.as_deref(),
+            Some("Low difficulty share")
+        );
+        assert!(client.session.pending.lock().await.is_empty());
+
+        let mut receivers = receivers.into_iter();
+        assert!(matches!(
+            receivers.next().unwrap().await.unwrap(),
+            Ok(Value::Bool(true))
+        ));
+        let rejected = receivers.next().unwrap().await.unwrap().unwrap_err();
+        assert_eq!(rejected.code, Some(23));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_error_reaches_caller() {
+        let client = StratumClient::new("pool.example.com", 3333, "worker", "x");
+        let receivers = pending_with(&client, &[(2, "mining.authorize")]).await;
+
+        StratumClient::handle_message(
+            r#"{"id":2,"result":null,"error":{"code":24,"message":"Unauthorized worker"}}"#,
+            &client.session,
+        )
+        .await
+        .unwrap();
+
+        let receiver = receivers.into_iter().next().unwrap();
+        let response = StratumClient::wait_for_response("mining.authorize", receiver)
+            .await
+            .unwrap();
+        let error = response.unwrap_err();
+        assert_eq!(error.describe(), "Unauthorized worker (code 24)");
+
+        // Authorization failures must not be counted as shares
+        let stats = client.get_stats().await;
+        assert_eq!(stats.accepted_shares, 0);
+        assert_eq!(stats.rejected_shares, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_reports_closed_connection() {
+        let client = StratumClient::new("pool.example.com", 3333, "worker", "x");
+        let receivers = pending_with(&client, &[(3, "mining.subscribe")]).await;
+        client.session.detach().await;
+
+        let receiver = receivers.into_iter().next().unwrap();
+        let result = StratumClient::wait_for_response("mining.subscribe", receiver).await;
+        assert!(matches!(result, Err(AppError::Stratum(_))));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_result_sets_extranonce() {
+        let client = StratumClient::new("pool.example.com", 3333, "worker", "x");
+        let receivers = pending_with(&client, &[(1, "mining.subscribe")]).await;
+
+        StratumClient::handle_message(
+            r#"{"id":1,"result":[[["mining.set_difficulty","b4b6693b"],["mining.notify","ae6812eb"]],"08000002",4],"error":null}"#,
+            &client.session,
+        )
+        .await
+        .unwrap();
+
+        let subscription = client.get_subscription().await.unwrap();
+        assert_eq!(subscription.extranonce1, "08000002");
+        assert_eq!(subscription.extranonce2_size, 4);
+        assert_eq!(
+            subscription.subscription_ids,
+            vec![
+                ("mining.set_difficulty".to_string(), "b4b6693b".to_string()),
+                ("mining.notify".to_string(), "ae6812eb".to_string()),
+            ]
+        );
+        let receiver = receivers.into_iter().next().unwrap();
+        assert!(receiver.await.unwrap().is_ok());
+
+        StratumClient::handle_message(
+            r#"{"id":null,"method":"mining.set_extranonce","params":["af4c0001",6]}"#,
+            &client.session,
+        )
+        .await
+        .unwrap();
+
+        let subscription = client.get_subscription().await.unwrap();
+        assert_eq!(subscription.extranonce1, "af4c0001");
+        assert_eq!(subscription.extranonce2_size, 6);
+        assert_eq!(subscription.subscription_ids.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_subscribe_result_is_an_error() {
+        let client = StratumClient::new("pool.example.com", 3333, "worker", "x");
+        let receivers = pending_with(&client, &[(1, "mining.subscribe")]).await;
+
+        StratumClient::handle_message(r#"{"id":1,"result":true,"error":null}"#, &client.session)
+            .await
+            .unwrap();
+
+        assert!(client.get_subscription().await.is_none());
+        let receiver = receivers.into_iter().next().unwrap();
+        assert!(receiver.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_reconnect_request() {
+        let client = StratumClient::new("pool.example.com", 3333, "worker", "x");
+
+        let request = StratumClient::handle_message(
+            r#"{"id":null,"method":"client.reconnect","params":["eu.pool.example.com","3334",5]}"#,
+            &client.session,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            request,
+            Some(ReconnectRequest {
+                host: "eu.pool.example.com".to_string(),
+                port: 3334,
+                wait: Duration::from_secs(5),
+            })
+        );
+
+        // Redirects to another domain keep the configured host
+        let request = StratumClient::handle_message(
+            r#"{"id":null,"method":"client.reconnect","params":["evil.example.net",4444,0]}"#,
+            &client.session,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.host, "pool.example.com");
+        assert_eq!(request.port, 4444);
+
+        // Sharing a parent domain is not enough: under co.uk it may be anyone's
+        let uk_client = StratumClient::new("pool.co.uk", 3333, "worker", "x");
+        let request = StratumClient::handle_message(
+            r#"{"id":null,"method":"client.reconnect","params":["attacker.co.uk",3333,0]}"#,
+            &uk_client.session,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.host, "pool.co.uk");
+        assert!(is_same_pool_domain("pool.co.uk", "EU.Pool.co.uk"));
+        assert!(!is_same_pool_domain("pool.co.uk", "evilpool.co.uk"));
+        assert!(!is_same_pool_domain("pool.example.com", "eu.example.com"));
+        assert!(!is_same_pool_domain("10.0.0.1", "a.10.0.0.1"));
+
+        // An empty parameter list means "reconnect to the same endpoint"
+        let request = StratumClient::handle_message(
+            r#"{"id":null,"method":"client.reconnect","params":[]}"#,
+            &client.session,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.host, "pool.example.com");
+        assert_eq!(request.port, 3333);
+        assert!(request.wait.is_zero());
+    }
+
+    #[test]
+    fn test_reconnect_backoff_is_capped_with_jitter() {
+        let policy = ReconnectPolicy {
+            initial_delay_ms: 100,
+            max_delay_ms: 1_000,
+            max_attempts: None,
+        };
+
+        for _ in 0..20 {
+            let first = policy.delay_for_attempt(0).as_millis();
+            assert!((50..=100).contains(&first), "first delay {}", first);
+
+            let third = policy.delay_for_attempt(2).as_millis();
+            assert!((200..=400).contains(&third), "third delay {}", third);
+
+            let late = policy.delay_for_attempt(30).as_millis();
+            assert!((500..=1_000).contains(&late), "capped delay {}", late);
+        }
+    }
+
+    // Answers the handshake on each accepted connection and returns the methods it saw
+    async fn serve_handshake(
+        listener: &tokio::net::TcpListener,
+        extranonce1: &str,
+        notify_job: &str,
+    ) -> (TcpStream, Vec<String>) {
+        let (stream, _) = listener.accept().await.unwrap();
+        answer_handshake(stream, extranonce1, notify_job).await
+    }
+
+    async fn answer_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+        stream: S,
+        extranonce1: &str,
+        notify_job: &str,
+    ) -> (S, Vec<String>) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        let mut methods = Vec::new();
+
+        while methods.len() < 2 {
+            let line = lines.next_line().await.unwrap().unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let method = request["method"].as_str().unwrap().to_string();
+            let result = if method == "mining.subscribe" {
+                json!([[["mining.notify", "1"]], extranonce1, 4])
+            } else {
+                json!(true)
+            };
+            let reply = json!({"id": request["id"], "result": result, "error": null});
+            writer
+                .write_all(format!("{}\n", reply).as_bytes())
+                .await
+                .unwrap();
+            methods.push(method);
+        }
+
+        let notify = json!({
+            "id": null,
+            "method": "mining.notify",
+            "params": [notify_job, "00", "01", "02", [], "20000000", "1d00ffff", "5f5e1000", true]
+        });
+        writer
+            .write_all(format!("{}\n", notify).as_bytes())
+            .await
+            .unwrap();
+
+        (lines.into_inner().into_inner().unsplit(writer), methods)
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_and_replays_handshake() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = StratumClient::new("127.0.0.1", port, "worker", "x").with_reconnect_policy(
+            ReconnectPolicy {
+                initial_delay_ms: 10,
+                max_delay_ms: 50,
+                max_attempts: Some(5),
+            },
+        );
+
+        let (connect_result, (first_stream, methods)) = tokio::join!(
+            client.connect(),
+            serve_handshake(&listener, "aa000001", "job-1")
+        );
+        connect_result.unwrap();
+        assert_eq!(methods, vec!["mining.subscribe", "mining.authorize"]);
+
+        // Give the first job time to queue up, then drop the connection
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(first_stream);
+
+        let (_second_stream, methods) = serve_handshake(&listener, "bb000002", "job-2").await;
+        assert_eq!(methods, vec!["mining.subscribe", "mining.authorize"]);
+
+        // The job from the dropped connection is stale and must be skipped
+        let job = timeout(Duration::from_secs(5), client.get_next_job())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.job_id, "job-2");
+
+        let stats = client.get_stats().await;
+        assert!(stats.connected);
+        assert_eq!(stats.reconnect_count, 1);
+        assert_eq!(
+            stats.last_error.as_deref(),
+            Some("Connection closed by pool")
+        );
+        assert_eq!(stats.subscription.unwrap().extranonce1, "bb000002");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_reauthorization_keeps_reconnecting() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = StratumClient::new("127.0.0.1", port, "worker", "x").with_reconnect_policy(
+            ReconnectPolicy {
+                initial_delay_ms: 10,
+                max_delay_ms: 50,
+                max_attempts: Some(5),
+            },
+        );
+
+        let (connect_result, (first_stream, _)) = tokio::join!(
+            client.connect(),
+            serve_handshake(&listener, "aa000001", "job-1")
+        );
+        connect_result.unwrap();
+        drop(first_stream);
+
+        // The pool takes the connection back but refuses the worker
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        for _ in 0..2 {
+            let request: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            let result = if request["method"] == "mining.subscribe" {
+                json!([[["mining.notify", "1"]], "cc000003", 4])
+            } else {
+                json!(false)
+            };
+            let reply = json!({"id": request["id"], "result": result, "error": null});
+            writer
+                .write_all(format!("{}\n", reply).as_bytes())
+                .await
+                .unwrap();
+        }
+
+        // A refused worker is a failed attempt, so the client dials again
+        let (_third_stream, methods) = timeout(
+            Duration::from_secs(5),
+            serve_handshake(&listener, "bb000002", "job-2"),
+        )
+        .await
+        .expect("client stopped reconnecting after the pool refused authorization");
+        assert_eq!(methods, vec!["mining.subscribe", "mining.authorize"]);
+
+        let job = timeout(Duration::from_secs(5), client.get_next_job())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.job_id, "job-2");
+
+        let stats = client.get_stats().await;
+        assert!(stats.connected);
+        assert_eq!(stats.reconnect_count, 1);
+        assert_eq!(stats.subscription.unwrap().extranonce1, "bb000002");
+    }
+
+    #[tokio::test]
+    async fn test_submit_share_while_jobs_stream_in() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = StratumClient::new("127.0.0.1", port, "worker", "x");
+
+        let (connect_result, (stream, _)) = tokio::join!(
+            client.connect(),
+            serve_handshake(&listener, "aa000001", "job-0")
+        );
+        connect_result.unwrap();
+
+        // Mock pool keeps announcing jobs until the share arrives, then answers it
+        let pool = tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut ticker = tokio::time::interval(Duration::from_millis(5));
+            let mut jobs_sent = 1;
+
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        let notify = json!({
+                            "id": null,
+                            "method": "mining.notify",
+                            "params": [format!("job-{}", jobs_sent), "00", "01", "02", [],
+                                       "20000000", "1d00ffff", "5f5e1000", false]
+                        });
+                        writer
+                            .write_all(format!("{}\n", notify).as_bytes())
+                            .await
+                            .unwrap();
+                        jobs_sent += 1;
+                    }
+                    line = lines.next_line() => {
+                        let request: Value = serde_json::from_str(&line.unwrap().unwrap()).unwrap();
+                        let reply = json!({"id": request["id"], "result": true, "error": null});
+                        writer
+                            .write_all(format!("{}\n", reply).as_bytes())
+                            .await
+                            .unwrap();
+                        // Hand the writer back so the connection outlives this task
+                        return (request, jobs_sent, writer);
+                    }
+                }
+            }
+        });
+
+        let job = timeout(Duration::from_secs(5), client.get_next_job())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.job_id, "job-0");
+
+        // Let the read loop get busy with incoming jobs before submitting
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let result = timeout(
+            Duration::from_secs(5),
+            client.submit_share("job-0", "00000000", "5f5e1000", "deadbeef"),
+        )
+        .await
+        .expect("share submission blocked behind the read loop")
+        .unwrap();
+        assert!(result.accepted);
+
+        let (request, jobs_sent, _pool_writer) = pool.await.unwrap();
+        assert_eq!(request["method"], "mining.submit");
+        assert_eq!(request["params"][1], "job-0");
+        assert!(jobs_sent > 1);
+
+        let next = timeout(Duration::from_secs(5), client.get_next_job())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.job_id, "job-1");
+        assert_eq!(client.get_stats().await.accepted_shares, 1);
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[test]
+    fn test_client_from_url_selects_transport() {
+        let plain =
+            StratumClient::from_url("stratum+tcp://pool.example.com:3333", "w", "x").unwrap();
+        assert_eq!(plain.session.tls, None);
+
+        let secure =
+            StratumClient::from_url("stratum+ssl://pool.example.com:4444", "w", "x").unwrap();
+        assert_eq!(secure.session.tls, Some(TlsOptions::default()));
+        assert_eq!(secure.session.pool_port, 4444);
+    }
+
+    // Self-signed certificate for the local TLS mock pool, along with its SHA-256 fingerprint
+    fn self_signed_acceptor() -> (tokio_rustls::TlsAcceptor, String) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert = certified.cert.der().clone();
+        let fingerprint = hex::encode(Sha256::digest(cert.as_ref()));
+        let key =
+            rustls::pki_types::PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into());
+
+        let config =
+            rustls::ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert], key)
+                .unwrap();
+
+        (
+            tokio_rustls::TlsAcceptor::from(Arc::new(config)),
+            fingerprint,
+        )
+    }
+
+    async fn connect_tls(port: u16, options: TlsOptions) -> Result<StratumClient, AppError> {
+        let client = StratumClient::new("localhost", port, "worker", "x").with_tls(options);
+        client.connect().await?;
+        Ok(client)
+    }
+
+    #[tokio::test]
+    async fn test_tls_certificate_checks() {
+        use tokio::io::AsyncReadExt;
+
+        let (acceptor, fingerprint) = self_signed_acceptor();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Mock pool serves the handshake on every TLS session the client accepts
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(tls_stream) = acceptor.accept(stream).await {
+                        let (mut tls_stream, _) =
+                            answer_handshake(tls_stream, "aa000001", "job-1").await;
+                        let mut buf = [0u8; 256];
+                        while matches!(tls_stream.read(&mut buf).await, Ok(n) if n > 0) {}
+                    }
+                });
+            }
+        });
+
+        // Self-signed certificates fail normal chain validation
+        let error = connect_tls(port, TlsOptions::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            error.to_string().contains("TLS handshake failed"),
+            "{}",
+            error
+        );
+
+        let wrong_pin = TlsOptions {
+            pinned_fingerprint: Some("00".repeat(32)),
+            accept_self_signed: true,
+        };
+        assert!(connect_tls(port, wrong_pin).await.is_err());
+
+        let bad_pin = TlsOptions {
+            pinned_fingerprint: Some("abc".to_string()),
+            accept_self_signed: false,
+        };
+        let error = connect_tls(port, bad_pin).await.err().unwrap();
+        assert!(error.to_string().contains("Invalid SHA-256"), "{}", error);
+
+        // Pins are accepted in the usual colon-separated uppercase form
+        let colon_pin = fingerprint
+            .to_uppercase()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":");
+        let pinned = TlsOptions {
+            pinned_fingerprint: Some(colon_pin),
+            accept_self_signed: false,
+        };
+        let client = connect_tls(port, pinned).await.unwrap();
+        let job = timeout(Duration::from_secs(5), client.get_next_job())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.job_id, "job-1");
+        client.disconnect().await.unwrap();
+
+        let self_signed = TlsOptions {
+            pinned_fingerprint: None,
+            accept_self_signed: true,
+        };
+        let client = connect_tls(port, self_signed).await.unwrap();
+        assert!(client.is_connected().await);
+        client.disconnect().await.unwrap();
+    }
 }
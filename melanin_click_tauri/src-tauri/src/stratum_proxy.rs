@@ -0,0 +1,931 @@
+use crate::stratum::{StratumClient, StratumJob};
+use crate::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
+use tracing::{debug, error, info, warn};
+
+// Bytes of the upstream extranonce2 reserved to tell downstream miners apart;
+// two bytes cover 65536 miners and leave the rest of the nonce space to each of them
+const DEFAULT_EXTRANONCE_PREFIX_SIZE: usize = 2;
+
+// Stratum error codes as used by common pool software
+const ERROR_OTHER: i64 = 20;
+const ERROR_UNAUTHORIZED: i64 = 24;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerStats {
+    pub connection_id: u64,
+    pub address: String,
+    pub worker_name: Option<String>,
+    pub extranonce1: String,
+    pub accepted_shares: u64,
+    pub rejected_shares: u64,
+    pub last_share_time: Option<chrono::DateTime<chrono::Utc>>,
+    pub connected_at: chrono::DateTime<chrono::Utc>,
+}
+
+// Upstream state every downstream miner is kept in sync with
+#[derive(Debug, Clone)]
+struct UpstreamWork {
+    job: StratumJob,
+    difficulty: f64,
+    extranonce1: String,
+    extranonce2_size: usize,
+}
+
+// State shared between the proxy handle, its accept loop and the downstream connections
+#[derive(Clone)]
+struct ProxyState {
+    upstream: Arc<StratumClient>,
+    extranonce_prefix_size: usize,
+    workers: Arc<Mutex<HashMap<u64, WorkerStats>>>,
+    // Extranonce prefixes currently handed out, so a reconnecting miner reuses the lowest free one
+    prefixes: Arc<Mutex<BTreeSet<u32>>>,
+    latest_work: Arc<Mutex<Option<UpstreamWork>>>,
+    work_sender: broadcast::Sender<UpstreamWork>,
+    next_connection_id: Arc<AtomicU64>,
+}
+
+// Stratum V1 proxy: serves any number of local miners over a single upstream pool connection
+pub struct StratumProxy {
+    state: ProxyState,
+    shutdown_sender: watch::Sender<bool>,
+}
+
+impl StratumProxy {
+    pub fn new(upstream: StratumClient) -> Self {
+        let (work_sender, _) = broadcast::channel(16);
+        let (shutdown_sender, _) = watch::channel(false);
+
+        Self {
+            state: ProxyState {
+                upstream: Arc::new(upstream),
+                extranonce_prefix_size: DEFAULT_EXTRANONCE_PREFIX_SIZE,
+                workers: Arc::new(Mutex::new(HashMap::new())),
+                prefixes: Arc::new(Mutex::new(BTreeSet::new())),
+                latest_work: Arc::new(Mutex::new(None)),
+                work_sender,
+                next_connection_id: Arc::new(AtomicU64::new(1)),
+            },
+            shutdown_sender,
+        }
+    }
+
+    pub fn with_extranonce_prefix_size(mut self, size: usize) -> Self {
+        self.state.extranonce_prefix_size = size;
+        self
+    }
+
+    // Connects upstream and starts serving miners; returns the bound local address
+    pub async fn start(&self, listen_addr: &str) -> Result<SocketAddr, AppError> {
+        if !(1..=4).contains(&self.state.extranonce_prefix_size) {
+            return Err(AppError::Stratum(format!(
+                "Extranonce prefix size must be between 1 and 4 bytes, got {}",
+                self.state.extranonce_prefix_size
+            )));
+        }
+
+        self.state.upstream.connect().await?;
+
+        let subscription = self
+            .state
+            .upstream
+            .get_subscription()
+            .await
+            .ok_or_else(|| {
+                AppError::Stratum("Upstream pool did not provide a subscription".to_string())
+            })?;
+        if subscription.extranonce2_size <= self.state.extranonce_prefix_size {
+            let _ = self.state.upstream.disconnect().await;
+            return Err(AppError::Stratum(format!(
+                "Upstream extranonce2 size {} leaves no room for a {} byte miner prefix",
+                subscription.extranonce2_size, self.state.extranonce_prefix_size
+            )));
+        }
+
+        let listener = match TcpListener::bind(listen_addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = self.state.upstream.disconnect().await;
+                return Err(e.into());
+            }
+        };
+        let local_addr = listener.local_addr()?;
+
+        tokio::spawn(Self::forward_jobs(
+            self.state.clone(),
+            self.shutdown_sender.subscribe(),
+        ));
+        tokio::spawn(Self::accept_loop(
+            self.state.clone(),
+            listener,
+            self.shutdown_sender.subscribe(),
+        ));
+
+        info!("Stratum proxy listening on {}", local_addr);
+        Ok(local_addr)
+    }
+
+    pub async fn stop(&self) -> Result<(), AppError> {
+        info!("Stopping Stratum proxy");
+        let _ = self.shutdown_sender.send(true);
+        self.state.upstream.disconnect().await
+    }
+
+    pub async fn get_worker_stats(&self) -> Vec<WorkerStats> {
+        let workers = self.state.workers.lock().await;
+        let mut stats: Vec<WorkerStats> = workers.values().cloned().collect();
+        stats.sort_by_key(|w| w.connection_id);
+        stats
+    }
+
+    // Pulls jobs off the upstream connection and fans them out to every miner
+    async fn forward_jobs(state: ProxyState, mut shutdown: watch::Receiver<bool>) {
+        loop {
+            let job = tokio::select! {
+                job = state.upstream.get_next_job() => job,
+                _ = shutdown.changed() => break,
+            };
+            let Some(job) = job else {
+                break;
+            };
+
+            // Difficulty and extranonce changes are picked up with the next job
+            let stats = state.upstream.get_stats().await;
+            let Some(subscription) = stats.subscription else {
+                warn!(
+                    "Dropping job {} without an upstream subscription",
+                    job.job_id
+                );
+                continue;
+            };
+
+            let work = UpstreamWork {
+                job,
+                difficulty: stats.difficulty,
+                extranonce1: subscription.extranonce1,
+                extranonce2_size: subscription.extranonce2_size,
+            };
+            debug!("Forwarding job {} to miners", work.job.job_id);
+            *state.latest_work.lock().await = Some(work.clone());
+            // Fails only while no miner is connected
+            let _ = state.work_sender.send(work);
+        }
+
+        debug!("Stratum proxy job forwarding stopped");
+    }
+
+    async fn accept_loop(
+        state: ProxyState,
+        listener: TcpListener,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = shutdown.changed() => break,
+            };
+
+            match accepted {
+                Ok((stream, address)) => {
+                    let state = state.clone();
+                    let shutdown = shutdown.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = Self::serve_miner(state, stream, address, shutdown).await {
+                            warn!("Miner {} disconnected: {}", address, e);
+                        }
+                    });
+                }
+                Err(e) => error!("Failed to accept miner connection: {}", e),
+            }
+        }
+
+        debug!("Stratum proxy accept loop stopped");
+    }
+
+    async fn serve_miner(
+        state: ProxyState,
+        stream: TcpStream,
+        address: SocketAddr,
+        shutdown: watch::Receiver<bool>,
+    ) -> Result<(), AppError> {
+        let Some(prefix) = state.allocate_prefix().await else {
+            return Err(AppError::Stratum(
+                "No free extranonce prefix left for another miner".to_string(),
+            ));
+        };
+
+        let connection_id = state.next_connection_id.fetch_add(1, Ordering::SeqCst);
+        info!(
+            "Miner {} connected as #{} with extranonce prefix {}",
+            address,
+            connection_id,
+            state.prefix_hex(prefix)
+        );
+
+        let mut miner = Miner {
+            state: state.clone(),
+            connection_id,
+            prefix,
+            outgoing: None,
+            subscribed: false,
+            extranonce_subscribed: false,
+            worker_name: None,
+            sent_difficulty: None,
+            sent_extranonce: None,
+        };
+        let result = miner.run(stream, address, shutdown).await;
+
+        state.workers.lock().await.remove(&connection_id);
+        state.prefixes.lock().await.remove(&prefix);
+        info!("Miner #{} ({}) left the proxy", connection_id, address);
+        result
+    }
+}
+
+impl ProxyState {
+    async fn allocate_prefix(&self) -> Option<u32> {
+        let capacity = 1u64 << (8 * self.extranonce_prefix_size);
+        let mut prefixes = self.prefixes.lock().await;
+        let prefix = (0..capacity)
+            .map(|p| p as u32)
+            .find(|p| !prefixes.contains(p))?;
+        prefixes.insert(prefix);
+        Some(prefix)
+    }
+
+    fn prefix_hex(&self, prefix: u32) -> String {
+        hex::encode(&prefix.to_be_bytes()[4 - self.extranonce_prefix_size..])
+    }
+
+    async fn record_share(&self, connection_id: u64, accepted: bool) {
+        let mut workers = self.workers.lock().await;
+        if let Some(worker) = workers.get_mut(&connection_id) {
+            if accepted {
+                worker.accepted_shares += 1;
+            } else {
+                worker.rejected_shares += 1;
+            }
+            worker.last_share_time = Some(chrono::Utc::now());
+        }
+    }
+}
+
+// One downstream miner connection
+struct Miner {
+    state: ProxyState,
+    connection_id: u64,
+    prefix: u32,
+    outgoing: Option<mpsc::Sender<String>>,
+    subscribed: bool,
+    // Opted in to mining.set_extranonce; other miners are disconnected on a change
+    extranonce_subscribed: bool,
+    worker_name: Option<String>,
+    // What this miner was last told, so updates are only sent on change
+    sent_difficulty: Option<f64>,
+    sent_extranonce: Option<(String, usize)>,
+}
+
+impl Miner {
+    async fn run(
+        &mut self,
+        stream: TcpStream,
+        address: SocketAddr,
+        mut shutdown: watch::Receiver<bool>,
+    ) -> Result<(), AppError> {
+        let (reader, writer) = stream.into_split();
+        let (outgoing, outgoing_receiver) = mpsc::channel(100);
+        self.outgoing = Some(outgoing);
+        tokio::spawn(Self::write_loop(writer, outgoing_receiver));
+
+        // Subscribe to work before registering, so no job slips between the two
+        let mut work_receiver = self.state.work_sender.subscribe();
+
+        let extranonce1 = self.extranonce().await.map(|(e, _)| e).unwrap_or_default();
+        self.state.workers.lock().await.insert(
+            self.connection_id,
+            WorkerStats {
+                connection_id: self.connection_id,
+                address: address.to_string(),
+                worker_name: None,
+                extranonce1,
+                accepted_shares: 0,
+                rejected_shares: 0,
+                last_share_time: None,
+                connected_at: chrono::Utc::now(),
+            },
+        );
+
+        let mut lines = BufReader::new(reader).lines();
+        loop {
+            tokio::select! {
+                line = lines.next_line() => match line? {
+                    Some(line) => self.handle_request(&line).await?,
+                    None => return Ok(()),
+                },
+                work = work_receiver.recv() => match work {
+                    Ok(work) => {
+                        if self.subscribed {
+                            self.send_work(&work).await?;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Miner #{} skipped {} jobs", self.connection_id, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                _ = shutdown.changed() => return Ok(()),
+            }
+        }
+    }
+
+    async fn write_loop(mut writer: OwnedWriteHalf, mut receiver: mpsc::Receiver<String>) {
+        while let Some(line) = receiver.recv().await {
+            if let Err(e) = writer.write_all(line.as_bytes()).await {
+                debug!("Failed to write to miner: {}", e);
+                break;
+            }
+        }
+    }
+
+    async fn send(&self, message: Value) -> Result<(), AppError> {
+        let outgoing = self
+            .outgoing
+            .as_ref()
+            .ok_or_else(|| AppError::Stratum("Miner connection is not open".to_string()))?;
+        outgoing
+            .send(format!("{}\n", message))
+            .await
+            .map_err(|_| AppError::Stratum("Miner connection closed".to_string()))
+    }
+
+    // Downstream extranonce1 is the upstream one followed by this miner's prefix
+    async fn extranonce(&self) -> Option<(String, usize)> {
+        let subscription = self.state.upstream.get_subscription().await?;
+        Some(self.downstream_extranonce(&subscription.extranonce1, subscription.extranonce2_size))
+    }
+
+    fn downstream_extranonce(&self, extranonce1: &str, extranonce2_size: usize) -> (String, usize) {
+        (
+            format!("{}{}", extranonce1, self.state.prefix_hex(self.prefix)),
+            extranonce2_size.saturating_sub(self.state.extranonce_prefix_size),
+        )
+    }
+
+    async fn handle_request(&mut self, line: &str) -> Result<(), AppError> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let request: Value = serde_json::from_str(line)
+            .map_err(|e| AppError::Stratum(format!("Failed to parse miner message: {}", e)))?;
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let params = request
+            .get("params")
+            .and_then(|p| p.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        match request.get("method").and_then(|m| m.as_str()) {
+            Some("mining.subscribe") => self.subscribe(id).await,
+            Some("mining.authorize") => {
+                let worker_name = params.first().and_then(|v| v.as_str()).unwrap_or("");
+                self.authorize(id, worker_name).await
+            }
+            Some("mining.submit") => self.submit(id, &params).await,
+            Some("mining.extranonce.subscribe") => {
+                self.extranonce_subscribed = true;
+                self.send(json!({"id": id, "result": true, "error": null}))
+                    .await
+            }
+            Some(method) => {
+                debug!(
+                    "Miner #{} sent unsupported method {}",
+                    self.connection_id, method
+                );
+                self.send(json!({
+                    "id": id,
+                    "result": null,
+                    "error": [ERROR_OTHER, "Method not supported by proxy", null]
+                }))
+                .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn subscribe(&mut self, id: Value) -> Result<(), AppError> {
+        let (extranonce1, extranonce2_size) = self
+            .extranonce()
+            .await
+            .ok_or_else(|| AppError::Stratum("Upstream pool is not subscribed".to_string()))?;
+        let subscription_id = format!("{:x}", self.connection_id);
+
+        self.send(json!({
+            "id": id,
+            "result": [
+                [
+                    ["mining.set_difficulty", subscription_id],
+                    ["mining.notify", subscription_id]
+                ],
+                extranonce1,
+                extranonce2_size
+            ],
+            "error": null
+        }))
+        .await?;
+        self.subscribed = true;
+        self.sent_extranonce = Some((extranonce1, extranonce2_size));
+
+        // Bring the miner up to date with the job everyone else is working on
+        let latest_work = self.state.latest_work.lock().await.clone();
+        if let Some(work) = latest_work {
+            self.send_work(&work).await?;
+        }
+        Ok(())
+    }
+
+    async fn authorize(&mut self, id: Value, worker_name: &str) -> Result<(), AppError> {
+        // The upstream pool only sees the proxy's own credentials, so any worker name is accepted
+        info!(
+            "Miner #{} authorized as {}",
+            self.connection_id, worker_name
+        );
+        self.worker_name = Some(worker_name.to_string());
+        if let Some(worker) = self.state.workers.lock().await.get_mut(&self.connection_id) {
+            worker.worker_name = Some(worker_name.to_string());
+        }
+        self.send(json!({"id": id, "result": true, "error": null}))
+            .await
+    }
+
+    // Shares are forwarded from a separate task so the miner keeps receiving jobs meanwhile
+    async fn submit(&self, id: Value, params: &[Value]) -> Result<(), AppError> {
+        if self.worker_name.is_none() {
+            return self
+                .reject(id, ERROR_UNAUTHORIZED, "Unauthorized worker")
+                .await;
+        }
+
+        let field = |index: usize| params.get(index).and_then(|v| v.as_str()).unwrap_or("");
+        let (job_id, extranonce2, ntime, nonce) = (field(1), field(2), field(3), field(4));
+
+        let expected_size = self.sent_extranonce.as_ref().map(|(_, size)| *size);
+        if Some(extranonce2.len()) != expected_size.map(|size| size * 2)
+            || hex::decode(extranonce2).is_err()
+        {
+            return self
+                .reject(id, ERROR_OTHER, "Invalid extranonce2 size")
+                .await;
+        }
+
+        let state = self.state.clone();
+        let connection_id = self.connection_id;
+        let outgoing = self.outgoing.clone();
+        let upstream_extranonce2 = format!("{}{}", state.prefix_hex(self.prefix), extranonce2);
+        let (job_id, ntime, nonce) = (job_id.to_string(), ntime.to_string(), nonce.to_string());
+
+        tokio::spawn(async move {
+            let reply = match state
+                .upstream
+                .submit_share(&job_id, &upstream_extranonce2, &ntime, &nonce)
+                .await
+            {
+                Ok(result) => {
+                    state.record_share(connection_id, result.accepted).await;
+                    if result.accepted {
+                        json!({"id": id, "result": true, "error": null})
+                    } else {
+                        json!({
+                            "id": id,
+                            "result": null,
+                            "error": [
+                                result.error_code.unwrap_or(ERROR_OTHER),
+                                result.reason.unwrap_or_else(|| "Share rejected".to_string()),
+                                null
+                            ]
+                        })
+                    }
+                }
+                Err(e) => {
+                    state.record_share(connection_id, false).await;
+                    json!({"id": id, "result": null, "error": [ERROR_OTHER, e.to_string(), null]})
+                }
+            };
+
+            if let Some(outgoing) = outgoing {
+                let _ = outgoing.send(format!("{}\n", reply)).await;
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn reject(&self, id: Value, code: i64, reason: &str) -> Result<(), AppError> {
+        self.state.record_share(self.connection_id, false).await;
+        self.send(json!({"id": id, "result": null, "error": [code, reason, null]}))
+            .await
+    }
+
+    async fn send_work(&mut self, work: &UpstreamWork) -> Result<(), AppError> {
+        let extranonce = self.downstream_extranonce(&work.extranonce1, work.extranonce2_size);
+        if self.sent_extranonce.as_ref() != Some(&extranonce) {
+            // Miners like stock cpuminer ignore set_extranonce and would keep mining on
+            // the old one; dropping them makes them subscribe again
+            if !self.extranonce_subscribed {
+                return Err(AppError::Stratum(
+                    "Upstream extranonce changed, closing the connection so the miner resubscribes"
+                        .to_string(),
+                ));
+            }
+            self.send(json!({
+                "id": null,
+                "method": "mining.set_extranonce",
+                "params": [extranonce.0, extranonce.1]
+            }))
+            .await?;
+            if let Some(worker) = self.state.workers.lock().await.get_mut(&self.connection_id) {
+                worker.extranonce1 = extranonce.0.clone();
+            }
+            self.sent_extranonce = Some(extranonce);
+        }
+
+        if self.sent_difficulty != Some(work.difficulty) {
+            self.send(json!({
+                "id": null,
+                "method": "mining.set_difficulty",
+                "params": [work.difficulty]
+            }))
+            .await?;
+            self.sent_difficulty = Some(work.difficulty);
+        }
+
+        let job = &work.job;
+        self.send(json!({
+            "id": null,
+            "method": "mining.notify",
+            "params": [
+                job.job_id,
+                job.previous_hash,
+                job.coinbase1,
+                job.coinbase2,
+                job.merkle_branches,
+                job.version,
+                job.nbits,
+                job.ntime,
+                job.clean_jobs
+            ]
+        }))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::ReconnectPolicy;
+    use tokio::io::Lines;
+    use tokio::net::tcp::OwnedReadHalf;
+    use tokio::time::{timeout, Duration};
+
+    // Mock pool: extranonce1 "f000" with 4 bytes of extranonce2, accepts shares with nonce
+    // 00000001 and rejects anything else. Returns the (extranonce2, nonce) of every submit
+    async fn serve_pool(listener: TcpListener, expected_submits: usize) -> Vec<(String, String)> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut submits = Vec::new();
+
+        while submits.len() < expected_submits {
+            let line = lines.next_line().await.unwrap().unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let params = request["params"].clone();
+            let mut replies = Vec::new();
+
+            match request["method"].as_str().unwrap() {
+                "mining.subscribe" => replies.push(json!({
+                    "id": request["id"],
+                    "result": [[["mining.notify", "1"]], "f000", 4],
+                    "error": null
+                })),
+                "mining.authorize" => {
+                    replies.push(json!({"id": request["id"], "result": true, "error": null}));
+                    replies.push(json!({
+                        "id": null, "method": "mining.set_difficulty", "params": [2]
+                    }));
+                    replies.push(json!({
+                        "id": null,
+                        "method": "mining.notify",
+                        "params": ["job-1", "00", "01", "02", [], "20000000", "1d00ffff", "5f5e1000", true]
+                    }));
+                }
+                "mining.submit" => {
+                    let nonce = params[4].as_str().unwrap().to_string();
+                    submits.push((params[2].as_str().unwrap().to_string(), nonce.clone()));
+                    replies.push(if nonce == "00000001" {
+                        json!({"id": request["id"], "result": true, "error": null})
+                    } else {
+                        json!({
+                            "id": request["id"],
+                            "result": null,
+                            "error": [23, "Low difficulty share", null]
+                        })
+                    });
+                }
+                _ => {}
+            }
+
+            for reply in replies {
+                writer
+                    .write_all(format!("{}\n", reply).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        }
+
+        submits
+    }
+
+    struct MockMiner {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+        next_id: u64,
+    }
+
+    impl MockMiner {
+        async fn connect(addr: SocketAddr) -> Self {
+            let (reader, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+            Self {
+                lines: BufReader::new(reader).lines(),
+                writer,
+                next_id: 1,
+            }
+        }
+
+        async fn request(&mut self, method: &str, params: Value) -> Value {
+            let id = self.next_id;
+            self.next_id += 1;
+            let request = json!({"id": id, "method": method, "params": params});
+            self.writer
+                .write_all(format!("{}\n", request).as_bytes())
+                .await
+                .unwrap();
+
+            loop {
+                let message = self.next_message().await;
+                if message["id"] == json!(id) {
+                    return message;
+                }
+            }
+        }
+
+        async fn next_message(&mut self) -> Value {
+            let line = timeout(Duration::from_secs(5), self.lines.next_line())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        async fn expect_method(&mut self, method: &str) -> Value {
+            loop {
+                let message = self.next_message().await;
+                if message["method"] == json!(method) {
+                    return message["params"].clone();
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_splits_extranonce_and_tracks_workers() {
+        let pool_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let pool_port = pool_listener.local_addr().unwrap().port();
+        let pool = tokio::spawn(serve_pool(pool_listener, 3));
+
+        let proxy = StratumProxy::new(StratumClient::new("127.0.0.1", pool_port, "farm", "x"))
+            .with_extranonce_prefix_size(1);
+        let addr = proxy.start("127.0.0.1:0").await.unwrap();
+
+        let mut rig_a = MockMiner::connect(addr).await;
+        let mut rig_b = MockMiner::connect(addr).await;
+
+        let subscribed_a = rig_a.request("mining.subscribe", json!(["cpuminer"])).await;
+        let subscribed_b = rig_b.request("mining.subscribe", json!(["cpuminer"])).await;
+        assert_eq!(subscribed_a["result"][1], json!("f00000"));
+        assert_eq!(subscribed_b["result"][1], json!("f00001"));
+        assert_eq!(subscribed_a["result"][2], json!(3));
+
+        // Both rigs receive the pool difficulty and the job the pool announced
+        for rig in [&mut rig_a, &mut rig_b] {
+            assert_eq!(
+                rig.expect_method("mining.set_difficulty").await,
+                json!([2.0])
+            );
+            assert_eq!(rig.expect_method("mining.notify").await[0], json!("job-1"));
+        }
+
+        let authorized = rig_a
+            .request("mining.authorize", json!(["rig-a", "x"]))
+            .await;
+        assert_eq!(authorized["result"], json!(true));
+        rig_b
+            .request("mining.authorize", json!(["rig-b", "x"]))
+            .await;
+
+        let accepted = rig_a
+            .request(
+                "mining.submit",
+                json!(["rig-a", "job-1", "aabbcc", "5f5e1000", "00000001"]),
+            )
+            .await;
+        assert_eq!(accepted["result"], json!(true));
+
+        let rejected = rig_b
+            .request(
+                "mining.submit",
+                json!(["rig-b", "job-1", "aabbcc", "5f5e1000", "00000002"]),
+            )
+            .await;
+        assert_eq!(rejected["error"][0], json!(23));
+        assert_eq!(rejected["error"][1], json!("Low difficulty share"));
+
+        let accepted = rig_b
+            .request(
+                "mining.submit",
+                json!(["rig-b", "job-1", "ddeeff", "5f5e1000", "00000001"]),
+            )
+            .await;
+        assert_eq!(accepted["result"], json!(true));
+
+        // A wrongly sized extranonce2 never reaches the pool
+        let invalid = rig_a
+            .request(
+                "mining.submit",
+                json!(["rig-a", "job-1", "aabbccdd", "5f5e1000", "00000001"]),
+            )
+            .await;
+        assert_eq!(invalid["error"][1], json!("Invalid extranonce2 size"));
+
+        // Every share went out on one upstream connection, tagged with the rig's prefix
+        let submits = timeout(Duration::from_secs(5), pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            submits,
+            vec![
+                ("00aabbcc".to_string(), "00000001".to_string()),
+                ("01aabbcc".to_string(), "00000002".to_string()),
+                ("01ddeeff".to_string(), "00000001".to_string()),
+            ]
+        );
+
+        let workers = proxy.get_worker_stats().await;
+        assert_eq!(workers.len(), 2);
+        assert_eq!(workers[0].worker_name.as_deref(), Some("rig-a"));
+        assert_eq!(workers[0].extranonce1, "f00000");
+        assert_eq!(
+            (workers[0].accepted_shares, workers[0].rejected_shares),
+            (1, 1)
+        );
+        assert_eq!(workers[1].worker_name.as_deref(), Some("rig-b"));
+        assert_eq!(
+            (workers[1].accepted_shares, workers[1].rejected_shares),
+            (1, 1)
+        );
+
+        // A disconnecting rig frees its prefix for the next one
+        drop(rig_a);
+        timeout(Duration::from_secs(5), async {
+            while proxy.get_worker_stats().await.len() != 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let mut rig_c = MockMiner::connect(addr).await;
+        let subscribed_c = rig_c.request("mining.subscribe", json!(["cpuminer"])).await;
+        assert_eq!(subscribed_c["result"][1], json!("f00000"));
+
+        proxy.stop().await.unwrap();
+    }
+
+    // Answers the handshake with `extranonce1`, then announces `job_id`
+    async fn serve_pool_session(
+        listener: &TcpListener,
+        extranonce1: &str,
+        job_id: &str,
+    ) -> TcpStream {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        for _ in 0..2 {
+            let request: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            let result = if request["method"] == "mining.subscribe" {
+                json!([[["mining.notify", "1"]], extranonce1, 4])
+            } else {
+                json!(true)
+            };
+            let reply = json!({"id": request["id"], "result": result, "error": null});
+            writer
+                .write_all(format!("{}\n", reply).as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let notify = json!({
+            "id": null,
+            "method": "mining.notify",
+            "params": [job_id, "00", "01", "02", [], "20000000", "1d00ffff", "5f5e1000", true]
+        });
+        writer
+            .write_all(format!("{}\n", notify).as_bytes())
+            .await
+            .unwrap();
+        lines.into_inner().into_inner().reunite(writer).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_proxy_handles_upstream_extranonce_change() {
+        let pool_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let pool_port = pool_listener.local_addr().unwrap().port();
+        let upstream = StratumClient::new("127.0.0.1", pool_port, "farm", "x")
+            .with_reconnect_policy(ReconnectPolicy {
+                initial_delay_ms: 10,
+                max_delay_ms: 50,
+                max_attempts: Some(5),
+            });
+        let proxy = StratumProxy::new(upstream).with_extranonce_prefix_size(1);
+
+        let (addr, first_session) = tokio::join!(
+            proxy.start("127.0.0.1:0"),
+            serve_pool_session(&pool_listener, "f000", "job-1")
+        );
+        let addr = addr.unwrap();
+
+        let mut opted_in = MockMiner::connect(addr).await;
+        let mut stock = MockMiner::connect(addr).await;
+        for rig in [&mut opted_in, &mut stock] {
+            rig.request("mining.subscribe", json!(["cpuminer"])).await;
+            assert_eq!(rig.expect_method("mining.notify").await[0], json!("job-1"));
+        }
+        let accepted = opted_in
+            .request("mining.extranonce.subscribe", json!([]))
+            .await;
+        assert_eq!(accepted["result"], json!(true));
+
+        // The pool comes back with a different extranonce1 after a reconnect
+        drop(first_session);
+        let _second_session = serve_pool_session(&pool_listener, "e000", "job-2").await;
+
+        assert_eq!(
+            opted_in.expect_method("mining.set_extranonce").await,
+            json!(["e00000", 3])
+        );
+        assert_eq!(
+            opted_in.expect_method("mining.notify").await[0],
+            json!("job-2")
+        );
+
+        // Stock cpuminer ignores set_extranonce, so it is disconnected instead
+        let closed = timeout(Duration::from_secs(5), async {
+            while let Some(line) = stock.lines.next_line().await.unwrap() {
+                assert!(!line.contains("mining.set_extranonce"), "{}", line);
+            }
+        })
+        .await;
+        assert!(closed.is_ok(), "stock miner was not disconnected");
+
+        let mut resubscribed = MockMiner::connect(addr).await;
+        let subscribed = resubscribed
+            .request("mining.subscribe", json!(["cpuminer"]))
+            .await;
+        assert_eq!(subscribed["result"][1], json!("e00001"));
+
+        proxy.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_proxy_rejects_prefix_wider_than_extranonce2() {
+        let pool_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let pool_port = pool_listener.local_addr().unwrap().port();
+        tokio::spawn(serve_pool(pool_listener, 1));
+
+        let proxy = StratumProxy::new(StratumClient::new("127.0.0.1", pool_port, "farm", "x"))
+            .with_extranonce_prefix_size(4);
+        let error = proxy.start("127.0.0.1:0").await.err().unwrap();
+        assert!(error.to_string().contains("leaves no room"), "{}", error);
+    }
+}
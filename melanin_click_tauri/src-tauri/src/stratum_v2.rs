@@ -0,0 +1,1543 @@
+use crate::stratum::{ShareResult, StratumJob, StratumStats};
+use crate::AppError;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use secp256k1::ellswift::{ElligatorSwift, ElligatorSwiftParty};
+use secp256k1::{schnorr, Keypair, Message, PublicKey, Secp256k1, XOnlyPublicKey};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::time::{timeout, Duration};
+use tracing::{debug, error, info, warn};
+
+// Noise_NX handshake as specified for Stratum V2: secp256k1 keys exchanged in
+// ElligatorSwift encoding, ChaCha20-Poly1305 and SHA-256
+const PROTOCOL_NAME: &[u8] = b"Noise_NX_Secp256k1+EllSwift_ChaChaPoly_SHA256";
+const MAC_LEN: usize = 16;
+const ELLSWIFT_LEN: usize = 64;
+const CERTIFICATE_LEN: usize = 74;
+// Responder reply: ephemeral key, encrypted static key, encrypted certificate
+const HANDSHAKE_REPLY_LEN: usize =
+    ELLSWIFT_LEN + (ELLSWIFT_LEN + MAC_LEN) + (CERTIFICATE_LEN + MAC_LEN);
+const CERTIFICATE_VERSION: u16 = 0;
+// Clock skew tolerated when checking the pool certificate validity window
+const CERTIFICATE_LEEWAY_SECS: u32 = 10;
+
+const FRAME_HEADER_LEN: usize = 6;
+// Noise messages are capped at 65535 bytes including the MAC
+const MAX_CHUNK_LEN: usize = 65535 - MAC_LEN;
+// Set in the extension type of messages addressed to a specific channel
+const CHANNEL_MSG_BIT: u16 = 0x8000;
+
+const MSG_SETUP_CONNECTION: u8 = 0x00;
+const MSG_SETUP_CONNECTION_SUCCESS: u8 = 0x01;
+const MSG_SETUP_CONNECTION_ERROR: u8 = 0x02;
+const MSG_RECONNECT: u8 = 0x04;
+const MSG_OPEN_STANDARD_MINING_CHANNEL: u8 = 0x10;
+const MSG_OPEN_STANDARD_MINING_CHANNEL_SUCCESS: u8 = 0x11;
+const MSG_OPEN_MINING_CHANNEL_ERROR: u8 = 0x12;
+const MSG_NEW_MINING_JOB: u8 = 0x15;
+const MSG_SUBMIT_SHARES_STANDARD: u8 = 0x1a;
+const MSG_SUBMIT_SHARES_SUCCESS: u8 = 0x1c;
+const MSG_SUBMIT_SHARES_ERROR: u8 = 0x1d;
+const MSG_SET_NEW_PREV_HASH: u8 = 0x20;
+const MSG_SET_TARGET: u8 = 0x21;
+
+const MINING_PROTOCOL: u8 = 0;
+const PROTOCOL_VERSION: u16 = 2;
+// SetupConnection flag: we only mine header-only jobs on standard channels
+const REQUIRES_STANDARD_JOBS: u32 = 0x1;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Frame {
+    extension_type: u16,
+    msg_type: u8,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    fn new(msg_type: u8, payload: Vec<u8>) -> Self {
+        Self {
+            extension_type: 0,
+            msg_type,
+            payload,
+        }
+    }
+
+    fn for_channel(msg_type: u8, payload: Vec<u8>) -> Self {
+        Self {
+            extension_type: CHANNEL_MSG_BIT,
+            msg_type,
+            payload,
+        }
+    }
+}
+
+// Serializes message fields in SV2 binary encoding (little-endian, length-prefixed strings)
+#[derive(Default)]
+struct PayloadWriter(Vec<u8>);
+
+impl PayloadWriter {
+    fn u8(mut self, value: u8) -> Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u16(mut self, value: u16) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn f32(mut self, value: f32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn str0_255(mut self, value: &str) -> Self {
+        let bytes = &value.as_bytes()[..value.len().min(255)];
+        self.0.push(bytes.len() as u8);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn u256(mut self, value: &[u8; 32]) -> Self {
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+struct PayloadReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PayloadReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AppError> {
+        if self.data.len() < len {
+            return Err(AppError::Stratum(
+                "Truncated Stratum V2 message".to_string(),
+            ));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, AppError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, AppError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, AppError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u256(&mut self) -> Result<[u8; 32], AppError> {
+        let mut value = [0u8; 32];
+        value.copy_from_slice(self.take(32)?);
+        Ok(value)
+    }
+
+    fn b0_32(&mut self) -> Result<Vec<u8>, AppError> {
+        let len = self.u8()? as usize;
+        if len > 32 {
+            return Err(AppError::Stratum(format!(
+                "Invalid B0_32 length in Stratum V2 message: {}",
+                len
+            )));
+        }
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn str0_255(&mut self) -> Result<String, AppError> {
+        let len = self.u8()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    // OPTION[u32] is a sequence of zero or one elements
+    fn option_u32(&mut self) -> Result<Option<u32>, AppError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.u32()?)),
+            len => Err(AppError::Stratum(format!(
+                "Invalid OPTION length in Stratum V2 message: {}",
+                len
+            ))),
+        }
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in data {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+// Noise HKDF with two outputs
+fn hkdf2(chaining_key: &[u8; 32], input_key_material: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let temp_key = hmac_sha256(chaining_key, &[input_key_material]);
+    let first = hmac_sha256(&temp_key, &[&[0x01]]);
+    let second = hmac_sha256(&temp_key, &[&first, &[0x02]]);
+    (first, second)
+}
+
+struct CipherState {
+    cipher: ChaCha20Poly1305,
+    nonce: u64,
+}
+
+impl CipherState {
+    fn new(key: [u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            nonce: 0,
+        }
+    }
+
+    // 32 zero bits followed by the little-endian message counter
+    fn next_nonce(&mut self) -> Nonce {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce += 1;
+        Nonce::clone_from_slice(&nonce)
+    }
+
+    fn encrypt(&mut self, ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+        let nonce = self.next_nonce();
+        self.cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: ad,
+                },
+            )
+            .map_err(|_| AppError::Stratum("Noise encryption failed".to_string()))
+    }
+
+    fn decrypt(&mut self, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AppError> {
+        let nonce = self.next_nonce();
+        self.cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: ad,
+                },
+            )
+            .map_err(|_| AppError::Stratum("Noise message failed authentication".to_string()))
+    }
+}
+
+// Symmetric state of the Noise handshake: handshake hash, chaining key and current cipher
+struct HandshakeState {
+    h: [u8; 32],
+    ck: [u8; 32],
+    cipher: Option<CipherState>,
+}
+
+impl HandshakeState {
+    fn new() -> Self {
+        let ck: [u8; 32] = Sha256::digest(PROTOCOL_NAME).into();
+        let h: [u8; 32] = Sha256::digest(ck).into();
+        Self {
+            h,
+            ck,
+            cipher: None,
+        }
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(self.h);
+        hasher.update(data);
+        self.h = hasher.finalize().into();
+    }
+
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (ck, key) = hkdf2(&self.ck, input_key_material);
+        self.ck = ck;
+        self.cipher = Some(CipherState::new(key));
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+        let h = self.h;
+        let ciphertext = match self.cipher.as_mut() {
+            Some(cipher) => cipher.encrypt(&h, plaintext)?,
+            None => plaintext.to_vec(),
+        };
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, AppError> {
+        let h = self.h;
+        let plaintext = match self.cipher.as_mut() {
+            Some(cipher) => cipher.decrypt(&h, ciphertext)?,
+            None => ciphertext.to_vec(),
+        };
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    // Transport ciphers: (initiator to responder, responder to initiator)
+    fn split(&self) -> (CipherState, CipherState) {
+        let (initiator_key, responder_key) = hkdf2(&self.ck, &[]);
+        (
+            CipherState::new(initiator_key),
+            CipherState::new(responder_key),
+        )
+    }
+}
+
+// ECDH over ElligatorSwift encodings using the BIP324 hash; the initiator's key always goes first
+fn ellswift_ecdh(
+    initiator: ElligatorSwift,
+    responder: ElligatorSwift,
+    secret: &Keypair,
+    party: ElligatorSwiftParty,
+) -> [u8; 32] {
+    ElligatorSwift::shared_secret(initiator, responder, secret.secret_key(), party, None)
+        .to_secret_bytes()
+}
+
+fn io_error(context: &str, e: std::io::Error) -> AppError {
+    AppError::Stratum(format!("{}: {}", context, e))
+}
+
+// Runs the initiator side of Noise_NX and returns the (send, receive) transport ciphers
+async fn noise_handshake(
+    stream: &mut TcpStream,
+    authority_key: Option<&XOnlyPublicKey>,
+) -> Result<(CipherState, CipherState), AppError> {
+    let secp = Secp256k1::new();
+    let ephemeral = Keypair::new(&secp, &mut secp256k1::rand::thread_rng());
+    let our_ephemeral = ElligatorSwift::from_pubkey(ephemeral.public_key());
+    let mut state = HandshakeState::new();
+
+    // -> e
+    state.mix_hash(&our_ephemeral.to_array());
+    state.encrypt_and_hash(&[])?;
+    stream
+        .write_all(&our_ephemeral.to_array())
+        .await
+        .map_err(|e| io_error("Failed to send Noise handshake", e))?;
+
+    // <- e, ee, s, es, certificate
+    let mut reply = [0u8; HANDSHAKE_REPLY_LEN];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .map_err(|e| io_error("Failed to read Noise handshake", e))?;
+    let (their_ephemeral, rest) = reply.split_at(ELLSWIFT_LEN);
+    let (encrypted_static, encrypted_certificate) = rest.split_at(ELLSWIFT_LEN + MAC_LEN);
+
+    state.mix_hash(their_ephemeral);
+    let their_ephemeral = ElligatorSwift::from_array(to_ellswift_bytes(their_ephemeral));
+    state.mix_key(&ellswift_ecdh(
+        our_ephemeral,
+        their_ephemeral,
+        &ephemeral,
+        ElligatorSwiftParty::A,
+    ));
+
+    let their_static = state.decrypt_and_hash(encrypted_static)?;
+    let their_static = ElligatorSwift::from_array(to_ellswift_bytes(&their_static));
+    state.mix_key(&ellswift_ecdh(
+        our_ephemeral,
+        their_static,
+        &ephemeral,
+        ElligatorSwiftParty::A,
+    ));
+
+    let certificate = state.decrypt_and_hash(encrypted_certificate)?;
+    let static_key = PublicKey::from_ellswift(their_static).x_only_public_key().0;
+    verify_certificate(&certificate, &static_key, authority_key, unix_now())?;
+
+    Ok(state.split())
+}
+
+fn to_ellswift_bytes(bytes: &[u8]) -> [u8; ELLSWIFT_LEN] {
+    let mut array = [0u8; ELLSWIFT_LEN];
+    array.copy_from_slice(bytes);
+    array
+}
+
+fn unix_now() -> u32 {
+    chrono::Utc::now().timestamp() as u32
+}
+
+// The pool certificate binds its static key to the pool's authority key:
+// version (u16), valid_from (u32), not_valid_after (u32), then a Schnorr signature
+// over SHA-256 of those fields followed by the x-only static key
+fn verify_certificate(
+    certificate: &[u8],
+    static_key: &XOnlyPublicKey,
+    authority_key: Option<&XOnlyPublicKey>,
+    now: u32,
+) -> Result<(), AppError> {
+    let mut fields = PayloadReader::new(certificate);
+    let version = fields.u16()?;
+    let valid_from = fields.u32()?;
+    let not_valid_after = fields.u32()?;
+    let signature = fields.take(64)?;
+
+    if version != CERTIFICATE_VERSION {
+        return Err(AppError::Stratum(format!(
+            "Unsupported pool certificate version {}",
+            version
+        )));
+    }
+
+    let Some(authority_key) = authority_key else {
+        warn!("No authority key configured for Stratum V2 pool, certificate not verified");
+        return Ok(());
+    };
+
+    if valid_from.saturating_sub(CERTIFICATE_LEEWAY_SECS) > now
+        || not_valid_after.saturating_add(CERTIFICATE_LEEWAY_SECS) < now
+    {
+        return Err(AppError::Stratum(
+            "Pool certificate is outside its validity period".to_string(),
+        ));
+    }
+
+    let digest = Sha256::digest([&certificate[..10], &static_key.serialize()[..]].concat());
+    let message = Message::from_digest_slice(&digest)
+        .map_err(|e| AppError::Stratum(format!("Invalid certificate digest: {}", e)))?;
+    let signature = schnorr::Signature::from_slice(signature)
+        .map_err(|e| AppError::Stratum(format!("Invalid certificate signature: {}", e)))?;
+
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, &message, authority_key)
+        .map_err(|_| {
+            AppError::Stratum(
+                "Pool certificate is not signed by the configured authority key".to_string(),
+            )
+        })
+}
+
+// Accepts the authority key as 64 hex characters or in the base58check form pools publish
+// (two version bytes followed by the x-only key)
+pub fn parse_authority_key(key: &str) -> Result<XOnlyPublicKey, AppError> {
+    let invalid = || AppError::Stratum(format!("Invalid pool authority key: {}", key));
+
+    let key_bytes = match hex::decode(key) {
+        Ok(bytes) if bytes.len() == 32 => bytes,
+        _ => {
+            let data = bs58::decode(key).into_vec().map_err(|_| invalid())?;
+            if data.len() != 38 {
+                return Err(invalid());
+            }
+            let (payload, checksum) = data.split_at(34);
+            if Sha256::digest(Sha256::digest(payload))[..4] != *checksum {
+                return Err(invalid());
+            }
+            payload[2..].to_vec()
+        }
+    };
+
+    XOnlyPublicKey::from_slice(&key_bytes).map_err(|_| invalid())
+}
+
+// Parse stratum2+tcp://host:port with an optional /authority_key path
+pub fn parse_sv2_url(url: &str) -> Result<(String, u16, Option<String>), AppError> {
+    let rest = url
+        .strip_prefix("stratum2+tcp://")
+        .ok_or_else(|| AppError::Stratum("Invalid Stratum V2 URL format".to_string()))?;
+
+    let (endpoint, authority_key) = match rest.split_once('/') {
+        Some((endpoint, "")) => (endpoint, None),
+        Some((endpoint, key)) => (endpoint, Some(key.to_string())),
+        None => (rest, None),
+    };
+
+    let (host, port) = endpoint
+        .split_once(':')
+        .ok_or_else(|| AppError::Stratum("Invalid URL format - expected host:port".to_string()))?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| AppError::Stratum("Invalid port number".to_string()))?;
+
+    Ok((host.to_string(), port, authority_key))
+}
+
+fn encrypt_frame(cipher: &mut CipherState, frame: &Frame) -> Result<Vec<u8>, AppError> {
+    let length = (frame.payload.len() as u32).to_le_bytes();
+    let mut header = Vec::with_capacity(FRAME_HEADER_LEN);
+    header.extend_from_slice(&frame.extension_type.to_le_bytes());
+    header.push(frame.msg_type);
+    header.extend_from_slice(&length[..3]);
+
+    // The header is sealed on its own so the reader knows how much payload follows
+    let mut encrypted = cipher.encrypt(&[], &header)?;
+    for chunk in frame.payload.chunks(MAX_CHUNK_LEN) {
+        encrypted.extend(cipher.encrypt(&[], chunk)?);
+    }
+    Ok(encrypted)
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    cipher: &mut CipherState,
+) -> Result<Frame, AppError> {
+    let mut header = [0u8; FRAME_HEADER_LEN + MAC_LEN];
+    reader
+        .read_exact(&mut header)
+        .await
+        .map_err(|e| io_error("Connection closed by pool", e))?;
+    let header = cipher.decrypt(&[], &header)?;
+
+    let extension_type = u16::from_le_bytes([header[0], header[1]]);
+    let msg_type = header[2];
+    let length = u32::from_le_bytes([header[3], header[4], header[5], 0]) as usize;
+
+    let mut payload = Vec::with_capacity(length);
+    while payload.len() < length {
+        let chunk_len = (length - payload.len()).min(MAX_CHUNK_LEN);
+        let mut chunk = vec![0u8; chunk_len + MAC_LEN];
+        reader
+            .read_exact(&mut chunk)
+            .await
+            .map_err(|e| io_error("Connection closed by pool", e))?;
+        payload.extend(cipher.decrypt(&[], &chunk)?);
+    }
+
+    Ok(Frame {
+        extension_type,
+        msg_type,
+        payload,
+    })
+}
+
+// Pool difficulty relative to the difficulty-1 target 0xffff * 2^208
+fn target_to_difficulty(target: &[u8; 32]) -> f64 {
+    let target = target
+        .iter()
+        .rev()
+        .fold(0.0, |value, byte| value * 256.0 + *byte as f64);
+    if target == 0.0 {
+        return 0.0;
+    }
+    65535.0 * 2f64.powi(208) / target
+}
+
+#[derive(Debug, Clone)]
+struct MiningJob {
+    job_id: u32,
+    // None marks a future job, activated by a later SetNewPrevHash
+    min_ntime: Option<u32>,
+    version: u32,
+    merkle_root: [u8; 32],
+}
+
+#[derive(Debug, Clone)]
+struct PrevHash {
+    job_id: u32,
+    prev_hash: [u8; 32],
+    min_ntime: u32,
+    nbits: u32,
+}
+
+#[derive(Default)]
+struct JobState {
+    prev_hash: Option<PrevHash>,
+    future_jobs: HashMap<u32, MiningJob>,
+    active_jobs: HashMap<u32, MiningJob>,
+}
+
+// State shared between the client handle and its message loop
+#[derive(Clone)]
+struct Sv2Session {
+    stats: Arc<Mutex<StratumStats>>,
+    channel_id: Arc<Mutex<Option<u32>>>,
+    jobs: Arc<Mutex<JobState>>,
+    // Shares awaiting the pool's verdict, keyed by sequence number
+    pending_shares: Arc<Mutex<HashMap<u32, oneshot::Sender<ShareResult>>>>,
+    outgoing: Arc<Mutex<Option<mpsc::Sender<Frame>>>>,
+    job_sender: mpsc::Sender<StratumJob>,
+}
+
+pub struct Sv2Client {
+    pool_host: String,
+    pool_port: u16,
+    user_identity: String,
+    authority_key: Option<String>,
+    nominal_hash_rate: f32,
+    session: Sv2Session,
+    sequence_number: AtomicU32,
+    job_receiver: Arc<Mutex<mpsc::Receiver<StratumJob>>>,
+    shutdown_sender: mpsc::Sender<()>,
+    shutdown_receiver: Arc<Mutex<Option<mpsc::Receiver<()>>>>,
+}
+
+impl Sv2Client {
+    pub fn new(pool_host: &str, pool_port: u16, user_identity: &str) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel(100);
+        let (shutdown_sender, shutdown_receiver) = mpsc::channel(1);
+
+        let stats = StratumStats {
+            connected: false,
+            pool_url: pool_host.to_string(),
+            worker_name: user_identity.to_string(),
+            difficulty: 1.0,
+            subscription: None,
+            accepted_shares: 0,
+            rejected_shares: 0,
+            last_share_time: None,
+            last_reject_code: None,
+            last_reject_reason: None,
+            connection_time: chrono::Utc::now(),
+            ping: None,
+            reconnect_count: 0,
+            last_error: None,
+        };
+
+        let session = Sv2Session {
+            stats: Arc::new(Mutex::new(stats)),
+            channel_id: Arc::new(Mutex::new(None)),
+            jobs: Arc::new(Mutex::new(JobState::default())),
+            pending_shares: Arc::new(Mutex::new(HashMap::new())),
+            outgoing: Arc::new(Mutex::new(None)),
+            job_sender,
+        };
+
+        Self {
+            pool_host: pool_host.to_string(),
+            pool_port,
+            user_identity: user_identity.to_string(),
+            authority_key: None,
+            nominal_hash_rate: 1_000_000.0,
+            session,
+            sequence_number: AtomicU32::new(0),
+            job_receiver: Arc::new(Mutex::new(job_receiver)),
+            shutdown_sender,
+            shutdown_receiver: Arc::new(Mutex::new(Some(shutdown_receiver))),
+        }
+    }
+
+    pub fn from_url(url: &str, user_identity: &str) -> Result<Self, AppError> {
+        let (host, port, authority_key) = parse_sv2_url(url)?;
+        let client = Self::new(&host, port, user_identity);
+
+        match authority_key {
+            Some(key) => Ok(client.with_authority_key(&key)),
+            None => Ok(client),
+        }
+    }
+
+    // Key the pool's Noise certificate must be signed with
+    pub fn with_authority_key(mut self, key: &str) -> Self {
+        self.authority_key = Some(key.to_string());
+        self
+    }
+
+    // Hash rate (H/s) reported when opening the channel; pools use it to pick the first target
+    pub fn with_nominal_hash_rate(mut self, hash_rate: f32) -> Self {
+        self.nominal_hash_rate = hash_rate;
+        self
+    }
+
+    pub async fn connect(&self) -> Result<(), AppError> {
+        if self.shutdown_receiver.lock().await.is_none() {
+            return Err(AppError::Stratum(
+                "Stratum V2 client has already been started".to_string(),
+            ));
+        }
+
+        let authority_key = self
+            .authority_key
+            .as_deref()
+            .map(parse_authority_key)
+            .transpose()?;
+
+        info!(
+            "Connecting to Stratum V2 pool: {}:{}",
+            self.pool_host, self.pool_port
+        );
+        let address = format!("{}:{}", self.pool_host, self.pool_port);
+        let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(&address))
+            .await
+            .map_err(|_| AppError::Stratum("Connection timeout".to_string()))?
+            .map_err(|e| AppError::Stratum(format!("Failed to connect: {}", e)))?;
+
+        let (mut encryptor, mut decryptor) = timeout(
+            RESPONSE_TIMEOUT,
+            noise_handshake(&mut stream, authority_key.as_ref()),
+        )
+        .await
+        .map_err(|_| AppError::Stratum("Noise handshake timeout".to_string()))??;
+
+        let (mut reader, mut writer) = stream.into_split();
+
+        // Connection setup and channel opening complete before the message loop starts
+        let reply = Self::exchange(
+            &mut reader,
+            &mut writer,
+            &mut encryptor,
+            &mut decryptor,
+            Frame::new(MSG_SETUP_CONNECTION, self.setup_connection_payload()),
+        )
+        .await?;
+        let mut fields = PayloadReader::new(&reply.payload);
+        match reply.msg_type {
+            MSG_SETUP_CONNECTION_SUCCESS => {
+                let used_version = fields.u16()?;
+                debug!("Stratum V2 connection set up, version {}", used_version);
+            }
+            MSG_SETUP_CONNECTION_ERROR => {
+                let _flags = fields.u32()?;
+                return Err(AppError::Stratum(format!(
+                    "Pool rejected connection setup: {}",
+                    fields.str0_255()?
+                )));
+            }
+            other => return Err(Self::unexpected_reply("SetupConnection", other)),
+        }
+
+        let reply = Self::exchange(
+            &mut reader,
+            &mut writer,
+            &mut encryptor,
+            &mut decryptor,
+            Frame::new(
+                MSG_OPEN_STANDARD_MINING_CHANNEL,
+                self.open_channel_payload(),
+            ),
+        )
+        .await?;
+        let mut fields = PayloadReader::new(&reply.payload);
+        match reply.msg_type {
+            MSG_OPEN_STANDARD_MINING_CHANNEL_SUCCESS => {
+                let _request_id = fields.u32()?;
+                let channel_id = fields.u32()?;
+                let target = fields.u256()?;
+                let _extranonce_prefix = fields.b0_32()?;
+
+                info!("Opened Stratum V2 standard channel {}", channel_id);
+                *self.session.channel_id.lock().await = Some(channel_id);
+                self.session.stats.lock().await.difficulty = target_to_difficulty(&target);
+            }
+            MSG_OPEN_MINING_CHANNEL_ERROR => {
+                let _request_id = fields.u32()?;
+                return Err(AppError::Stratum(format!(
+                    "Pool rejected mining channel: {}",
+                    fields.str0_255()?
+                )));
+            }
+            other => return Err(Self::unexpected_reply("OpenStandardMiningChannel", other)),
+        }
+
+        let (outgoing_sender, outgoing_receiver) = mpsc::channel(100);
+        tokio::spawn(Self::write_loop(writer, encryptor, outgoing_receiver));
+        *self.session.outgoing.lock().await = Some(outgoing_sender);
+
+        {
+            let mut stats = self.session.stats.lock().await;
+            stats.connected = true;
+            stats.connection_time = chrono::Utc::now();
+        }
+
+        let shutdown_receiver = self
+            .shutdown_receiver
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| AppError::Stratum("Shutdown receiver already taken".to_string()))?;
+        tokio::spawn(Self::read_loop(
+            self.session.clone(),
+            reader,
+            decryptor,
+            shutdown_receiver,
+        ));
+
+        info!(
+            "Connected to Stratum V2 pool successfully: {}",
+            self.pool_host
+        );
+        Ok(())
+    }
+
+    fn setup_connection_payload(&self) -> Vec<u8> {
+        PayloadWriter::default()
+            .u8(MINING_PROTOCOL)
+            .u16(PROTOCOL_VERSION)
+            .u16(PROTOCOL_VERSION)
+            .u32(REQUIRES_STANDARD_JOBS)
+            .str0_255(&self.pool_host)
+            .u16(self.pool_port)
+            .str0_255("Melanin Click")
+            .str0_255("")
+            .str0_255(env!("CARGO_PKG_VERSION"))
+            .str0_255("")
+            .finish()
+    }
+
+    fn open_channel_payload(&self) -> Vec<u8> {
+        PayloadWriter::default()
+            .u32(1)
+            .str0_255(&self.user_identity)
+            .f32(self.nominal_hash_rate)
+            .u256(&[0xff; 32])
+            .finish()
+    }
+
+    fn unexpected_reply(request: &str, msg_type: u8) -> AppError {
+        AppError::Stratum(format!(
+            "Unexpected Stratum V2 message {:#04x} in reply to {}",
+            msg_type, request
+        ))
+    }
+
+    // Send a handshake-phase request and wait for the pool's reply
+    async fn exchange(
+        reader: &mut OwnedReadHalf,
+        writer: &mut OwnedWriteHalf,
+        encryptor: &mut CipherState,
+        decryptor: &mut CipherState,
+        request: Frame,
+    ) -> Result<Frame, AppError> {
+        writer
+            .write_all(&encrypt_frame(encryptor, &request)?)
+            .await
+            .map_err(|e| io_error("Failed to send message", e))?;
+
+        timeout(RESPONSE_TIMEOUT, read_frame(reader, decryptor))
+            .await
+            .map_err(|_| AppError::Stratum("Timed out waiting for pool reply".to_string()))?
+    }
+
+    async fn write_loop(
+        mut writer: OwnedWriteHalf,
+        mut encryptor: CipherState,
+        mut outgoing: mpsc::Receiver<Frame>,
+    ) {
+        while let Some(frame) = outgoing.recv().await {
+            let result = match encrypt_frame(&mut encryptor, &frame) {
+                Ok(bytes) => writer
+                    .write_all(&bytes)
+                    .await
+                    .map_err(|e| io_error("Failed to write to pool", e)),
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = result {
+                warn!("Stratum V2 writer stopped: {}", e);
+                break;
+            }
+        }
+
+        let _ = writer.shutdown().await;
+    }
+
+    async fn read_loop(
+        session: Sv2Session,
+        mut reader: OwnedReadHalf,
+        mut decryptor: CipherState,
+        mut shutdown_receiver: mpsc::Receiver<()>,
+    ) {
+        loop {
+            tokio::select! {
+                frame = read_frame(&mut reader, &mut decryptor) => {
+                    match frame {
+                        Ok(frame) => {
+                            if let Err(e) = session.handle_frame(frame).await {
+                                error!("Error handling Stratum V2 message: {}", e);
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Stratum V2 connection lost: {}", e);
+                            session.stats.lock().await.last_error = Some(e.to_string());
+                            break;
+                        }
+                    }
+                }
+                _ = shutdown_receiver.recv() => {
+                    info!("Received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        session.detach().await;
+    }
+
+    pub async fn submit_share(
+        &self,
+        job_id: u32,
+        nonce: u32,
+        ntime: u32,
+        version: u32,
+    ) -> Result<ShareResult, AppError> {
+        let channel_id = self
+            .session
+            .channel_id
+            .lock()
+            .await
+            .ok_or_else(|| AppError::Stratum("Not connected".to_string()))?;
+        let sequence_number = self.sequence_number.fetch_add(1, Ordering::SeqCst);
+        info!(
+            "Submitting share: job_id={}, nonce={:08x}, sequence={}",
+            job_id, nonce, sequence_number
+        );
+
+        let (responder, receiver) = oneshot::channel();
+        self.session
+            .pending_shares
+            .lock()
+            .await
+            .insert(sequence_number, responder);
+
+        let payload = PayloadWriter::default()
+            .u32(channel_id)
+            .u32(sequence_number)
+            .u32(job_id)
+            .u32(nonce)
+            .u32(ntime)
+            .u32(version)
+            .finish();
+        if let Err(e) = self
+            .session
+            .send(Frame::for_channel(MSG_SUBMIT_SHARES_STANDARD, payload))
+            .await
+        {
+            self.session
+                .pending_shares
+                .lock()
+                .await
+                .remove(&sequence_number);
+            return Err(e);
+        }
+
+        match timeout(RESPONSE_TIMEOUT, receiver).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => Err(AppError::Stratum(
+                "Connection closed before share was acknowledged".to_string(),
+            )),
+            Err(_) => {
+                self.session
+                    .pending_shares
+                    .lock()
+                    .await
+                    .remove(&sequence_number);
+                Err(AppError::Stratum(
+                    "Timed out waiting for share acknowledgement".to_string(),
+                ))
+            }
+        }
+    }
+
+    // Block version of an active job, needed to submit shares against it
+    pub async fn job_version(&self, job_id: u32) -> Option<u32> {
+        let jobs = self.session.jobs.lock().await;
+        jobs.active_jobs.get(&job_id).map(|job| job.version)
+    }
+
+    pub async fn get_stats(&self) -> StratumStats {
+        let stats = self.session.stats.lock().await;
+        stats.clone()
+    }
+
+    pub async fn get_next_job(&self) -> Option<StratumJob> {
+        let mut receiver = self.job_receiver.lock().await;
+        receiver.recv().await
+    }
+
+    pub async fn disconnect(&self) -> Result<(), AppError> {
+        info!("Disconnecting from Stratum V2 pool");
+
+        if let Err(e) = self.shutdown_sender.send(()).await {
+            warn!("Failed to send shutdown signal: {}", e);
+        }
+        self.session.detach().await;
+
+        info!("Disconnected from Stratum V2 pool");
+        Ok(())
+    }
+
+    pub async fn is_connected(&self) -> bool {
+        let stats = self.session.stats.lock().await;
+        stats.connected
+    }
+}
+
+impl Sv2Session {
+    async fn send(&self, frame: Frame) -> Result<(), AppError> {
+        let outgoing = self
+            .outgoing
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| AppError::Stratum("Not connected".to_string()))?;
+
+        outgoing
+            .send(frame)
+            .await
+            .map_err(|_| AppError::Stratum("Failed to send message: connection closed".to_string()))
+    }
+
+    async fn detach(&self) {
+        // Dropping the sender stops the writer task, dropping responders fails waiting shares
+        self.outgoing.lock().await.take();
+        self.pending_shares.lock().await.clear();
+        self.channel_id.lock().await.take();
+        self.stats.lock().await.connected = false;
+    }
+
+    async fn handle_frame(&self, frame: Frame) -> Result<(), AppError> {
+        if frame.extension_type & !CHANNEL_MSG_BIT != 0 {
+            debug!(
+                "Ignoring message for protocol extension {:#06x}",
+                frame.extension_type & !CHANNEL_MSG_BIT
+            );
+            return Ok(());
+        }
+
+        let mut fields = PayloadReader::new(&frame.payload);
+        match frame.msg_type {
+            MSG_NEW_MINING_JOB => {
+                let _channel_id = fields.u32()?;
+                let job = MiningJob {
+                    job_id: fields.u32()?,
+                    min_ntime: fields.option_u32()?,
+                    version: fields.u32()?,
+                    merkle_root: fields.u256()?,
+                };
+                self.new_mining_job(job).await;
+            }
+            MSG_SET_NEW_PREV_HASH => {
+                let _channel_id = fields.u32()?;
+                let prev_hash = PrevHash {
+                    job_id: fields.u32()?,
+                    prev_hash: fields.u256()?,
+                    min_ntime: fields.u32()?,
+                    nbits: fields.u32()?,
+                };
+                self.set_new_prev_hash(prev_hash).await;
+            }
+            MSG_SET_TARGET => {
+                let _channel_id = fields.u32()?;
+                let target = fields.u256()?;
+                let difficulty = target_to_difficulty(&target);
+                info!("Pool set new difficulty: {}", difficulty);
+                self.stats.lock().await.difficulty = difficulty;
+            }
+            MSG_SUBMIT_SHARES_SUCCESS => {
+                let _channel_id = fields.u32()?;
+                let last_sequence_number = fields.u32()?;
+                self.shares_accepted(last_sequence_number).await;
+            }
+            MSG_SUBMIT_SHARES_ERROR => {
+                let _channel_id = fields.u32()?;
+                let sequence_number = fields.u32()?;
+                let error_code = fields.str0_255()?;
+                self.share_rejected(sequence_number, error_code).await;
+            }
+            MSG_RECONNECT => {
+                let new_host = fields.str0_255()?;
+                let new_port = fields.u16()?;
+                warn!(
+                    "Pool asked to reconnect to {}:{}, which this client does not follow",
+                    new_host, new_port
+                );
+            }
+            other => {
+                debug!("Ignoring Stratum V2 message type {:#04x}", other);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn new_mining_job(&self, job: MiningJob) {
+        let mut jobs = self.jobs.lock().await;
+        let Some(ntime) = job.min_ntime else {
+            jobs.future_jobs.insert(job.job_id, job);
+            return;
+        };
+        let Some(prev_hash) = jobs.prev_hash.clone() else {
+            warn!("Job {} arrived before any previous block hash", job.job_id);
+            return;
+        };
+
+        jobs.active_jobs.insert(job.job_id, job.clone());
+        drop(jobs);
+        self.announce(&job, &prev_hash, ntime, false).await;
+    }
+
+    // A new block: the referenced future job becomes the only valid one
+    async fn set_new_prev_hash(&self, prev_hash: PrevHash) {
+        let mut jobs = self.jobs.lock().await;
+        jobs.prev_hash = Some(prev_hash.clone());
+        jobs.active_jobs.clear();
+
+        let activated = jobs.future_jobs.remove(&prev_hash.job_id);
+        jobs.future_jobs.clear();
+
+        match activated {
+            Some(job) => {
+                jobs.active_jobs.insert(job.job_id, job.clone());
+                drop(jobs);
+                self.announce(&job, &prev_hash, prev_hash.min_ntime, true)
+                    .await;
+            }
+            None => warn!(
+                "New previous hash refers to unknown job {}",
+                prev_hash.job_id
+            ),
+        }
+    }
+
+    // Standard channel jobs are header-only: the pool supplies the merkle root, so the
+    // coinbase fields stay empty. Hashes are hex in wire order, as they go into the header
+    async fn announce(&self, job: &MiningJob, prev_hash: &PrevHash, ntime: u32, clean_jobs: bool) {
+        let stratum_job = StratumJob {
+            job_id: job.job_id.to_string(),
+            previous_hash: hex::encode(prev_hash.prev_hash),
+            coinbase1: String::new(),
+            coinbase2: String::new(),
+            merkle_branches: Vec::new(),
+            version: format!("{:08x}", job.version),
+            nbits: format!("{:08x}", prev_hash.nbits),
+            ntime: format!("{:08x}", ntime),
+            clean_jobs,
+            merkle_root: Some(hex::encode(job.merkle_root)),
+        };
+
+        info!("Received new job: {}", stratum_job.job_id);
+        if let Err(e) = self.job_sender.send(stratum_job).await {
+            error!("Failed to send job: {}", e);
+        }
+    }
+
+    // Acknowledgements may be batched: everything up to the sequence number is accepted
+    async fn shares_accepted(&self, last_sequence_number: u32) {
+        let mut pending = self.pending_shares.lock().await;
+        let accepted: Vec<u32> = pending
+            .keys()
+            .copied()
+            .filter(|sequence_number| *sequence_number <= last_sequence_number)
+            .collect();
+
+        let mut stats = self.stats.lock().await;
+        for sequence_number in accepted {
+            if let Some(responder) = pending.remove(&sequence_number) {
+                stats.accepted_shares += 1;
+                stats.last_share_time = Some(chrono::Utc::now());
+                let _ = responder.send(ShareResult {
+                    accepted: true,
+                    error_code: None,
+                    reason: None,
+                });
+            }
+        }
+    }
+
+    async fn share_rejected(&self, sequence_number: u32, error_code: String) {
+        warn!("Share {} rejected by pool: {}", sequence_number, error_code);
+
+        let responder = self.pending_shares.lock().await.remove(&sequence_number);
+        {
+            let mut stats = self.stats.lock().await;
+            stats.rejected_shares += 1;
+            stats.last_reject_code = None;
+            stats.last_reject_reason = Some(error_code.clone());
+        }
+
+        if let Some(responder) = responder {
+            let _ = responder.send(ShareResult {
+                accepted: false,
+                error_code: None,
+                reason: Some(error_code),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn keypair() -> Keypair {
+        Keypair::new(&Secp256k1::new(), &mut secp256k1::rand::thread_rng())
+    }
+
+    // Certificate over the pool's static key, signed by its authority key
+    fn certificate(static_key: &Keypair, authority: &Keypair) -> Vec<u8> {
+        let now = unix_now();
+        let mut certificate = PayloadWriter::default()
+            .u16(CERTIFICATE_VERSION)
+            .u32(now - 60)
+            .u32(now + 3600)
+            .finish();
+        let digest = Sha256::digest(
+            [
+                &certificate[..],
+                &static_key.x_only_public_key().0.serialize()[..],
+            ]
+            .concat(),
+        );
+        let message = Message::from_digest_slice(&digest).unwrap();
+        let signature = Secp256k1::new().sign_schnorr(&message, authority);
+        certificate.extend_from_slice(signature.as_ref());
+        certificate
+    }
+
+    // Responder side of Noise_NX as a pool runs it; returns the pool's (send, receive) ciphers
+    async fn accept_handshake(
+        stream: &mut TcpStream,
+        static_key: &Keypair,
+        certificate: &[u8],
+    ) -> (CipherState, CipherState) {
+        let mut state = HandshakeState::new();
+
+        let mut their_ephemeral = [0u8; ELLSWIFT_LEN];
+        stream.read_exact(&mut their_ephemeral).await.unwrap();
+        state.mix_hash(&their_ephemeral);
+        state.decrypt_and_hash(&[]).unwrap();
+        let their_ephemeral = ElligatorSwift::from_array(their_ephemeral);
+
+        let ephemeral = keypair();
+        let our_ephemeral = ElligatorSwift::from_pubkey(ephemeral.public_key());
+        let mut reply = our_ephemeral.to_array().to_vec();
+        state.mix_hash(&our_ephemeral.to_array());
+        state.mix_key(&ellswift_ecdh(
+            their_ephemeral,
+            our_ephemeral,
+            &ephemeral,
+            ElligatorSwiftParty::B,
+        ));
+
+        let our_static = ElligatorSwift::from_pubkey(static_key.public_key());
+        reply.extend(state.encrypt_and_hash(&our_static.to_array()).unwrap());
+        state.mix_key(&ellswift_ecdh(
+            their_ephemeral,
+            our_static,
+            static_key,
+            ElligatorSwiftParty::B,
+        ));
+        reply.extend(state.encrypt_and_hash(certificate).unwrap());
+        stream.write_all(&reply).await.unwrap();
+
+        let (from_client, to_client) = state.split();
+        (to_client, from_client)
+    }
+
+    async fn send_frame(stream: &mut TcpStream, cipher: &mut CipherState, frame: Frame) {
+        let bytes = encrypt_frame(cipher, &frame).unwrap();
+        stream.write_all(&bytes).await.unwrap();
+    }
+
+    // Mock pool: answers setup and channel opening, announces one job and acks shares for it.
+    // Returns every frame the client sent
+    async fn serve_pool(
+        listener: TcpListener,
+        static_key: Keypair,
+        certificate: Vec<u8>,
+    ) -> Vec<Frame> {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (mut encryptor, mut decryptor) =
+            accept_handshake(&mut stream, &static_key, &certificate).await;
+        let mut received = Vec::new();
+
+        while let Ok(frame) = read_frame(&mut stream, &mut decryptor).await {
+            let mut fields = PayloadReader::new(&frame.payload);
+            match frame.msg_type {
+                MSG_SETUP_CONNECTION => {
+                    let payload = PayloadWriter::default().u16(2).u32(0).finish();
+                    send_frame(
+                        &mut stream,
+                        &mut encryptor,
+                        Frame::new(MSG_SETUP_CONNECTION_SUCCESS, payload),
+                    )
+                    .await;
+                }
+                MSG_OPEN_STANDARD_MINING_CHANNEL => {
+                    let mut diff1_target = [0u8; 32];
+                    diff1_target[26] = 0xff;
+                    diff1_target[27] = 0xff;
+                    let payload = PayloadWriter::default()
+                        .u32(fields.u32().unwrap())
+                        .u32(7)
+                        .u256(&diff1_target)
+                        .u8(4)
+                        .u32(0xdeadbeef)
+                        .u32(0)
+                        .finish();
+                    send_frame(
+                        &mut stream,
+                        &mut encryptor,
+                        Frame::new(MSG_OPEN_STANDARD_MINING_CHANNEL_SUCCESS, payload),
+                    )
+                    .await;
+
+                    // A future job, then the prev hash that activates it
+                    let job = PayloadWriter::default()
+                        .u32(7)
+                        .u32(1)
+                        .u8(0)
+                        .u32(0x20000000)
+                        .u256(&[0x11; 32])
+                        .finish();
+                    send_frame(
+                        &mut stream,
+                        &mut encryptor,
+                        Frame::for_channel(MSG_NEW_MINING_JOB, job),
+                    )
+                    .await;
+                    let prev_hash = PayloadWriter::default()
+                        .u32(7)
+                        .u32(1)
+                        .u256(&[0x22; 32])
+                        .u32(0x5f5e1000)
+                        .u32(0x1d00ffff)
+                        .finish();
+                    send_frame(
+                        &mut stream,
+                        &mut encryptor,
+                        Frame::for_channel(MSG_SET_NEW_PREV_HASH, prev_hash),
+                    )
+                    .await;
+                }
+                MSG_SUBMIT_SHARES_STANDARD => {
+                    let channel_id = fields.u32().unwrap();
+                    let sequence_number = fields.u32().unwrap();
+                    let job_id = fields.u32().unwrap();
+                    let reply = if job_id == 1 {
+                        Frame::for_channel(
+                            MSG_SUBMIT_SHARES_SUCCESS,
+                            PayloadWriter::default()
+                                .u32(channel_id)
+                                .u32(sequence_number)
+                                .u32(1)
+                                .u32(1)
+                                .u32(0)
+                                .finish(),
+                        )
+                    } else {
+                        Frame::for_channel(
+                            MSG_SUBMIT_SHARES_ERROR,
+                            PayloadWriter::default()
+                                .u32(channel_id)
+                                .u32(sequence_number)
+                                .str0_255("invalid-job-id")
+                                .finish(),
+                        )
+                    };
+                    send_frame(&mut stream, &mut encryptor, reply).await;
+                }
+                _ => {}
+            }
+            received.push(frame);
+        }
+
+        received
+    }
+
+    #[test]
+    fn test_protocol_name_hash() {
+        // Chaining key every SV2 implementation starts the handshake from
+        let expected: [u8; 32] = [
+            46, 180, 120, 129, 32, 142, 158, 238, 31, 102, 159, 103, 198, 110, 231, 14, 169, 234,
+            136, 9, 13, 80, 63, 232, 48, 220, 75, 200, 62, 41, 191, 16,
+        ];
+        assert_eq!(HandshakeState::new().ck, expected);
+    }
+
+    #[test]
+    fn test_parse_sv2_url() {
+        assert_eq!(
+            parse_sv2_url("stratum2+tcp://sv2.pool.com:34254").unwrap(),
+            ("sv2.pool.com".to_string(), 34254, None)
+        );
+        assert_eq!(
+            parse_sv2_url("stratum2+tcp://sv2.pool.com:34254/authoritykey").unwrap(),
+            (
+                "sv2.pool.com".to_string(),
+                34254,
+                Some("authoritykey".to_string())
+            )
+        );
+        assert!(parse_sv2_url("stratum+tcp://pool.com:3333").is_err());
+        assert!(parse_sv2_url("stratum2+tcp://pool.com").is_err());
+    }
+
+    #[test]
+    fn test_parse_authority_key_formats() {
+        let key = keypair().x_only_public_key().0;
+
+        assert_eq!(
+            parse_authority_key(&hex::encode(key.serialize())).unwrap(),
+            key
+        );
+
+        let mut payload = vec![1, 0];
+        payload.extend_from_slice(&key.serialize());
+        let checksum = Sha256::digest(Sha256::digest(&payload));
+        payload.extend_from_slice(&checksum[..4]);
+        let encoded = bs58::encode(&payload).into_string();
+        assert_eq!(parse_authority_key(&encoded).unwrap(), key);
+
+        let last = payload.len() - 1;
+        payload[last] ^= 0x01;
+        assert!(parse_authority_key(&bs58::encode(&payload).into_string()).is_err());
+        assert!(parse_authority_key("not-a-key").is_err());
+    }
+
+    #[test]
+    fn test_target_to_difficulty() {
+        let mut target = [0u8; 32];
+        target[26] = 0xff;
+        target[27] = 0xff;
+        assert_eq!(target_to_difficulty(&target), 1.0);
+
+        // Halving the target doubles the difficulty
+        target[27] = 0x7f;
+        target[26] = 0xff;
+        assert!((target_to_difficulty(&target) - 2.0).abs() < 0.001);
+    }
+
+    #[tokio::test]
+    async fn test_frames_round_trip_across_chunks() {
+        // Both ends split the same handshake state into matching keys
+        let (mut sender, _) = HandshakeState::new().split();
+        let (mut receiver, _) = HandshakeState::new().split();
+
+        let payload: Vec<u8> = (0..70_000u32).map(|i| i as u8).collect();
+        let frame = Frame::for_channel(MSG_NEW_MINING_JOB, payload.clone());
+        let bytes = encrypt_frame(&mut sender, &frame).unwrap();
+        assert_eq!(bytes.len(), FRAME_HEADER_LEN + payload.len() + 3 * MAC_LEN);
+
+        let decoded = read_frame(&mut bytes.as_slice(), &mut receiver)
+            .await
+            .unwrap();
+        assert_eq!(decoded.extension_type, CHANNEL_MSG_BIT);
+        assert_eq!(decoded.msg_type, MSG_NEW_MINING_JOB);
+        assert_eq!(decoded.payload, payload);
+    }
+
+    #[tokio::test]
+    async fn test_sv2_session_with_mock_pool() {
+        let authority = keypair();
+        let static_key = keypair();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pool = tokio::spawn(serve_pool(
+            listener,
+            static_key,
+            certificate(&static_key, &authority),
+        ));
+
+        let url = format!(
+            "stratum2+tcp://127.0.0.1:{}/{}",
+            port,
+            hex::encode(authority.x_only_public_key().0.serialize())
+        );
+        let client = Sv2Client::from_url(&url, "worker.1").unwrap();
+        client.connect().await.unwrap();
+        assert!(client.is_connected().await);
+        assert_eq!(client.get_stats().await.difficulty, 1.0);
+
+        let job = timeout(Duration::from_secs(5), client.get_next_job())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.job_id, "1");
+        assert_eq!(job.previous_hash, hex::encode([0x22; 32]));
+        assert_eq!(job.merkle_root, Some(hex::encode([0x11; 32])));
+        assert_eq!(job.version, "20000000");
+        assert_eq!(job.nbits, "1d00ffff");
+        assert_eq!(job.ntime, "5f5e1000");
+        assert!(job.clean_jobs);
+        assert_eq!(client.job_version(1).await, Some(0x20000000));
+
+        let accepted = client
+            .submit_share(1, 0xcafebabe, 0x5f5e1000, 0x20000000)
+            .await
+            .unwrap();
+        assert!(accepted.accepted);
+
+        let rejected = client
+            .submit_share(99, 0xcafebabe, 0x5f5e1000, 0x20000000)
+            .await
+            .unwrap();
+        assert!(!rejected.accepted);
+        assert_eq!(rejected.reason.as_deref(), Some("invalid-job-id"));
+
+        let stats = client.get_stats().await;
+        assert_eq!(stats.accepted_shares, 1);
+        assert_eq!(stats.rejected_shares, 1);
+
+        client.disconnect().await.unwrap();
+        let received = timeout(Duration::from_secs(5), pool)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let setup = &received[0];
+        assert_eq!(setup.msg_type, MSG_SETUP_CONNECTION);
+        let mut fields = PayloadReader::new(&setup.payload);
+        assert_eq!(fields.u8().unwrap(), MINING_PROTOCOL);
+        assert_eq!(fields.u16().unwrap(), 2);
+        assert_eq!(fields.u16().unwrap(), 2);
+        assert_eq!(fields.u32().unwrap(), REQUIRES_STANDARD_JOBS);
+
+        let submit = &received[2];
+        assert_eq!(submit.msg_type, MSG_SUBMIT_SHARES_STANDARD);
+        assert_eq!(submit.extension_type, CHANNEL_MSG_BIT);
+        let mut fields = PayloadReader::new(&submit.payload);
+        assert_eq!(fields.u32().unwrap(), 7);
+        assert_eq!(fields.u32().unwrap(), 0);
+        assert_eq!(fields.u32().unwrap(), 1);
+        assert_eq!(fields.u32().unwrap(), 0xcafebabe);
+    }
+
+    #[tokio::test]
+    async fn test_sv2_rejects_certificate_from_other_authority() {
+        let authority = keypair();
+        let impostor = keypair();
+        let static_key = keypair();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve_pool(
+            listener,
+            static_key,
+            certificate(&static_key, &impostor),
+        ));
+
+        let client = Sv2Client::new("127.0.0.1", port, "worker.1")
+            .with_authority_key(&hex::encode(authority.x_only_public_key().0.serialize()));
+        let error = client.connect().await.err().unwrap();
+        assert!(error.to_string().contains("not signed"), "{}", error);
+        assert!(!client.is_connected().await);
+    }
+}
@@ -0,0 +1,222 @@
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+// Hashing threads sleep at most this often, so short bursts keep the machine responsive
+pub const DUTY_PERIOD: Duration = Duration::from_millis(100);
+// Stopping and continuing a process is coarser, so it is cycled more slowly
+pub const PROCESS_DUTY_PERIOD: Duration = Duration::from_millis(500);
+
+// Paces a worker thread to spend `intensity` percent of its time working
+pub struct DutyCycle {
+    working_since: Instant,
+}
+
+impl Default for DutyCycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DutyCycle {
+    pub fn new() -> Self {
+        Self {
+            working_since: Instant::now(),
+        }
+    }
+
+    // Call between units of work. Once the busy share of a period has been used,
+    // sleeps long enough that work made up `intensity` percent of the elapsed time.
+    pub fn pace(&mut self, intensity: u8) {
+        let intensity = intensity.min(100) as u32;
+        if intensity == 100 {
+            self.working_since = Instant::now();
+            return;
+        }
+        if intensity == 0 {
+            std::thread::sleep(DUTY_PERIOD);
+            self.working_since = Instant::now();
+            return;
+        }
+
+        let worked = self.working_since.elapsed();
+        if worked >= DUTY_PERIOD * intensity / 100 {
+            std::thread::sleep(worked * (100 - intensity) / intensity);
+            self.working_since = Instant::now();
+        }
+    }
+}
+
+// Duty-cycles an external process with SIGSTOP/SIGCONT. Dropping the throttle lets
+// the process run freely again.
+pub struct ProcessThrottle {
+    pid: u32,
+    intensity: watch::Sender<u8>,
+    task: JoinHandle<()>,
+}
+
+impl ProcessThrottle {
+    pub fn start(pid: u32, intensity: u8) -> Self {
+        let (sender, receiver) = watch::channel(intensity.min(100));
+        let task = tokio::spawn(Self::cycle(pid, receiver));
+        debug!("Throttling process {} to {}%", pid, intensity);
+        Self {
+            pid,
+            intensity: sender,
+            task,
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn set_intensity(&self, intensity: u8) {
+        let _ = self.intensity.send(intensity.min(100));
+    }
+
+    pub fn intensity(&self) -> u8 {
+        *self.intensity.borrow()
+    }
+
+    async fn cycle(pid: u32, mut intensity: watch::Receiver<u8>) {
+        loop {
+            let current = *intensity.borrow_and_update();
+            let running = PROCESS_DUTY_PERIOD * current as u32 / 100;
+            let stopped = PROCESS_DUTY_PERIOD - running;
+
+            if !running.is_zero() {
+                if !signal(pid, Signal::Continue) {
+                    break;
+                }
+                if stopped.is_zero() {
+                    // Full intensity: leave the process running until told otherwise
+                    if intensity.changed().await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(running) => {}
+                    changed = intensity.changed() => {
+                        if changed.is_err() { break; }
+                        continue;
+                    }
+                }
+            }
+
+            if !signal(pid, Signal::Stop) {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(stopped) => {}
+                changed = intensity.changed() => {
+                    if changed.is_err() { break; }
+                }
+            }
+        }
+        signal(pid, Signal::Continue);
+    }
+}
+
+impl Drop for ProcessThrottle {
+    fn drop(&mut self) {
+        self.task.abort();
+        signal(self.pid, Signal::Continue);
+    }
+}
+
+enum Signal {
+    Stop,
+    Continue,
+}
+
+// False once the process no longer exists
+#[cfg(unix)]
+fn signal(pid: u32, signal: Signal) -> bool {
+    let signal = match signal {
+        Signal::Stop => libc::SIGSTOP,
+        Signal::Continue => libc::SIGCONT,
+    };
+    // SAFETY: kill has no memory-safety preconditions
+    unsafe { libc::kill(pid as libc::pid_t, signal) == 0 }
+}
+
+#[cfg(not(unix))]
+fn signal(pid: u32, _signal: Signal) -> bool {
+    tracing::warn!(
+        "Process throttling is not supported on this platform; process {} runs unthrottled",
+        pid
+    );
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy_fraction(intensity: u8, duration: Duration) -> f64 {
+        let mut duty = DutyCycle::new();
+        let started = Instant::now();
+        let mut busy = Duration::ZERO;
+        while started.elapsed() < duration {
+            let work = Instant::now();
+            while work.elapsed() < Duration::from_millis(5) {
+                std::hint::spin_loop();
+            }
+            busy += work.elapsed();
+            duty.pace(intensity);
+        }
+        busy.as_secs_f64() / started.elapsed().as_secs_f64()
+    }
+
+    #[test]
+    fn test_duty_cycle_follows_intensity() {
+        let full = busy_fraction(100, Duration::from_millis(300));
+        assert!(full > 0.9, "{}", full);
+
+        let quarter = busy_fraction(25, Duration::from_millis(600));
+        assert!((0.15..0.35).contains(&quarter), "{}", quarter);
+    }
+
+    #[cfg(target_os = "linux")]
+    fn cpu_seconds(pid: u32) -> f64 {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+        let fields: Vec<&str> = stat
+            .rsplit(')')
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .collect();
+        // utime and stime, in clock ticks
+        let ticks: u64 = fields[11].parse::<u64>().unwrap() + fields[12].parse::<u64>().unwrap();
+        // SAFETY: sysconf has no memory-safety preconditions
+        ticks as f64 / unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_process_throttle_limits_cpu_time() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "while :; do :; done"])
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+
+        let throttle = ProcessThrottle::start(pid, 20);
+        let before = cpu_seconds(pid);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        let used = cpu_seconds(pid) - before;
+        assert!(used < 0.75, "throttled process used {}s of CPU", used);
+
+        // Dropping the throttle resumes the process
+        drop(throttle);
+        let before = cpu_seconds(pid);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(cpu_seconds(pid) - before > 0.2);
+
+        child.kill().await.unwrap();
+    }
+}
@@ -243,7 +243,10 @@ pub fn validate_pool_url(url: &str) -> Result<bool, AppError> {
     }
 
     // Check for stratum protocol
-    if !url.starts_with("stratum+tcp://") && !url.starts_with("stratum+ssl://") {
+    if !url.starts_with("stratum+tcp://")
+        && !url.starts_with("stratum+ssl://")
+        && !url.starts_with("stratum2+tcp://")
+    {
         return Ok(false);
     }
 
@@ -284,6 +287,31 @@ pub fn validate_mining_config(config: &crate::MiningConfig) -> Result<Vec<String
         errors.push("Invalid pool URL format".to_string());
     }
 
+    // Validate failover pools
+    for (index, pool) in config.pools.iter().enumerate() {
+        if !validate_pool_url(&pool.url)? {
+            errors.push(format!("Invalid URL for failover pool {}", index + 1));
+        }
+    }
+
+    if !(0.0..=1.0).contains(&config.failover.max_reject_ratio) {
+        errors.push("Failover reject ratio must be between 0 and 1".to_string());
+    }
+
+    // Validate algorithm
+    if crate::algorithms::lookup(&config.algorithm).is_err() {
+        errors.push(format!(
+            "Unsupported mining algorithm: {}",
+            config.algorithm
+        ));
+    }
+
+    if let Err(e) =
+        crate::process_limits::ProcessLimits::from_hardware_selection(&config.hardware_selection)
+    {
+        errors.push(e.to_string());
+    }
+
     // Validate wallet address (basic check)
     if config.wallet_address.is_empty() {
         errors.push("Wallet address cannot be empty".to_string());
@@ -409,6 +437,10 @@ mod tests {
         assert!(validate_pool_url("stratum+tcp://pool.example.com:4334").unwrap());
         assert!(validate_pool_url("stratum+tcp://solo.ckpool.org:3333").unwrap());
         assert!(validate_pool_url("stratum+ssl://secure.pool.com:443").unwrap());
+        assert!(validate_pool_url(
+            "stratum2+tcp://sv2.pool.com:34254/9auqWEzQDVyd2oe1JVGFLMLHZLQi8Zb6SRvQVpVFkGa6h5oGhD9"
+        )
+        .unwrap());
 
         // Invalid pool URLs
         assert!(!validate_pool_url("").unwrap());
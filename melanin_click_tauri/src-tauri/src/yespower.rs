@@ -0,0 +1,404 @@
+use crate::AppError;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// pwxform settings that define yespower 1.0
+const PWX_SIMPLE: usize = 2;
+const PWX_GATHER: usize = 4;
+const PWX_ROUNDS: usize = 3;
+const SWIDTH: usize = 11;
+const SALSA20_ROUNDS: usize = 2;
+
+// Each of the three S-boxes holds 2^Swidth * PWXsimple pairs of 32-bit words
+const SBOX_PAIRS: usize = (1 << SWIDTH) * PWX_SIMPLE;
+const SBOX_WORDS: usize = SBOX_PAIRS * 2;
+const SMASK: u32 = (((1 << SWIDTH) - 1) * PWX_SIMPLE * 8) as u32;
+
+pub const WHIVE_PERSONALIZATION: &[u8] =
+    b"Satoshi Nakamoto 31/Oct/2008 Proof-of-work is essentially one-CPU-one-vote";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YespowerParams {
+    pub n: u32,
+    pub r: u32,
+    pub pers: &'static [u8],
+}
+
+// Parameters of Whive's proof of work
+pub const WHIVE: YespowerParams = YespowerParams {
+    n: 2048,
+    r: 32,
+    pers: WHIVE_PERSONALIZATION,
+};
+
+impl YespowerParams {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !(1024..=512 * 1024).contains(&self.n) || !self.n.is_power_of_two() {
+            return Err(AppError::Mining(format!(
+                "Yespower N must be a power of two between 1024 and 524288, got {}",
+                self.n
+            )));
+        }
+        if !(8..=32).contains(&self.r) {
+            return Err(AppError::Mining(format!(
+                "Yespower r must be between 8 and 32, got {}",
+                self.r
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Computes yespower 1.0 of the input; see Yespower for repeated hashing
+pub fn yespower(input: &[u8], params: &YespowerParams) -> Result<[u8; 32], AppError> {
+    Ok(Yespower::new(*params)?.hash(input))
+}
+
+// S-boxes written and read by pwxform, with the write position
+struct Sboxes {
+    s: Vec<u32>,
+    s0: usize,
+    s1: usize,
+    s2: usize,
+    w: usize,
+}
+
+impl Sboxes {
+    fn reset(&mut self) {
+        self.s0 = 0;
+        self.s1 = SBOX_WORDS;
+        self.s2 = 2 * SBOX_WORDS;
+        self.w = 0;
+    }
+
+    fn pwxform(&mut self, x: &mut [u32]) {
+        let (s0, s1) = (self.s0, self.s1);
+        let mut w = self.w;
+
+        for round in 0..PWX_ROUNDS {
+            for j in 0..PWX_GATHER {
+                let lane = j * PWX_SIMPLE * 2;
+                let p0 = s0 + (x[lane] & SMASK) as usize / 4;
+                let p1 = s1 + (x[lane + 1] & SMASK) as usize / 4;
+
+                for k in 0..PWX_SIMPLE {
+                    let word = lane + k * 2;
+                    let box0 = (self.s[p0 + k * 2 + 1] as u64) << 32 | self.s[p0 + k * 2] as u64;
+                    let box1 = (self.s[p1 + k * 2 + 1] as u64) << 32 | self.s[p1 + k * 2] as u64;
+                    let value = (x[word + 1] as u64 * x[word] as u64).wrapping_add(box0) ^ box1;
+                    x[word] = value as u32;
+                    x[word + 1] = (value >> 32) as u32;
+                }
+
+                if round == 0 || j < PWX_GATHER / 2 {
+                    for k in 0..PWX_SIMPLE {
+                        let word = lane + k * 2;
+                        let at = if j & 1 == 1 {
+                            s1 + (w + k) * 2
+                        } else {
+                            s0 + (w + k) * 2
+                        };
+                        self.s[at] = x[word];
+                        self.s[at + 1] = x[word + 1];
+                    }
+                    if j & 1 == 1 {
+                        w += PWX_SIMPLE;
+                    }
+                }
+            }
+        }
+
+        (self.s0, self.s1, self.s2) = (self.s2, s0, s1);
+        self.w = w & (SBOX_PAIRS - 1);
+    }
+
+    // BlockMix with pwxform over 64 byte blocks, then Salsa20 on the last one
+    fn blockmix_pwxform(&mut self, b: &mut [u32], r: usize) {
+        let blocks = 2 * r;
+        let mut x = [0u32; 16];
+        x.copy_from_slice(&b[(blocks - 1) * 16..blocks * 16]);
+
+        for block in b[..blocks * 16].chunks_mut(16) {
+            if blocks > 1 {
+                xor(&mut x, block);
+            }
+            self.pwxform(&mut x);
+            block.copy_from_slice(&x);
+        }
+
+        salsa20(&mut b[(blocks - 1) * 16..blocks * 16]);
+    }
+}
+
+// Reusable yespower hasher; keeps its 128 * r * N byte scratch buffer between hashes
+pub struct Yespower {
+    params: YespowerParams,
+    v: Vec<u32>,
+    x: Vec<u32>,
+    sboxes: Sboxes,
+}
+
+impl Yespower {
+    pub fn new(params: YespowerParams) -> Result<Self, AppError> {
+        params.validate()?;
+        let block_words = 32 * params.r as usize;
+
+        Ok(Self {
+            params,
+            v: vec![0; block_words * params.n as usize],
+            x: vec![0; block_words],
+            sboxes: Sboxes {
+                s: vec![0; 3 * SBOX_WORDS],
+                s0: 0,
+                s1: 0,
+                s2: 0,
+                w: 0,
+            },
+        })
+    }
+
+    pub fn params(&self) -> &YespowerParams {
+        &self.params
+    }
+
+    pub fn hash(&mut self, input: &[u8]) -> [u8; 32] {
+        let r = self.params.r as usize;
+        let prehash = Sha256::digest(input);
+        let mut bytes = pbkdf2_sha256(&prehash, self.params.pers, 128 * r);
+        let digest: [u8; 32] = bytes[..32].try_into().expect("B is at least 32 bytes");
+
+        let mut b: Vec<u32> = bytes
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        self.smix(&mut b);
+        for (chunk, word) in bytes.chunks_mut(4).zip(&b) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        let mut mac = HmacSha256::new_from_slice(&bytes[bytes.len() - 64..])
+            .expect("HMAC accepts keys of any length");
+        mac.update(&digest);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn smix(&mut self, b: &mut [u32]) {
+        let r = self.params.r as usize;
+        let n = self.params.n as usize;
+        let Self { v, x, sboxes, .. } = self;
+
+        // The S-boxes are filled by a Salsa20 SMix over the first 128 bytes
+        sboxes.reset();
+        let sbox_blocks = sboxes.s.len() / 32;
+        smix1(
+            &mut b[..32],
+            1,
+            sbox_blocks,
+            &mut sboxes.s,
+            &mut x[..32],
+            |block, _| blockmix_salsa(block),
+        );
+
+        // Version 1.0 reads and writes V for a third of N, rounded up to even
+        let nloop = (n.div_ceil(3) + 1) & !1;
+        smix1(b, r, n, v, x, |block, r| sboxes.blockmix_pwxform(block, r));
+        smix2(b, r, n, nloop, v, x, sboxes);
+    }
+}
+
+fn xor(target: &mut [u32], other: &[u32]) {
+    for (word, value) in target.iter_mut().zip(other) {
+        *word ^= value;
+    }
+}
+
+// Words are kept in the SIMD-shuffled order of the reference implementation
+fn shuffle(b: &[u32], x: &mut [u32]) {
+    for (block, out) in b.chunks(16).zip(x.chunks_mut(16)) {
+        for (i, word) in out.iter_mut().enumerate() {
+            *word = block[i * 5 % 16];
+        }
+    }
+}
+
+fn unshuffle(x: &[u32], b: &mut [u32]) {
+    for (block, out) in x.chunks(16).zip(b.chunks_mut(16)) {
+        for (i, word) in block.iter().enumerate() {
+            out[i * 5 % 16] = *word;
+        }
+    }
+}
+
+fn integerify(x: &[u32], r: usize) -> usize {
+    x[(2 * r - 1) * 16] as usize
+}
+
+// Maps x into 0..i, favouring the most recently written blocks
+fn wrap(x: usize, i: usize) -> usize {
+    let n = 1 << (usize::BITS - 1 - i.leading_zeros());
+    (x & (n - 1)) + (i - n)
+}
+
+fn smix1(
+    b: &mut [u32],
+    r: usize,
+    n: usize,
+    v: &mut [u32],
+    x: &mut [u32],
+    mut blockmix: impl FnMut(&mut [u32], usize),
+) {
+    let s = 32 * r;
+    shuffle(b, x);
+    for k in 1..r {
+        let (previous, current) = x.split_at_mut(k * 32);
+        current[..32].copy_from_slice(&previous[(k - 1) * 32..]);
+        blockmix(&mut current[..32], 1);
+    }
+
+    for i in 0..n {
+        v[i * s..(i + 1) * s].copy_from_slice(x);
+        if i > 1 {
+            let j = wrap(integerify(x, r), i);
+            xor(x, &v[j * s..(j + 1) * s]);
+        }
+        blockmix(x, r);
+    }
+    unshuffle(x, b);
+}
+
+fn smix2(
+    b: &mut [u32],
+    r: usize,
+    n: usize,
+    nloop: usize,
+    v: &mut [u32],
+    x: &mut [u32],
+    sboxes: &mut Sboxes,
+) {
+    let s = 32 * r;
+    shuffle(b, x);
+    for _ in 0..nloop {
+        let j = integerify(x, r) & (n - 1);
+        xor(x, &v[j * s..(j + 1) * s]);
+        v[j * s..(j + 1) * s].copy_from_slice(x);
+        sboxes.blockmix_pwxform(x, r);
+    }
+    unshuffle(x, b);
+}
+
+fn blockmix_salsa(b: &mut [u32]) {
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[16..32]);
+    for block in b[..32].chunks_mut(16) {
+        xor(&mut x, block);
+        salsa20(&mut x);
+        block.copy_from_slice(&x);
+    }
+}
+
+fn salsa20(b: &mut [u32]) {
+    let mut x = [0u32; 16];
+    for (i, word) in b.iter().enumerate() {
+        x[i * 5 % 16] = *word;
+    }
+
+    let quarter = |x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize| {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    };
+    for _ in 0..SALSA20_ROUNDS / 2 {
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 5, 9, 13, 1);
+        quarter(&mut x, 10, 14, 2, 6);
+        quarter(&mut x, 15, 3, 7, 11);
+        quarter(&mut x, 0, 1, 2, 3);
+        quarter(&mut x, 5, 6, 7, 4);
+        quarter(&mut x, 10, 11, 8, 9);
+        quarter(&mut x, 15, 12, 13, 14);
+    }
+
+    for (i, word) in b.iter_mut().enumerate() {
+        *word = word.wrapping_add(x[i * 5 % 16]);
+    }
+}
+
+// PBKDF2-HMAC-SHA256 with a single iteration, as yespower uses it
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], length: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(length);
+    for block in 1u32.. {
+        if output.len() >= length {
+            break;
+        }
+        let mut mac =
+            HmacSha256::new_from_slice(password).expect("HMAC accepts keys of any length");
+        mac.update(salt);
+        mac.update(&block.to_be_bytes());
+        output.extend_from_slice(&mac.finalize().into_bytes());
+    }
+    output.truncate(length);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference_input() -> Vec<u8> {
+        (0..80u32).map(|i| (i * 3) as u8).collect()
+    }
+
+    #[test]
+    fn test_whive_known_answers() {
+        let mut hasher = Yespower::new(WHIVE).unwrap();
+        assert_eq!(
+            hex::encode(hasher.hash(&reference_input())),
+            "3e132ee33d9eb900c1782e4b67beb9722b36a734ab7c80f16eb005b54eeb034c"
+        );
+        assert_eq!(
+            hex::encode(hasher.hash(&[0u8; 80])),
+            "170ac3e8187d4492779bb71ab2e7790f5677329af64851f7f5f9a83b462498eb"
+        );
+        assert_eq!(
+            hex::encode(hasher.hash(b"abc")),
+            "7656648490836c7fd5956fd6cfc96b9e63c5260f21161ace1550412c68132b2f"
+        );
+    }
+
+    #[test]
+    fn test_unpersonalized_known_answers() {
+        let cases = [
+            (
+                1024,
+                8,
+                "c98f319ea7df5e7fd0d8aca4ac14f7692a40f4886315e24209285a42949123fd",
+            ),
+            (
+                4096,
+                16,
+                "33fb8f063824a4a020f63dca535f5ca66ab5576468c75d1ccaac7542f76495ac",
+            ),
+            (
+                2048,
+                32,
+                "d5efb813cd263e9b34540130233cbbc6a921fbff3431e5ec1a1abde2aea6ff4d",
+            ),
+        ];
+        for (n, r, expected) in cases {
+            let params = YespowerParams { n, r, pers: b"" };
+            let hash = yespower(&reference_input(), &params).unwrap();
+            assert_eq!(hex::encode(hash), expected, "N={} r={}", n, r);
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_params() {
+        for (n, r) in [(1000, 8), (512, 8), (2048, 4), (2048, 33)] {
+            let params = YespowerParams { n, r, pers: b"" };
+            assert!(Yespower::new(params).is_err(), "N={} r={}", n, r);
+        }
+    }
+}
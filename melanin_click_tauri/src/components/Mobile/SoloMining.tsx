@@ -18,20 +18,40 @@ interface SoloMiningStats {
   current_block_height: number
   difficulty: number
   network_hashrate: string
+  local_hashrate: string
   blocks_found: number
   mining_address: string
 }
 
+interface TemplateTransaction {
+  data: string
+  txid: string
+  hash: string
+  depends: number[]
+  fee: number | null
+  sigops: number | null
+  weight: number | null
+}
+
 interface BlockTemplate {
   version: number
+  rules: string[]
   previous_block_hash: string
-  transactions: string[]
+  transactions: TemplateTransaction[]
+  coinbase_aux: Record<string, string>
   coinbase_value: number
+  longpoll_id: string | null
   target: string
   min_time: number
+  mutable: string[]
+  nonce_range: string
+  sigop_limit: number | null
+  size_limit: number | null
+  weight_limit: number | null
   cur_time: number
   bits: string
   height: number
+  default_witness_commitment: string | null
 }
 
 export default function SoloMining() {
@@ -295,6 +315,10 @@ export default function SoloMining() {
               <span className="text-gray-400">Blocks Found:</span>
               <span className="text-white ml-2">{stats.blocks_found}</span>
             </div>
+            <div className="col-span-2">
+              <span className="text-gray-400">Your Hashrate:</span>
+              <span className="text-white ml-2">{stats.local_hashrate}</span>
+            </div>
             <div className="col-span-2">
               <span className="text-gray-400">Network Hashrate:</span>
               <span className="text-white ml-2">{stats.network_hashrate}</span>
@@ -14,6 +14,38 @@ interface MiningStats {
   last_update: string;
 }
 
+interface RestartPolicy {
+  mode: 'never' | 'on-failure' | 'always';
+  max_retries: number;
+  window_secs: number;
+  backoff_secs: number;
+  max_backoff_secs: number;
+}
+
+interface MiningSession {
+  mining_type: string;
+  algorithm: string;
+  pool_url: string;
+  worker_address: string;
+  threads: number;
+  intensity: number;
+  started_at: string;
+  restarts: number;
+  running: boolean;
+  restart_policy: RestartPolicy;
+  last_failure: string | null;
+  stats: MiningStats;
+}
+
+// Delivered live through the 'process-log' event as well
+interface LogLine {
+  seq: number;
+  process: string;
+  stream: 'stdout' | 'stderr';
+  line: string;
+  timestamp: string;
+}
+
 interface SystemInfo {
   platform: string;
   architecture: string;
@@ -34,6 +66,10 @@ interface NodeStatus {
   network: string;
   data_dir: string;
   config_path: string;
+  headers: number;
+  mempool_size: number;
+  uptime_seconds: number;
+  rpc_error: string | null;
 }
 
 interface MiningPool {
@@ -97,13 +133,17 @@ export class TauriService {
     whiveAddress: string,
     threads?: number,
     intensity?: number,
-    poolUrl?: string
+    poolUrl?: string,
+    algorithm?: string,
+    autotune?: boolean
   ): Promise<string> {
     return await invoke('start_enhanced_whive_mining', {
       whiveAddress,
       threads,
       intensity,
       poolUrl,
+      algorithm,
+      autotune,
     });
   }
 
@@ -127,10 +167,33 @@ export class TauriService {
     return await invoke('stop_mining', { miningType: miningType || 'bitcoin' });
   }
 
+  static async setMiningIntensity(miningType: string, intensity: number): Promise<string> {
+    return await invoke('set_mining_intensity', { miningType, intensity });
+  }
+
   static async getMiningStatus(miningType: string): Promise<MiningStats | null> {
     return await invoke('get_mining_status', { miningType });
   }
 
+  static async restartMining(miningType: string): Promise<string> {
+    return await invoke('restart_mining', { miningType });
+  }
+
+  static async getMiningSessions(): Promise<MiningSession[]> {
+    return await invoke('get_mining_sessions');
+  }
+
+  static async getProcessLogs(
+    process: string,
+    options: { lines?: number; pattern?: string; stream?: 'stdout' | 'stderr'; sinceSeq?: number } = {}
+  ): Promise<LogLine[]> {
+    return await invoke('get_process_logs', { process, ...options });
+  }
+
+  static async listLoggedProcesses(): Promise<string[]> {
+    return await invoke('list_logged_processes');
+  }
+
   static async getMiningPools(): Promise<MiningPool[]> {
     return await invoke('get_mining_pools');
   }
@@ -55,6 +55,7 @@ export interface MiningConfig {
   wallet_address: string;
   worker_name: string;
   mining_intensity: number;
+  // Also takes process limits for miners: 'cpu:0-3', 'nice:10', 'memory:2048', 'io:idle'
   hardware_selection: string[];
   algorithm: string;
   auto_start: boolean;